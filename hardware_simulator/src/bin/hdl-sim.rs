@@ -0,0 +1,559 @@
+use hardware_simulator::catalog::{project_interfaces, to_html, to_markdown};
+use hardware_simulator::deps::{project_dependencies, Dependencies};
+use hardware_simulator::diagnostics::{check_hdl_with, use_color, Severity};
+use hardware_simulator::grade::{grade_batch, grade_with};
+use hardware_simulator::image::{read_image, write_image};
+use hardware_simulator::manifest::{Manifest, MANIFEST};
+use hardware_simulator::model::dialect::Dialect;
+use hardware_simulator::model::diff::diff_files;
+use hardware_simulator::model::preprocess::preprocess;
+use hardware_simulator::scaffold::{new_project, scaffold_tests_with};
+use hardware_simulator::test_script::{
+    fault_coverage, find_golden, parse_script, run_golden, toggle_coverage, Limits, TestRunner,
+};
+use hardware_simulator::trace::diff_traces;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+const USAGE: &str = "\
+Usage: hdl-sim grade <project-dir> [options]
+       hdl-sim golden <project-dir> <golden-dir>
+       hdl-sim faults <script>
+       hdl-sim toggles <script>
+       hdl-sim explain <script> <pin> [bit]
+       hdl-sim wavediff <script> <other-script> [--context <steps>]
+       hdl-sim check <hdl-file>... [--extended] [--format json|text]
+       hdl-sim convert <image> <other-image>
+       hdl-sim deps <project-dir> [--extended] [--format dot|json|text]
+       hdl-sim ifdiff <hdl-file> <other-hdl-file> [--extended]
+       hdl-sim new <project> [<dir>]
+       hdl-sim catalog <project-dir> [--extended] [--format markdown|html]
+       hdl-sim scaffold <hdl-file> [--extended]
+
+Runs every .tst file in the project directory and prints a report. Exits with an error if any
+script fails. If the directory holds an hdl.toml manifest, the scripts are taken from the test
+directories it lists, and their chips are loaded as it says.
+
+Options:
+    --format json|junit|table   The format of the report. Defaults to json, or table with --batch
+    --output <file>             Writes the report to a file instead of printing it
+    --batch                     Grades every subdirectory as a separate submission
+    --jobs <n>                  How many submissions are graded at the same time
+    --cycle-limit <n>           Stops scripts after this many clock cycles
+    --time-limit <seconds>      Stops scripts after running for this long
+
+The golden command runs every script which has an output file of the same name in the golden
+directory, and prints each line of its table which differs from the stored one.
+
+The faults command reruns a script with every single stuck-at fault in its chip, and prints the
+share of faults which made it fail along with those which did not.
+
+The toggles command runs a script and prints every bit of a pin in its chip which was never seen
+both low and high.
+
+The explain command runs a script and prints every signal which a bit of a pin depends on at the
+end of it, along with its value. The pin is an output of the chip, or a pin of a part such as
+`Mux0.out`.
+
+The wavediff command runs two scripts, such as the same script next to two versions of a chip,
+records the pins of their chips after every eval, tick and tock, and prints where they first
+differ.
+
+The check command prints every error and warning about the chips in the files, along with the
+lines they point at. Colors are left out if NO_COLOR is set. With --format json, they are printed
+as a JSON array of diagnostics with a code, a severity, a message, a file and a byte range. With
+--extended, the files are read in the extended dialect of HDL. Exits with an error if there are
+any errors.
+
+The convert command reads a memory image, such as a program for the ROM32K, and writes it in the
+format of the other file. Files ending in .bin hold two bytes for each word, most significant
+first, files ending in .hex hold the same bytes as Intel HEX, and any other file is read and
+written as the text of a .hack file.
+
+The deps command prints which chips of the project use which others as parts, the parts which are
+neither builtins nor defined in the project, and the chips which no other chip uses. With
+--format dot, it prints a graph for Graphviz instead, and with --format json, a JSON object. Exits
+with an error if any part is missing or any file could not be parsed.
+
+The ifdiff command compares the pins of the chips in two HDL files, such as two revisions of the
+same file, and prints every pin which was added, removed, or changed in width, direction or
+clocking. Exits with an error if they differ.
+
+The new command starts a project of the course, such as project03, in a directory of the same
+name unless another is given. It writes a stub HDL file for each chip of the project, with the
+interface of the course and no parts, and a test script which drives its pins. Existing files are
+never overwritten.
+
+The catalog command prints the documentation of every chip of the project, taken from the
+/** ... */ comment before its CHIP declaration, along with its pins, as Markdown, or as an HTML
+page with --format html.
+
+The scaffold command writes a test script for the chip in an HDL file next to it, which outputs
+every pin and sets every input once, along with an empty comparison file to fill in. Existing
+files are never overwritten.
+
+The check, deps, ifdiff, catalog and scaffold commands read the files in the dialect of the
+hdl.toml manifest in or above their directory, and check and scaffold build chips with its
+builtins. The deps and catalog commands read the chips from the directories it lists. --extended
+reads the files in the extended dialect whatever the manifest says.";
+
+enum ReportFormat {
+    Json,
+    Junit,
+    Table,
+}
+
+struct GradeArgs {
+    dir: PathBuf,
+    format: Option<ReportFormat>,
+    output: Option<PathBuf>,
+    batch: bool,
+    jobs: usize,
+    limits: Limits,
+}
+
+fn parse_grade_args(mut args: impl Iterator<Item = String>) -> Result<GradeArgs, String> {
+    let mut grade_args = GradeArgs {
+        dir: PathBuf::new(),
+        format: None,
+        output: None,
+        batch: false,
+        jobs: std::thread::available_parallelism().map_or(1, |x| x.get()),
+        limits: Limits::default(),
+    };
+    let mut dir = None;
+    let number = |args: &mut dyn Iterator<Item = String>, name: &str| {
+        args.next()
+            .and_then(|x| x.parse::<f64>().ok())
+            .ok_or(format!("{name} needs a number"))
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                grade_args.format = Some(match args.next().as_deref() {
+                    Some("json") => ReportFormat::Json,
+                    Some("junit") => ReportFormat::Junit,
+                    Some("table") => ReportFormat::Table,
+                    other => return Err(format!("Unknown report format {other:?}")),
+                })
+            }
+            "--output" => {
+                grade_args.output = Some(PathBuf::from(
+                    args.next().ok_or("--output needs a file name")?,
+                ))
+            }
+            "--batch" => grade_args.batch = true,
+            "--jobs" => grade_args.jobs = number(&mut args, "--jobs")? as usize,
+            "--cycle-limit" => {
+                grade_args.limits.cycles = Some(number(&mut args, "--cycle-limit")? as usize)
+            }
+            "--time-limit" => {
+                grade_args.limits.time =
+                    Some(Duration::from_secs_f64(number(&mut args, "--time-limit")?))
+            }
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument `{arg}`")),
+        }
+    }
+    grade_args.dir = dir.ok_or("No project directory given")?;
+    Ok(grade_args)
+}
+
+fn run_grade(args: GradeArgs) -> Result<bool, String> {
+    let read_error = |e| format!("Could not read {:?}: {e}", args.dir);
+    let (text, passed) = if args.batch {
+        let report = grade_batch(&args.dir, args.limits, args.jobs).map_err(read_error)?;
+        let text = match args.format.unwrap_or(ReportFormat::Table) {
+            ReportFormat::Json => report.to_json() + "\n",
+            ReportFormat::Junit => report.to_junit(),
+            ReportFormat::Table => report.summary(),
+        };
+        (text, report.failed() == 0)
+    } else {
+        let report = grade_with(&args.dir, args.limits).map_err(read_error)?;
+        let text = match args.format.unwrap_or(ReportFormat::Json) {
+            ReportFormat::Json => report.to_json() + "\n",
+            ReportFormat::Junit => report.to_junit(),
+            ReportFormat::Table => report.summary(),
+        };
+        (text, report.failed() == 0)
+    };
+    match args.output {
+        Some(path) => {
+            std::fs::write(&path, text).map_err(|e| format!("Could not write {path:?}: {e}"))?
+        }
+        None => print!("{text}"),
+    }
+    Ok(passed)
+}
+
+fn run_golden_dir(mut args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let (Some(dir), Some(golden), None) = (args.next(), args.next(), args.next()) else {
+        return Err(USAGE.to_string());
+    };
+    let scripts = find_golden(dir.as_ref(), golden.as_ref())
+        .map_err(|e| format!("Could not read {dir:?}: {e}"))?;
+    let mut passed = true;
+    for (script, golden) in scripts {
+        match run_golden(&script, &golden) {
+            Ok(diffs) if diffs.is_empty() => println!("{}: ok", script.display()),
+            Ok(diffs) => {
+                passed = false;
+                println!("{}: {} lines differ", script.display(), diffs.len());
+                for diff in diffs {
+                    println!("    {diff}");
+                }
+            }
+            Err(e) => {
+                passed = false;
+                println!("{}: {e}", script.display());
+            }
+        }
+    }
+    Ok(passed)
+}
+
+fn run_faults(mut args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let (Some(script), None) = (args.next(), args.next()) else {
+        return Err(USAGE.to_string());
+    };
+    let limits = Limits {
+        time: Some(Duration::from_secs(10)),
+        ..Limits::default()
+    };
+    let report = fault_coverage(&script, limits).map_err(|e| format!("{script}:{e}"))?;
+    println!(
+        "{} of {} faults detected ({:.1}%)",
+        report.detected.len(),
+        report.detected.len() + report.undetected.len(),
+        report.coverage() * 100.0
+    );
+    for fault in report.undetected.iter() {
+        println!("    not detected: {fault}");
+    }
+    Ok(report.undetected.is_empty())
+}
+
+fn run_toggles(mut args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let (Some(script), None) = (args.next(), args.next()) else {
+        return Err(USAGE.to_string());
+    };
+    let toggles =
+        toggle_coverage(&script, Limits::default()).map_err(|e| format!("{script}:{e}"))?;
+    println!("{:.1}% of pin bits toggled", toggles.coverage() * 100.0);
+    print!("{}", toggles.summary());
+    Ok(toggles.untoggled().is_empty())
+}
+
+fn run_explain(mut args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let (Some(script), Some(pin)) = (args.next(), args.next()) else {
+        return Err(USAGE.to_string());
+    };
+    let bit = match args.next() {
+        Some(bit) => bit
+            .parse()
+            .map_err(|_| format!("`{bit}` is not a bit index"))?,
+        None => 0,
+    };
+    let source = std::fs::read_to_string(&script).map_err(|e| format!("{script}: {e}"))?;
+    let parsed = parse_script(&source).map_err(|e| format!("{script}:{e}"))?;
+    let dir = Path::new(&script).parent().unwrap_or(Path::new("."));
+    let mut runner = TestRunner::new(dir).without_output_file();
+    let result = runner.run(&parsed);
+    let simulator = runner
+        .simulator()
+        .ok_or(format!("{script} does not load a chip"))?;
+    let influences = simulator
+        .chip()
+        .explain(&pin, bit)
+        .ok_or(format!("The chip has no pin `{pin}` with bit {bit}"))?;
+    if let Err(e) = result {
+        println!("The script stopped at {e}");
+    }
+    for influence in influences {
+        println!("{}{influence}", "  ".repeat(influence.depth));
+    }
+    Ok(true)
+}
+
+fn run_wavediff(args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let mut scripts = Vec::new();
+    let mut context = 3;
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--context" => {
+                context = args
+                    .next()
+                    .and_then(|x| x.parse().ok())
+                    .ok_or("--context needs a number")?
+            }
+            _ => scripts.push(arg),
+        }
+    }
+    let [left, right] = scripts.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+    let trace = |script: &String| {
+        let source = std::fs::read_to_string(script).map_err(|e| format!("{script}: {e}"))?;
+        let parsed = parse_script(&source).map_err(|e| format!("{script}:{e}"))?;
+        let dir = Path::new(script).parent().unwrap_or(Path::new("."));
+        let mut runner = TestRunner::new(dir).without_output_file().with_trace();
+        if let Err(e) = runner.run(&parsed) {
+            println!("{script} stopped at {e}");
+        }
+        Ok::<_, String>(runner.trace().cloned().unwrap_or_default())
+    };
+    match diff_traces(&trace(left)?, &trace(right)?, context) {
+        Some(divergence) => {
+            print!("{divergence}");
+            Ok(false)
+        }
+        None => {
+            println!("The traces are the same");
+            Ok(true)
+        }
+    }
+}
+
+fn run_check(args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let mut files = Vec::new();
+    let mut extended = false;
+    let mut json = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extended" => extended = true,
+            "--format" => {
+                json = match args.next().as_deref() {
+                    Some("json") => true,
+                    Some("text") => false,
+                    other => return Err(format!("Unknown diagnostics format {other:?}")),
+                }
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if files.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let mut diagnostics = Vec::new();
+    let mut dialects = Vec::new();
+    for file in files.iter() {
+        let manifest = find_manifest(file, extended)?;
+        let found = check_hdl_with(file, &manifest);
+        dialects.extend(found.iter().map(|_| manifest.dialect));
+        diagnostics.extend(found);
+    }
+    if json {
+        let json = diagnostics
+            .iter()
+            .map(|x| x.to_json())
+            .collect::<Vec<_>>()
+            .join(",");
+        println!("[{json}]");
+    } else {
+        let color = use_color();
+        for (diagnostic, dialect) in diagnostics.iter().zip(dialects) {
+            // ranges in the extended dialect refer to the preprocessed text
+            let source = std::fs::read_to_string(&diagnostic.file).unwrap_or_default();
+            let source = match dialect {
+                Dialect::Strict => source,
+                Dialect::Extended => preprocess(&source, &diagnostic.file).unwrap_or(source),
+            };
+            println!("{}", diagnostic.render(&source, color));
+        }
+    }
+    Ok(diagnostics.iter().all(|x| x.severity != Severity::Error))
+}
+
+fn run_convert(mut args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let (Some(input), Some(output), None) = (args.next(), args.next(), args.next()) else {
+        return Err(USAGE.to_string());
+    };
+    let words = read_image(&input).map_err(|e| format!("{input}: {e}"))?;
+    write_image(&output, &words).map_err(|e| format!("{output}: {e}"))?;
+    Ok(true)
+}
+
+fn run_deps(args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let mut dir = None;
+    let mut extended = false;
+    let mut format = "text".to_string();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extended" => extended = true,
+            "--format" => format = args.next().unwrap_or_default(),
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument `{arg}`")),
+        }
+    }
+    let dir: PathBuf = dir.ok_or(USAGE)?;
+    let manifest = find_manifest(&dir, extended)?;
+    let mut dependencies = Dependencies::default();
+    for dir in manifest.chips.iter() {
+        let found = project_dependencies(dir, manifest.dialect)
+            .map_err(|e| format!("Could not read {dir:?}: {e}"))?;
+        dependencies.chips.extend(found.chips);
+        dependencies.unparsed.extend(found.unparsed);
+    }
+    match format.as_str() {
+        "text" => print!("{}", dependencies.summary()),
+        "dot" => print!("{}", dependencies.to_dot()),
+        "json" => println!("{}", dependencies.to_json()),
+        other => return Err(format!("Unknown dependency format {other:?}")),
+    }
+    Ok(dependencies.missing().is_empty() && dependencies.unparsed.is_empty())
+}
+
+fn run_ifdiff(args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let mut files = Vec::new();
+    let mut extended = false;
+    for arg in args {
+        match arg.as_str() {
+            "--extended" => extended = true,
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    let [before, after] = &files[..] else {
+        return Err(USAGE.to_string());
+    };
+    let dialect = find_manifest(before, extended)?.dialect;
+    let changes = diff_files(before, after, dialect).map_err(|e| format!("{e}"))?;
+    if changes.is_empty() {
+        println!("The interfaces are the same");
+    }
+    for change in changes.iter() {
+        println!("{change}");
+    }
+    Ok(changes.is_empty())
+}
+
+fn run_new(mut args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let Some(project) = args.next() else {
+        return Err(USAGE.to_string());
+    };
+    let number = project
+        .strip_prefix("project")
+        .unwrap_or(&project)
+        .parse::<u8>()
+        .map_err(|_| format!("`{project}` is not a project of the course"))?;
+    let dir = args.next().map_or_else(
+        || PathBuf::from(format!("project{number:02}")),
+        PathBuf::from,
+    );
+    for path in new_project(number, &dir).map_err(|e| e.to_string())? {
+        println!("{}", path.display());
+    }
+    Ok(true)
+}
+
+fn run_catalog(args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let mut dir = None;
+    let mut extended = false;
+    let mut html = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--extended" => extended = true,
+            "--format" => {
+                html = match args.next().as_deref() {
+                    Some("html") => true,
+                    Some("markdown") => false,
+                    other => return Err(format!("Unknown catalog format {other:?}")),
+                }
+            }
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument `{arg}`")),
+        }
+    }
+    let dir: PathBuf = dir.ok_or(USAGE)?;
+    let manifest = find_manifest(&dir, extended)?;
+    let mut interfaces = Vec::new();
+    for dir in manifest.chips.iter() {
+        interfaces.extend(
+            project_interfaces(dir, manifest.dialect)
+                .map_err(|e| format!("Could not read {dir:?}: {e}"))?,
+        );
+    }
+    if html {
+        print!("{}", to_html(&interfaces));
+    } else {
+        print!("{}", to_markdown(&interfaces));
+    }
+    Ok(true)
+}
+
+fn run_scaffold(args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let mut files = Vec::new();
+    let mut extended = false;
+    for arg in args {
+        match arg.as_str() {
+            "--extended" => extended = true,
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    let [hdl] = &files[..] else {
+        return Err(USAGE.to_string());
+    };
+    let manifest = find_manifest(hdl, extended)?;
+    let scaffolded =
+        scaffold_tests_with(hdl, &manifest).map_err(|e| format!("{}: {e}", hdl.display()))?;
+    for path in scaffolded {
+        println!("{}", path.display());
+    }
+    Ok(true)
+}
+
+/// The manifest of the project holding a file or directory, which is the first one found in its
+/// directory or above it, or the defaults for its directory if there is none. With `extended`,
+/// the files are read in the extended dialect whatever the manifest says.
+fn find_manifest(path: &Path, extended: bool) -> Result<Manifest, String> {
+    let dir = match path.is_dir() {
+        true => path,
+        false => path.parent().unwrap_or(Path::new("")),
+    };
+    let mut manifest = None;
+    for dir in dir.ancestors() {
+        manifest =
+            Manifest::find(dir).map_err(|e| format!("{}: {e}", dir.join(MANIFEST).display()))?;
+        if manifest.is_some() {
+            break;
+        }
+    }
+    let mut manifest = manifest.unwrap_or_else(|| Manifest::new(dir));
+    if extended {
+        manifest.dialect = Dialect::Extended;
+    }
+    Ok(manifest)
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("grade") => parse_grade_args(args).and_then(run_grade),
+        Some("golden") => run_golden_dir(args),
+        Some("faults") => run_faults(args),
+        Some("toggles") => run_toggles(args),
+        Some("explain") => run_explain(args),
+        Some("wavediff") => run_wavediff(args),
+        Some("check") => run_check(args),
+        Some("convert") => run_convert(args),
+        Some("deps") => run_deps(args),
+        Some("ifdiff") => run_ifdiff(args),
+        Some("new") => run_new(args),
+        Some("catalog") => run_catalog(args),
+        Some("scaffold") => run_scaffold(args),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -1,11 +1,130 @@
+use std::fmt::{Display, Formatter};
+use std::ops::{Range, RangeInclusive};
+
+/// The bits of a bus from `start` to `end`, both included. The start is never after the end, so a
+/// range always covers at least one bit.
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct BusRange {
-    pub start: u16,
-    pub end: u16,
+    start: u16,
+    end: u16,
 }
 
 impl BusRange {
-    pub unsafe fn size(&self) -> u16 {
-        self.end - self.start
+    /// The bits from `start` to `end`, or `None` if `start` is after `end`
+    pub fn new(start: u16, end: u16) -> Option<Self> {
+        (start <= end).then_some(Self { start, end })
+    }
+
+    /// The `width` bits from `start`, or `None` if there are none or they do not fit in a `u16`
+    pub fn with_width(start: u16, width: u16) -> Option<Self> {
+        Self::new(start, start.checked_add(width.checked_sub(1)?)?)
+    }
+
+    /// The single bit at `index`
+    pub fn bit(index: u16) -> Self {
+        Self {
+            start: index,
+            end: index,
+        }
+    }
+
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    pub fn end(&self) -> u16 {
+        self.end
+    }
+
+    /// The number of bits covered by the range
+    pub fn width(&self) -> u16 {
+        self.end - self.start + 1
+    }
+
+    pub fn contains(&self, bit: u16) -> bool {
+        self.start <= bit && bit <= self.end
+    }
+
+    /// Whether the two ranges have a bit in common
+    pub fn overlaps(&self, other: &BusRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// The bits at `relative` within this range, counting from its start, such as the bits of
+    /// `in[2..3]` when `in` is the range `16..31`. Returns `None` if `relative` goes past the end.
+    pub fn slice(&self, relative: &BusRange) -> Option<BusRange> {
+        (relative.end < self.width()).then(|| BusRange {
+            start: self.start + relative.start,
+            end: self.start + relative.end,
+        })
+    }
+
+    /// The bits of the range, from the start
+    pub fn iter(&self) -> RangeInclusive<u16> {
+        self.start..=self.end
+    }
+
+    /// The bits of the range as indices, such as into the values of the pins of a chip
+    pub fn indices(&self) -> Range<usize> {
+        self.start as usize..self.end as usize + 1
+    }
+}
+
+impl Display for BusRange {
+    /// The range as it is written in HDL, such as `[3..4]`, or `[3]` for a single bit
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "[{}]", self.start)
+        } else {
+            write!(f, "[{}..{}]", self.start, self.end)
+        }
+    }
+}
+
+impl IntoIterator for &BusRange {
+    type Item = u16;
+    type IntoIter = RangeInclusive<u16>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bus_range() {
+        assert_eq!(BusRange::new(3, 2), None);
+        let range = BusRange::new(16, 31).unwrap();
+        assert_eq!(range.width(), 16);
+        assert_eq!(BusRange::with_width(16, 16), Some(range.clone()));
+        assert_eq!(BusRange::with_width(16, 0), None);
+        assert_eq!(BusRange::with_width(u16::MAX, 2), None);
+        assert_eq!(BusRange::bit(4).width(), 1);
+
+        assert!(range.contains(16) && range.contains(31));
+        assert!(!range.contains(15) && !range.contains(32));
+        assert!(range.overlaps(&BusRange::new(0, 16).unwrap()));
+        assert!(!range.overlaps(&BusRange::new(0, 15).unwrap()));
+
+        assert_eq!(
+            range.slice(&BusRange::new(2, 3).unwrap()),
+            BusRange::new(18, 19)
+        );
+        assert_eq!(
+            range.slice(&BusRange::new(15, 15).unwrap()),
+            BusRange::new(31, 31)
+        );
+        assert_eq!(range.slice(&BusRange::new(8, 16).unwrap()), None);
+
+        assert_eq!(
+            BusRange::new(2, 4).unwrap().into_iter().collect::<Vec<_>>(),
+            [2, 3, 4]
+        );
+        assert_eq!(BusRange::new(2, 4).unwrap().indices(), 2..5);
+        assert_eq!(BusRange::new(2, 4).unwrap().to_string(), "[2..4]");
+        assert_eq!(BusRange::bit(7).to_string(), "[7]");
     }
 }
@@ -0,0 +1,125 @@
+//! A catalog of the chips of a project, with the documentation comment and the pins of each, as
+//! Markdown or HTML.
+
+use crate::grade::escape_xml;
+use crate::model::dialect::Dialect;
+use crate::model::diff::read_interface;
+use crate::model::Interface;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The interfaces of the chips in the HDL files of a directory, in the order of their names.
+/// Files which cannot be read or parsed are left out.
+pub fn project_interfaces(dir: &Path, dialect: Dialect) -> io::Result<Vec<Interface>> {
+    let mut interfaces = fs::read_dir(dir)?
+        .filter_map(|x| Some(x.ok()?.path()))
+        .filter(|x| x.extension().is_some_and(|x| x == "hdl"))
+        .filter_map(|x| read_interface(&x, dialect).ok())
+        .collect::<Vec<_>>();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(interfaces)
+}
+
+fn clocked(interface: &Interface, pin: &str) -> &'static str {
+    if interface.seq_in.contains_key(pin) || interface.seq_out.contains_key(pin) {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+pub fn to_markdown(interfaces: &[Interface]) -> String {
+    let mut markdown = String::from("# Chips\n");
+    for interface in interfaces {
+        write!(markdown, "\n## {}\n\n", interface.name).unwrap();
+        if let Some(doc) = &interface.doc {
+            write!(markdown, "{doc}\n\n").unwrap();
+        }
+        markdown.push_str("| Pin | Direction | Width | Clocked |\n");
+        markdown.push_str("|-----|-----------|-------|---------|\n");
+        for pin in interface.pins() {
+            writeln!(
+                markdown,
+                "| `{}` | {} | {} | {} |",
+                pin.name,
+                pin.direction,
+                pin.width,
+                clocked(interface, pin.name)
+            )
+            .unwrap();
+        }
+    }
+    markdown
+}
+
+pub fn to_html(interfaces: &[Interface]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Chips</title></head>\n\
+         <body>\n<h1>Chips</h1>\n",
+    );
+    for interface in interfaces {
+        let name = escape_xml(&interface.name);
+        writeln!(html, "<h2 id=\"{name}\">{name}</h2>").unwrap();
+        if let Some(doc) = &interface.doc {
+            writeln!(html, "<pre>{}</pre>", escape_xml(doc)).unwrap();
+        }
+        html.push_str(
+            "<table>\n<tr><th>Pin</th><th>Direction</th><th>Width</th><th>Clocked</th></tr>\n",
+        );
+        for pin in interface.pins() {
+            writeln!(
+                html,
+                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_xml(pin.name),
+                pin.direction,
+                pin.width,
+                clocked(interface, pin.name)
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        let dir = std::env::current_dir().unwrap().join("../test_files");
+        let interfaces = project_interfaces(&dir, Dialect::Strict).unwrap();
+        let names = interfaces.iter().map(|x| &*x.name).collect::<Vec<_>>();
+        assert_eq!(names[..3], ["And", "And16", "Bit"]);
+        // the exercises with no parts are listed as well
+        assert!(names.contains(&"Xor"));
+
+        let markdown = to_markdown(&interfaces[..1]);
+        assert_eq!(
+            markdown,
+            "\
+# Chips
+
+## And
+
+And gate:
+out = 1 if (a == 1 and b == 1)
+      0 otherwise
+
+| Pin | Direction | Width | Clocked |
+|-----|-----------|-------|---------|
+| `a` | input | 1 | no |
+| `b` | input | 1 | no |
+| `out` | output | 1 | no |
+"
+        );
+        let dff = interfaces.iter().find(|x| &*x.name == "DFF").unwrap();
+        let html = to_html(std::slice::from_ref(dff));
+        assert!(html.contains("<h2 id=\"DFF\">DFF</h2>\n<pre>Data Flip-flop: "));
+        assert!(html.contains("<tr><td><code>in</code></td><td>input</td><td>1</td><td>yes</td>"));
+    }
+}
@@ -1,11 +1,15 @@
-#[derive(Debug, Clone)]
+use std::fmt::{Display, Formatter};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ClockBehavior {
     Combinatorial,
     Sequential,
 }
 
 impl ClockBehavior {
-    pub unsafe fn and(&self, rhs: &Self) -> Self {
+    pub fn and(&self, rhs: &Self) -> Self {
         if matches!(self, ClockBehavior::Sequential) || matches!(rhs, ClockBehavior::Sequential) {
             ClockBehavior::Sequential
         } else {
@@ -13,3 +17,79 @@ impl ClockBehavior {
         }
     }
 }
+
+/// The simulated clock. Time advances by one every full cycle, and is displayed the way the
+/// official simulator does: `3` while the clock is low and `3+` after a tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Clock {
+    time: usize,
+    high: bool,
+}
+
+impl Clock {
+    pub fn time(&self) -> usize {
+        self.time
+    }
+
+    pub fn is_high(&self) -> bool {
+        self.high
+    }
+
+    pub fn tick(&mut self) {
+        self.high = true;
+    }
+
+    pub fn tock(&mut self) {
+        if self.high {
+            self.high = false;
+            self.time += 1;
+        }
+    }
+}
+
+impl Display for Clock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.time, if self.high { "+" } else { "" })
+    }
+}
+
+/// Holds a simulation back to a number of cycles per second, so that a program drawing on the
+/// screen can be watched. Cycles are spaced out evenly from the first one, so that the time spent
+/// running each of them does not add up to a slower clock.
+#[derive(Debug, Clone, Copy)]
+pub struct Throttle {
+    period: Duration,
+    next: Option<Instant>,
+}
+
+impl Throttle {
+    /// How far the simulation may fall behind, such as while it is paused, before it stops trying
+    /// to catch up
+    const SLACK: Duration = Duration::from_millis(100);
+
+    /// A throttle to a positive and finite frequency, whose period can be represented
+    pub fn new(hertz: f64) -> Option<Self> {
+        if !(hertz.is_finite() && hertz > 0.0) {
+            return None;
+        }
+        let period = Duration::try_from_secs_f64(1.0 / hertz).ok()?;
+        Some(Self { period, next: None })
+    }
+
+    pub fn hertz(&self) -> f64 {
+        1.0 / self.period.as_secs_f64()
+    }
+
+    /// Waits until the next cycle is due
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        let due = match self.next {
+            Some(due) if due + Self::SLACK >= now => due,
+            _ => now,
+        };
+        if due > now {
+            thread::sleep(due - now);
+        }
+        self.next = Some(due + self.period);
+    }
+}
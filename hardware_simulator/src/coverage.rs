@@ -0,0 +1,110 @@
+//! Toggle coverage: which bits of every pin inside a chip have been seen both low and high while
+//! it was driven. A bit which never toggled points at logic which the tests do not exercise.
+
+use std::fmt::Write;
+
+/// The values a pin has taken since tracking started, named like a
+/// [`FaultSite`](crate::model::chip::fault::FaultSite)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinToggles {
+    pub pin: String,
+    /// For every bit, least significant first, whether it has been seen low and high
+    pub seen: Vec<(bool, bool)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ToggleCoverage {
+    pub pins: Vec<PinToggles>,
+}
+
+impl ToggleCoverage {
+    /// Adds the current values of the pins of a chip, as returned by
+    /// [`Chip::pin_values`](crate::model::chip::Chip::pin_values)
+    pub fn record(&mut self, values: &[(String, Vec<Option<bool>>)]) {
+        if self.pins.is_empty() {
+            self.pins = values
+                .iter()
+                .map(|(pin, bits)| PinToggles {
+                    pin: pin.clone(),
+                    seen: vec![(false, false); bits.len()],
+                })
+                .collect();
+        }
+        for (toggles, (_, bits)) in self.pins.iter_mut().zip(values) {
+            for (seen, bit) in toggles.seen.iter_mut().zip(bits) {
+                match bit {
+                    Some(false) => seen.0 = true,
+                    Some(true) => seen.1 = true,
+                    None => {}
+                }
+            }
+        }
+    }
+
+    /// Every bit which has not been seen both low and high, as a pin and a bit index
+    pub fn untoggled(&self) -> Vec<(&str, usize)> {
+        self.pins
+            .iter()
+            .flat_map(|pin| {
+                pin.seen
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (low, high))| !(low & high))
+                    .map(|(bit, _)| (pin.pin.as_str(), bit))
+            })
+            .collect()
+    }
+
+    /// The share of bits which have toggled, between 0 and 1
+    pub fn coverage(&self) -> f64 {
+        let total = self.pins.iter().map(|x| x.seen.len()).sum::<usize>();
+        if total == 0 {
+            1.0
+        } else {
+            1.0 - self.untoggled().len() as f64 / total as f64
+        }
+    }
+
+    /// One line per pin with untoggled bits, listing them
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for pin in self.pins.iter() {
+            let bits = pin
+                .seen
+                .iter()
+                .enumerate()
+                .filter(|(_, (low, high))| !(low & high))
+                .map(|(bit, seen)| match seen {
+                    (true, _) => format!("{bit} (always 0)"),
+                    (_, true) => format!("{bit} (always 1)"),
+                    _ => format!("{bit} (never driven)"),
+                })
+                .collect::<Vec<_>>();
+            if !bits.is_empty() {
+                writeln!(summary, "{}: {}", pin.pin, bits.join(", ")).unwrap();
+            }
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut coverage = ToggleCoverage::default();
+        let values = |a, b| {
+            [
+                ("a".to_string(), vec![Some(a), None]),
+                ("b".to_string(), vec![Some(b)]),
+            ]
+        };
+        coverage.record(&values(false, true));
+        coverage.record(&values(true, true));
+        assert_eq!(coverage.untoggled(), [("a", 1), ("b", 0)]);
+        assert!((coverage.coverage() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(coverage.summary(), "a: 1 (never driven)\nb: 0 (always 1)\n");
+    }
+}
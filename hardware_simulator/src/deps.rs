@@ -0,0 +1,238 @@
+//! Which chips of a project use which others as parts, to find one's way around a design spread
+//! over many files, along with the parts which are defined nowhere and the chips which no other
+//! chip uses.
+
+use crate::grade::escape_json;
+use crate::model::chip::builtin::get_builtin;
+use crate::model::dialect::Dialect;
+use crate::model::parser::create_chip;
+use crate::model::preprocess::preprocess;
+use crate::Span;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dependencies {
+    /// Every chip defined in the project, with the names of its parts in the order they are
+    /// first used. Generic chips and their specializations, such as `Mux<16>`, go by their base
+    /// name.
+    pub chips: BTreeMap<String, Vec<String>>,
+    /// The files which could not be read or parsed
+    pub unparsed: Vec<PathBuf>,
+}
+
+/// The chips defined by the HDL files in a directory and their parts
+pub fn project_dependencies(dir: &Path, dialect: Dialect) -> io::Result<Dependencies> {
+    let mut paths = fs::read_dir(dir)?
+        .filter_map(|x| Some(x.ok()?.path()))
+        .filter(|x| x.extension().is_some_and(|x| x == "hdl"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut dependencies = Dependencies::default();
+    for path in paths {
+        let Some(source) = fs::read_to_string(&path).ok().and_then(|x| match dialect {
+            Dialect::Strict => Some(x),
+            Dialect::Extended => preprocess(&x, &path).ok(),
+        }) else {
+            dependencies.unparsed.push(path);
+            continue;
+        };
+        match create_chip(Span::new(&source)) {
+            Ok(chip) => {
+                let mut parts = Vec::new();
+                for part in chip.parts().into_iter().map(base_name) {
+                    if !parts.contains(&part) {
+                        parts.push(part);
+                    }
+                }
+                dependencies.chips.insert(base_name(*chip.name), parts);
+            }
+            Err(_) => dependencies.unparsed.push(path),
+        }
+    }
+    Ok(dependencies)
+}
+
+fn base_name(name: &str) -> String {
+    name.split('<').next().unwrap_or(name).trim().to_string()
+}
+
+impl Dependencies {
+    /// The parts which are neither builtins nor defined in the project, with the chips which use
+    /// them
+    pub fn missing(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut missing = BTreeMap::<_, Vec<_>>::new();
+        for (chip, parts) in self.chips.iter() {
+            for part in parts {
+                if !self.chips.contains_key(part) && get_builtin(part).is_none() {
+                    missing
+                        .entry(part.as_str())
+                        .or_default()
+                        .push(chip.as_str());
+                }
+            }
+        }
+        missing
+    }
+
+    /// The chips which no other chip uses, such as the top chip of a design, or one which is
+    /// left over
+    pub fn unused(&self) -> Vec<&str> {
+        let used = self
+            .chips
+            .iter()
+            .flat_map(|(chip, parts)| parts.iter().filter(move |x| *x != chip))
+            .collect::<BTreeSet<_>>();
+        self.chips
+            .keys()
+            .filter(|x| !used.contains(x))
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for (chip, parts) in self.chips.iter() {
+            writeln!(summary, "{chip}: {}", parts.join(", ")).unwrap();
+        }
+        for (part, users) in self.missing() {
+            writeln!(summary, "missing: {part} (used by {})", users.join(", ")).unwrap();
+        }
+        let unused = self.unused();
+        if !unused.is_empty() {
+            writeln!(summary, "not used by any other chip: {}", unused.join(", ")).unwrap();
+        }
+        for path in self.unparsed.iter() {
+            writeln!(summary, "could not be parsed: {}", path.display()).unwrap();
+        }
+        summary
+    }
+
+    /// A graph for Graphviz with an edge from each chip to each of its parts. Builtins are drawn
+    /// as boxes, and missing parts in red.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+        let missing = self.missing();
+        let parts = self.chips.values().flatten().collect::<BTreeSet<_>>();
+        for part in parts {
+            if self.chips.contains_key(part) {
+                continue;
+            }
+            let style = if missing.contains_key(part.as_str()) {
+                "color = red"
+            } else {
+                "shape = box"
+            };
+            writeln!(dot, "    \"{part}\" [{style}]").unwrap();
+        }
+        for (chip, parts) in self.chips.iter() {
+            writeln!(dot, "    \"{chip}\"").unwrap();
+            for part in parts {
+                writeln!(dot, "    \"{chip}\" -> \"{part}\"").unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        let list = |items: &mut dyn Iterator<Item = &str>| {
+            items
+                .map(|x| format!("\"{}\"", escape_json(x)))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let chips = self
+            .chips
+            .iter()
+            .map(|(chip, parts)| {
+                format!(
+                    "\"{}\":[{}]",
+                    escape_json(chip),
+                    list(&mut parts.iter().map(String::as_str))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let missing = self
+            .missing()
+            .iter()
+            .map(|(part, users)| {
+                format!(
+                    "\"{}\":[{}]",
+                    escape_json(part),
+                    list(&mut users.iter().copied())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let unparsed = self
+            .unparsed
+            .iter()
+            .map(|x| x.to_string_lossy())
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"chips\":{{{chips}}},\"missing\":{{{missing}}},\"unused\":[{}],\"unparsed\":[{}]}}",
+            list(&mut self.unused().into_iter()),
+            list(&mut unparsed.iter().map(|x| x.as_ref()))
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn test_dependencies() {
+        let dir = TempDir::new("deps");
+        let files = [
+            (
+                "Not.hdl",
+                "CHIP Not { IN in; OUT out; PARTS: Nand(a=in, b=in, out=out); }",
+            ),
+            (
+                "And.hdl",
+                "CHIP And { IN a, b; OUT out; PARTS: Nand(a=a, b=b, out=x); Not(in=x, out=out); }",
+            ),
+            (
+                "Top.hdl",
+                "CHIP Top { IN a, b; OUT out; PARTS: And(a=a, b=b, out=x); Or(a=x, b=a, out=out); }",
+            ),
+            ("Broken.hdl", "CHIP Broken {"),
+        ];
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        let dependencies = project_dependencies(&dir, Dialect::Strict).unwrap();
+
+        assert_eq!(dependencies.chips["And"], ["Nand", "Not"]);
+        assert_eq!(
+            dependencies.missing(),
+            BTreeMap::from([("Or", vec!["Top"])])
+        );
+        assert_eq!(dependencies.unused(), ["Top"]);
+        assert_eq!(dependencies.unparsed, [dir.join("Broken.hdl")]);
+        assert_eq!(
+            dependencies.summary(),
+            format!(
+                "And: Nand, Not\nNot: Nand\nTop: And, Or\nmissing: Or (used by Top)\n\
+                 not used by any other chip: Top\ncould not be parsed: {}\n",
+                dir.join("Broken.hdl").display()
+            )
+        );
+        let dot = dependencies.to_dot();
+        assert!(dot.contains("    \"Nand\" [shape = box]\n"));
+        assert!(dot.contains("    \"Or\" [color = red]\n"));
+        assert!(dot.contains("    \"Top\" -> \"And\"\n"));
+        assert!(dependencies.to_json().starts_with(
+            "{\"chips\":{\"And\":[\"Nand\",\"Not\"],\"Not\":[\"Nand\"],\"Top\":[\"And\",\"Or\"]},\
+             \"missing\":{\"Or\":[\"Top\"]},\"unused\":[\"Top\"],\"unparsed\":["
+        ));
+    }
+}
@@ -0,0 +1,685 @@
+//! Errors and warnings about an HDL file in a form which tools can read, so that an editor can
+//! underline the place of a syntax error, or a grader can point a student at it.
+//!
+//! Every diagnostic has one of the codes listed in [`crate::error`]: `E0100` for a file which
+//! cannot be read, `E0102` with a location for a syntax error, the codes of
+//! [`ModelConstructionError::code`] for chips which cannot be built or use extensions outside of
+//! the extended dialect, `W0101` for pins which differ from the course interface of the chip, and
+//! `W0102` for bits of its outputs which none of its parts drive.
+
+use crate::bus_range::BusRange;
+use crate::grade::escape_json;
+use crate::manifest::Manifest;
+use crate::model::chip::build_ctx::{BuiltinPolicy, ChipBuilder};
+use crate::model::chip::canonical::check_conformance;
+use crate::model::chip::error::ModelConstructionError;
+use crate::model::dialect::Dialect;
+use crate::model::parser::error::SyntaxError;
+use crate::model::parser::{create_chip, create_chips, Chip, Form, Symbol};
+use crate::model::preprocess::{preprocess, PreprocessError};
+use crate::Span;
+use nom_supreme::error::ErrorTree;
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::IsTerminal;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A place in a file which explains a diagnostic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Related {
+    pub message: String,
+    pub file: PathBuf,
+    /// The bytes of the file, or of its preprocessed text in the extended dialect
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub file: PathBuf,
+    /// The bytes of the file the diagnostic is about, or of its preprocessed text in the extended
+    /// dialect. `None` if it is about the whole file.
+    pub range: Option<Range<usize>>,
+    pub related: Vec<Related>,
+}
+
+impl Diagnostic {
+    fn error(code: &'static str, message: impl ToString, file: &Path) -> Self {
+        Self {
+            code,
+            severity: Severity::Error,
+            message: message.to_string(),
+            file: file.to_path_buf(),
+            range: None,
+            related: Vec::new(),
+        }
+    }
+
+    fn at(mut self, range: Option<Range<usize>>) -> Self {
+        self.range = range;
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        let range = |range: &Range<usize>| format!("[{},{}]", range.start, range.end);
+        let related = self
+            .related
+            .iter()
+            .map(|x| {
+                format!(
+                    "{{\"message\":\"{}\",\"file\":\"{}\",\"range\":{}}}",
+                    escape_json(&x.message),
+                    escape_json(&x.file.to_string_lossy()),
+                    range(&x.range)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"code\":\"{}\",\"severity\":\"{}\",\"message\":\"{}\",\"file\":\"{}\",\"range\":{},\"related\":[{related}]}}",
+            self.code,
+            self.severity,
+            escape_json(&self.message),
+            escape_json(&self.file.to_string_lossy()),
+            self.range.as_ref().map_or("null".to_string(), range),
+        )
+    }
+}
+
+impl Diagnostic {
+    /// The diagnostic as the compiler prints it, with the lines of the source it points at.
+    /// `source` is the text the range refers to. Colors are ANSI escapes.
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("\x1b[{style}m{text}\x1b[0m")
+            } else {
+                text.to_string()
+            }
+        };
+        let accent = match self.severity {
+            Severity::Error => "1;31",
+            Severity::Warning => "1;33",
+        };
+        let mut output = format!(
+            "{}{}\n",
+            paint(accent, &format!("{}[{}]", self.severity, self.code)),
+            paint("1", &format!(": {}", self.message))
+        );
+        let Some(range) = self.range.as_ref().filter(|x| x.end <= source.len()) else {
+            output.push_str(&format!(
+                " {} {}\n",
+                paint("1;34", "-->"),
+                self.file.display()
+            ));
+            return output;
+        };
+        let (line, column) = line_column(source, range.start);
+        let text = source.lines().nth(line - 1).unwrap_or("");
+        let gutter = " ".repeat(line.to_string().len());
+        let underline = source[range.clone()]
+            .lines()
+            .next()
+            .map_or(1, |x| x.chars().count().max(1));
+        let bar = paint("1;34", "|");
+        output.push_str(&format!(
+            "{gutter}{} {}:{line}:{column}\n",
+            paint("1;34", "-->"),
+            self.file.display()
+        ));
+        output.push_str(&format!("{gutter} {bar}\n"));
+        output.push_str(&format!(
+            "{} {bar} {text}\n",
+            paint("1;34", &line.to_string())
+        ));
+        output.push_str(&format!(
+            "{gutter} {bar} {}{}\n",
+            " ".repeat(column - 1),
+            paint(accent, &"^".repeat(underline))
+        ));
+        for related in self.related.iter() {
+            // a note about another file, such as a part, is placed in the text of that file
+            let text = match related.file == self.file {
+                true => Cow::Borrowed(source),
+                false => Cow::Owned(fs::read_to_string(&related.file).unwrap_or_default()),
+            };
+            let mut offset = related.range.start.min(text.len());
+            while !text.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            let (line, column) = line_column(&text, offset);
+            output.push_str(&format!(
+                "{gutter} {} {}: {} at {}:{line}:{column}\n",
+                paint("1;34", "="),
+                paint("1", "note"),
+                related.message,
+                related.file.display()
+            ));
+        }
+        output
+    }
+}
+
+/// Whether diagnostics printed to the terminal should be colored, which they are unless the
+/// output is not a terminal, or the `NO_COLOR` environment variable is set
+pub fn use_color() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|x| x.is_empty())
+}
+
+/// The line and column of a byte of the text, both counted from 1
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Every diagnostic about the chip in an HDL file, and the chips it uses from the same directory
+pub fn check_hdl(path: &Path, dialect: Dialect) -> Vec<Diagnostic> {
+    let manifest = Manifest {
+        dialect,
+        ..Manifest::new(path.parent().unwrap_or(Path::new(".")))
+    };
+    check_hdl_with(path, &manifest)
+}
+
+/// The diagnostics of [`check_hdl`], with the chips loaded in the dialect and with the builtins
+/// of a manifest
+pub fn check_hdl_with(path: &Path, manifest: &Manifest) -> Vec<Diagnostic> {
+    check_file(path, manifest, &mut Vec::new())
+}
+
+/// The diagnostics of [`check_hdl`], where `checking` holds the files further up which use the
+/// chip as a part
+fn check_file(path: &Path, manifest: &Manifest, checking: &mut Vec<PathBuf>) -> Vec<Diagnostic> {
+    let dialect = manifest.dialect;
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => return vec![Diagnostic::error("E0100", e, path)],
+    };
+    let source = match dialect {
+        Dialect::Strict => source,
+        Dialect::Extended => match preprocess(&source, path) {
+            Ok(source) => source,
+            Err(e) => return vec![preprocess_diagnostic(e, path, &source)],
+        },
+    };
+    let chip = match create_chip(Span::new(&source)) {
+        Ok(chip) => chip,
+        Err(e) => return vec![parse_diagnostic(e, path, &source)],
+    };
+
+    let rejected = dialect.rejected(&chip);
+    if !rejected.is_empty() {
+        return rejected
+            .into_iter()
+            .map(|(extension, span)| {
+                let error = ModelConstructionError::Extension(extension);
+                Diagnostic::error(error.code(), error, path).at(Some(span_range(span)))
+            })
+            .collect();
+    }
+
+    let mut diagnostics = check_conformance(&chip.interface())
+        .into_iter()
+        .map(|deviation| Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error("W0101", deviation, path).at(Some(span_range(chip.name)))
+        })
+        .collect::<Vec<_>>();
+    diagnostics.extend(undriven_outputs(&chip, path));
+    let mut builder = ChipBuilder::new();
+    manifest.configure_builder(&mut builder);
+    if let Err(e) = builder.add_hdl(path) {
+        diagnostics.push(build_diagnostic(
+            e, &chip, path, manifest, builder, checking,
+        ));
+    }
+    diagnostics
+}
+
+/// A warning for every output of a chip built from parts with bits which no part drives, which
+/// then stay low whatever the inputs. A builtin drives all of its outputs.
+fn undriven_outputs(chip: &Chip, path: &Path) -> Vec<Diagnostic> {
+    let Form::Native(connections) = &chip.logic else {
+        return Vec::new();
+    };
+    let mut diagnostics = Vec::new();
+    for pin in chip.out_pins.iter() {
+        let width = pin.size.unwrap_or(1) as usize;
+        let mut driven = vec![false; width];
+        let arguments = connections.iter().flat_map(|x| x.inputs.iter());
+        for argument in
+            arguments.filter(|x| matches!(x.external, Symbol::Name(name) if *name == *pin.name))
+        {
+            let (start, end) = match &argument.external_bus {
+                Some(range) => (range.start() as usize, range.end() as usize),
+                None => (0, width.saturating_sub(1)),
+            };
+            for bit in driven.iter_mut().take(end + 1).skip(start) {
+                *bit = true;
+            }
+        }
+        // the runs of bits which are not driven, such as `[4..7]`
+        let mut runs = Vec::new();
+        let mut bit = 0;
+        while bit < width {
+            let Some(start) = (bit..width).find(|&x| !driven[x]) else {
+                break;
+            };
+            let end = (start..width).find(|&x| driven[x]).unwrap_or(width);
+            runs.push(
+                BusRange::new(start as u16, end as u16 - 1)
+                    .unwrap()
+                    .to_string(),
+            );
+            bit = end;
+        }
+        let message = match runs.as_slice() {
+            [] => continue,
+            _ if !driven.contains(&true) => {
+                format!(
+                    "No part drives the output `{}`, so it is always false",
+                    pin.name
+                )
+            }
+            _ => format!(
+                "No part drives bits {} of the output `{}`, so they are always false",
+                runs.join(", "),
+                pin.name
+            ),
+        };
+        diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error("W0102", message, path).at(Some(span_range(pin.name)))
+        });
+    }
+    diagnostics
+}
+
+/// The diagnostic of a chip which cannot be built. The builder stops at the first part of it
+/// which cannot be built, with the error of that part, so the error is put where the part is
+/// used, with a note at its place in the file of the part.
+fn build_diagnostic(
+    error: ModelConstructionError,
+    chip: &Chip,
+    path: &Path,
+    manifest: &Manifest,
+    mut builder: ChipBuilder,
+    checking: &mut Vec<PathBuf>,
+) -> Diagnostic {
+    // a part which has no file is not found by the chip itself, and so is an error in this file
+    let broken = chip.parts().into_iter().find(|part| {
+        if part.contains('<') {
+            return builder.resolve_chip(part).is_err();
+        }
+        let wanted = builder.builtin_policy(part) == BuiltinPolicy::PreferHdl
+            || builder.builtin_chip(part).is_none();
+        wanted
+            && path.with_file_name(format!("{part}.hdl")).is_file()
+            && builder.hdl_chip(part).is_none()
+    });
+    let Some(part) = broken else {
+        let range = error.range();
+        return Diagnostic::error(error.code(), &error, path).at(range);
+    };
+    let range = part_range(chip, part);
+    let part_path = path.with_file_name(format!("{part}.hdl"));
+    // a specialization has no file of its own, and a part in a cycle is being checked already
+    let inner = if part.contains('<') || checking.contains(&part_path) {
+        None
+    } else {
+        checking.push(path.to_path_buf());
+        let inner = check_file(&part_path, manifest, checking)
+            .into_iter()
+            .find(|x| x.severity == Severity::Error);
+        checking.pop();
+        inner
+    };
+    let Some(inner) = inner else {
+        let message = format!("Part `{part}` cannot be built: {error}");
+        return Diagnostic::error(error.code(), message, path).at(range);
+    };
+    let message = format!("Part `{part}` cannot be built: {}", inner.message);
+    let mut diagnostic = Diagnostic::error(inner.code, message, path).at(range);
+    diagnostic.related = inner
+        .range
+        .map(|range| Related {
+            message: inner.message,
+            file: inner.file,
+            range,
+        })
+        .into_iter()
+        .collect();
+    diagnostic
+}
+
+/// The diagnostics of HDL source which cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let messages = self
+            .0
+            .iter()
+            .map(|x| format!("{}[{}]: {}", x.severity, x.code, x.message));
+        f.write_str(&messages.collect::<Vec<_>>().join("\n"))
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Parses HDL source holding one or more chips, without preprocessing it. The interface of each
+/// chip is given by [`Chip::interface`], and the extensions it uses by
+/// [`Dialect::rejected`]. The diagnostics are about no file, so their paths are empty.
+pub fn parse_hdl(source: &str) -> Result<Vec<Chip<'_>>, Diagnostics> {
+    create_chips(Span::new(source))
+        .map_err(|e| Diagnostics(vec![parse_diagnostic(e, Path::new(""), source)]))
+}
+
+fn parse_diagnostic(error: nom::Err<ErrorTree<Span>>, path: &Path, source: &str) -> Diagnostic {
+    syntax_diagnostic(SyntaxError::new(&error, source), path)
+}
+
+fn preprocess_diagnostic(error: PreprocessError, path: &Path, source: &str) -> Diagnostic {
+    let error_code = error.code();
+    match &error {
+        PreprocessError::BadDirective {
+            path: file, line, ..
+        } => {
+            let text = (file != path).then(|| fs::read_to_string(file).unwrap_or_default());
+            let range = line_range(text.as_deref().unwrap_or(source), *line);
+            Diagnostic::error(error_code, &error, file).at(range)
+        }
+        _ => Diagnostic::error(error_code, &error, path),
+    }
+}
+
+/// Points at the place where the parser got furthest, which is where the text stops making sense
+fn syntax_diagnostic(error: SyntaxError, path: &Path) -> Diagnostic {
+    let code = ModelConstructionError::HdlParseError(error.clone()).code();
+    let mut diagnostic = Diagnostic::error(code, error.message, path).at(Some(error.range));
+    diagnostic.related = error
+        .contexts
+        .into_iter()
+        .map(|(message, range)| Related {
+            message,
+            file: path.to_path_buf(),
+            range,
+        })
+        .collect();
+    diagnostic
+}
+
+/// Where a chip uses the part, such as a chip which does not exist
+fn part_range(chip: &Chip, part: &str) -> Option<Range<usize>> {
+    let Form::Native(connections) = &chip.logic else {
+        return None;
+    };
+    connections
+        .iter()
+        .find(|x| *x.chip_name == part)
+        .map(|x| span_range(x.chip_name))
+}
+
+fn span_range(span: Span) -> Range<usize> {
+    span.location_offset()..span.location_offset() + span.len()
+}
+
+fn line_range(source: &str, line: usize) -> Option<Range<usize>> {
+    let start = source
+        .split_inclusive('\n')
+        .take(line - 1)
+        .map(str::len)
+        .sum::<usize>();
+    let text = source.get(start..)?.lines().next()?;
+    Some(start..start + text.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    fn check(files: &[(&str, &str)], dialect: Dialect) -> Vec<Diagnostic> {
+        let dir = TempDir::new(&format!("diagnostics-{}", files[0].0));
+        for (name, source) in files {
+            fs::write(dir.join(format!("{name}.hdl")), source).unwrap();
+        }
+        let diagnostics = check_hdl(&dir.join(format!("{}.hdl", files[0].0)), dialect);
+        diagnostics
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let source = "CHIP Bad {\n    IN a;\n    OUT out;\n    PARTS:\n    Not(in=a, out=out)\n}";
+        let diagnostics = check(&[("Bad", source)], Dialect::Strict);
+        let [diagnostic] = diagnostics.as_slice() else {
+            panic!("{diagnostics:?}");
+        };
+        assert_eq!(diagnostic.code, "E0102");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.message, "expected ';'");
+        let range = diagnostic.range.clone().unwrap();
+        assert_eq!(&source[range], "}");
+    }
+
+    #[test]
+    fn test_render() {
+        let source =
+            "CHIP Top {\n    IN a;\n    OUT out;\n    PARTS:\n    Missing(in=a, out=out);\n}";
+        let diagnostics = check(&[("Top", source)], Dialect::Strict);
+        let rendered = diagnostics[0].render(source, false);
+        let file = diagnostics[0].file.display();
+        assert_eq!(
+            rendered,
+            format!(
+                "\
+error[E0101]: Chip `Missing` cannot be found with the given path
+ --> {file}:5:5
+  |
+5 |     Missing(in=a, out=out);
+  |     ^^^^^^^
+"
+            )
+        );
+        let colored = diagnostics[0].render(source, true);
+        assert!(colored.starts_with("\x1b[1;31merror[E0101]\x1b[0m"));
+
+        let whole_file = Diagnostic::error("E0100", "No such file", Path::new("Top.hdl"));
+        assert_eq!(
+            whole_file.render("", false),
+            "error[E0100]: No such file\n --> Top.hdl\n"
+        );
+    }
+
+    #[test]
+    fn test_elaboration_error() {
+        let source = "CHIP Top { IN a; OUT out; PARTS: Missing(in=a, out=out); }";
+        let diagnostics = check(&[("Top", source)], Dialect::Strict);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "E0101");
+        assert_eq!(&source[diagnostics[0].range.clone().unwrap()], "Missing");
+        assert_eq!(
+            diagnostics[0].to_json(),
+            format!(
+                "{{\"code\":\"E0101\",\"severity\":\"error\",\"message\":\"Chip `Missing` cannot be found with the given path\",\"file\":\"{}\",\"range\":[33,40],\"related\":[]}}",
+                escape_json(&diagnostics[0].file.to_string_lossy())
+            )
+        );
+
+        let not = "CHIP Not { IN a; OUT out; PARTS: Nand(a=a, b=a, out=out); }";
+        let diagnostics = check(&[("Not", not)], Dialect::Strict);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics
+            .iter()
+            .all(|x| x.code == "W0101" && x.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn test_broken_part() {
+        let top = "CHIP Top { IN a; OUT out; PARTS: Nand(a=a, b=a, out=x); Not(in=x, out=out); }";
+        let not =
+            "CHIP Not {\n    IN in;\n    OUT out;\n    PARTS:\n    Nand(a=in, c=in, out=out);\n}";
+        let diagnostics = check(&[("Top", top), ("Not", not)], Dialect::Strict);
+        let [diagnostic] = diagnostics.as_slice() else {
+            panic!("{diagnostics:?}");
+        };
+        // the error of the part is where it is used, with a note at its place in the part
+        assert_eq!(diagnostic.code, "E0110");
+        assert_eq!(
+            diagnostic.message,
+            "Part `Not` cannot be built: Chip `Nand` has no pin `c`"
+        );
+        assert_eq!(&top[diagnostic.range.clone().unwrap()], "Not");
+        let [related] = diagnostic.related.as_slice() else {
+            panic!("{:?}", diagnostic.related);
+        };
+        assert_eq!(related.message, "Chip `Nand` has no pin `c`");
+        assert_eq!(related.file, diagnostic.file.with_file_name("Not.hdl"));
+        assert_eq!(&not[related.range.clone()], "c");
+        // the note is placed in the text of the part, which is kept until it is rendered
+        let dir = TempDir::new("diagnostics-render-part");
+        fs::write(dir.join("Top.hdl"), top).unwrap();
+        fs::write(dir.join("Not.hdl"), not).unwrap();
+        let rendered = check_hdl(&dir.join("Top.hdl"), Dialect::Strict)[0].render(top, false);
+        let note = format!("{}:5:16", dir.join("Not.hdl").display());
+        assert!(rendered.contains(&note), "{rendered}");
+
+        // an error of the chip itself is where it is
+        let top =
+            "CHIP Top { IN a; OUT out; PARTS: Nand(a=a, b=a, out=out); Nand(a=a, b=a, out=out); }";
+        let diagnostics = check(&[("Top", top)], Dialect::Strict);
+        assert_eq!(diagnostics[0].code, "E0113");
+        assert_eq!(&top[diagnostics[0].range.clone().unwrap()], "out");
+
+        // parts which use each other are only checked once
+        let a = "CHIP A { IN in; OUT out; PARTS: B(in=in, out=out); }";
+        let b = "CHIP B { IN in; OUT out; PARTS: A(in=in, out=out); }";
+        let diagnostics = check(&[("A", a), ("B", b)], Dialect::Strict);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(&a[diagnostics[0].range.clone().unwrap()], "B");
+    }
+
+    #[test]
+    fn test_undriven_output() {
+        let source = "CHIP Top { IN a; OUT out[4], x, y; PARTS: \
+                      Nand(a=a, b=a, out=out[1], out=out[3], out=y); }";
+        let diagnostics = check(&[("Top", source)], Dialect::Strict);
+        let warnings = diagnostics
+            .iter()
+            .map(|x| {
+                (
+                    x.code,
+                    x.severity,
+                    x.message.as_str(),
+                    &source[x.range.clone().unwrap()],
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                (
+                    "W0102",
+                    Severity::Warning,
+                    "No part drives bits [0], [2] of the output `out`, so they are always false",
+                    "out"
+                ),
+                (
+                    "W0102",
+                    Severity::Warning,
+                    "No part drives the output `x`, so it is always false",
+                    "x"
+                ),
+            ]
+        );
+
+        // a builtin drives its outputs itself, and a stub has no parts to drive them
+        let builtin = "CHIP Nand { IN a, b; OUT out; BUILTIN Nand; }";
+        assert!(check(&[("Nand", builtin)], Dialect::Strict).is_empty());
+        let stub = "CHIP Top { IN a; OUT out; PARTS: }";
+        assert_eq!(check(&[("Top", stub)], Dialect::Strict)[0].code, "W0102");
+    }
+
+    #[test]
+    fn test_manifest() {
+        let dir = TempDir::new("diagnostics-manifest");
+        let top = "CHIP Top { IN in[16], load, address[3]; OUT out[16]; PARTS: \
+                   RAM8(in=in, load=load, address=address, out=out); }";
+        fs::write(dir.join("Top.hdl"), top).unwrap();
+        fs::write(
+            dir.join("RAM8.hdl"),
+            "CHIP RAM8 { IN in; OUT out; PARTS: Nope(); }",
+        )
+        .unwrap();
+        // the broken RAM8 is only a part if the manifest prefers chips written in HDL
+        let manifest = Manifest::parse("prefer = \"hdl\"", &*dir).unwrap();
+        let diagnostics = check_hdl_with(&dir.join("Top.hdl"), &manifest);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(&top[diagnostics[0].range.clone().unwrap()], "RAM8");
+        assert!(check_hdl(&dir.join("Top.hdl"), Dialect::Strict).is_empty());
+        let manifest = Manifest::parse("prefer = \"hdl\"\nbuiltins = [\"RAM8\"]", &*dir).unwrap();
+        assert!(check_hdl_with(&dir.join("Top.hdl"), &manifest).is_empty());
+    }
+
+    #[test]
+    fn test_extension_error() {
+        let files = [
+            (
+                "Labelled",
+                "CHIP Labelled { IN a; OUT out; PARTS: first: Not(in=a, out=out); }",
+            ),
+            (
+                "Not",
+                "CHIP Not { IN in; OUT out; PARTS: Nand(a=in, b=in, out=out); }",
+            ),
+        ];
+        let diagnostics = check(&files, Dialect::Strict);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "E0109");
+        assert_eq!(
+            diagnostics[0].message,
+            "The chip uses instance labels, which are only accepted in the extended dialect"
+        );
+        assert_eq!(&files[0].1[diagnostics[0].range.clone().unwrap()], "first");
+        assert!(check(&files, Dialect::Extended).is_empty());
+    }
+
+    #[test]
+    fn test_directive_error() {
+        let source =
+            "// A chip\n//! definee N 2\nCHIP Top { IN a; OUT out; PARTS: Not(in=a, out=out); }";
+        let diagnostics = check(&[("Top", source)], Dialect::Extended);
+        assert_eq!(diagnostics[0].code, "E0103");
+        assert_eq!(
+            &source[diagnostics[0].range.clone().unwrap()],
+            "//! definee N 2"
+        );
+        let diagnostics = check(&[("Empty", "")], Dialect::Strict);
+        assert_eq!(diagnostics[0].code, "E0102");
+        assert_eq!(diagnostics[0].range, Some(0..0));
+    }
+}
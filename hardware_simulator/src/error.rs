@@ -0,0 +1,100 @@
+//! The errors of the whole crate in one type, for callers which load, simulate and test chips and
+//! want to handle every failure in one place.
+//!
+//! Each kind of error has a code which stays the same across versions, for tools and graders
+//! which recognize particular mistakes:
+//!
+//! | Codes   | Errors                                                      |
+//! |---------|-------------------------------------------------------------|
+//! | `E0100` | A file could not be read or written                         |
+//! | `E01xx` | Chips could not be loaded, see [`ModelConstructionError`]   |
+//! | `E02xx` | The HDL has a syntax error, see [`HdlParseError`]           |
+//! | `E03xx` | A chip was driven wrongly, see [`SimulationError`]          |
+//! | `E04xx` | A test script failed, see [`ScriptErrorKind`]               |
+//! | `E05xx` | A memory image could not be read, see [`ImageError`]        |
+//! | `E06xx` | The manifest of a project is wrong, see [`ManifestError`]   |
+//! | `W01xx` | Warnings about HDL files, see [`crate::diagnostics`]        |
+//!
+//! [`ScriptErrorKind`]: crate::test_script::ScriptErrorKind
+
+use crate::image::ImageError;
+use crate::manifest::ManifestError;
+use crate::model::chip::error::ModelConstructionError;
+use crate::model::preprocess::PreprocessError;
+use crate::model::HdlParseError;
+use crate::simulator::SimulationError;
+use crate::test_script::ScriptError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Preprocess(#[from] PreprocessError),
+    #[error(transparent)]
+    Parse(#[from] HdlParseError),
+    #[error(transparent)]
+    Model(#[from] ModelConstructionError),
+    #[error(transparent)]
+    Simulation(#[from] SimulationError),
+    #[error(transparent)]
+    Script(#[from] ScriptError),
+    #[error(transparent)]
+    Image(#[from] ImageError),
+    #[error(transparent)]
+    Manifest(#[from] ManifestError),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "E0100",
+            Error::Preprocess(e) => e.code(),
+            Error::Parse(e) => e.code(),
+            Error::Model(e) => e.code(),
+            Error::Simulation(e) => e.code(),
+            Error::Script(e) => e.kind.code(),
+            Error::Image(e) => e.code(),
+            Error::Manifest(e) => e.code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::chip::build_ctx::ChipBuilder;
+    use crate::simulator::Simulator;
+    use crate::test_script::{parse_script, ScriptErrorKind, TestRunner};
+
+    fn drive() -> Result<Vec<bool>> {
+        let mut builder = ChipBuilder::new();
+        let mut simulator = Simulator::new(builder.resolve_chip("Nand")?);
+        simulator.set("a", &[true, true])?;
+        Ok(simulator.eval().to_vec())
+    }
+
+    #[test]
+    fn test_code() {
+        let error = drive().unwrap_err();
+        assert!(matches!(error, Error::Simulation(_)));
+        assert_eq!(error.code(), "E0302");
+        assert_eq!(
+            error.to_string(),
+            "Pin `a` is 1 bits wide, but 2 bits were given"
+        );
+
+        let script = parse_script("load Missing.hdl;").unwrap();
+        let error = Error::from(TestRunner::new(".").run(&script).unwrap_err());
+        // scripts which fail to load a chip have the code of the load error
+        assert_eq!(error.code(), "E0101");
+        assert_eq!(
+            Error::from(ModelConstructionError::ChipNotFound("X".into())).code(),
+            "E0101"
+        );
+        assert_eq!(ScriptErrorKind::NoChip.code(), "E0402");
+    }
+}
@@ -0,0 +1,497 @@
+//! Runs every test script of a project and collects the results into a report which can be read
+//! by other tools, such as an LMS.
+
+use crate::error::Error;
+use crate::manifest::Manifest;
+use crate::test_script::{run_script_file, Limits, TestRunner};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::iter::once;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub line: u32,
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptReport {
+    /// The name of the script, without its extension
+    pub name: String,
+    pub path: PathBuf,
+    /// The first error which stopped the script, if any
+    pub failure: Option<Failure>,
+    pub time: Duration,
+}
+
+impl ScriptReport {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Why a submission could not be graded at all
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GradeError {
+    /// The code of the error, see [`crate::error`]
+    pub code: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GradeReport {
+    pub name: String,
+    pub scripts: Vec<ScriptReport>,
+    /// Set if the scripts could not be found, such as for a submission with a broken manifest. It
+    /// then has no scripts, and counts as one failure.
+    pub error: Option<GradeError>,
+}
+
+/// Every `.tst` file in a directory and its subdirectories, in a stable order
+pub fn find_scripts(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut scripts = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scripts.extend(find_scripts(&path)?);
+        } else if path.extension().is_some_and(|x| x == "tst") {
+            scripts.push(path);
+        }
+    }
+    scripts.sort();
+    Ok(scripts)
+}
+
+pub fn run_graded(path: &Path, limits: Limits) -> ScriptReport {
+    run_graded_with(path, |runner| runner.with_limits(limits))
+}
+
+fn run_graded_with(path: &Path, configure: impl FnOnce(TestRunner) -> TestRunner) -> ScriptReport {
+    let start = Instant::now();
+    // a bug in the simulator should fail the script rather than the whole run
+    let failure = match catch_unwind(AssertUnwindSafe(|| run_script_file(path, configure))) {
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => Some(Failure {
+            line: e.line,
+            column: e.column,
+            message: e.kind.to_string(),
+        }),
+        Err(_) => Some(Failure {
+            line: 0,
+            column: 0,
+            message: "The simulator panicked".to_string(),
+        }),
+    };
+    ScriptReport {
+        name: path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        path: path.to_path_buf(),
+        failure,
+        time: start.elapsed(),
+    }
+}
+
+/// Runs every test script found in `dir`
+pub fn grade(dir: &Path) -> io::Result<GradeReport> {
+    grade_with(dir, Limits::default())
+}
+
+/// Runs every test script found in `dir`, stopping each one which exceeds the limits. If `dir`
+/// has a [manifest](crate::manifest), the scripts are found and their chips are loaded as it
+/// says.
+pub fn grade_with(dir: &Path, limits: Limits) -> io::Result<GradeReport> {
+    grade_submission(dir, limits).map_err(|e| match e {
+        Error::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    })
+}
+
+fn grade_submission(dir: &Path, limits: Limits) -> Result<GradeReport, Error> {
+    let manifest = Manifest::find(dir)?.unwrap_or_else(|| Manifest::new(dir));
+    // the scripts are graded, not run for their output, so the submission is left as it was
+    let configure =
+        |runner: TestRunner| manifest.configure(runner.without_output_file().with_limits(limits));
+    Ok(GradeReport {
+        name: submission_name(dir),
+        scripts: manifest
+            .scripts()?
+            .iter()
+            .map(|x| run_graded_with(x, configure))
+            .collect(),
+        error: None,
+    })
+}
+
+fn submission_name(dir: &Path) -> String {
+    dir.file_name()
+        .unwrap_or(dir.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The reports of several submissions, one per subdirectory
+#[derive(Debug, Clone)]
+pub struct BatchReport {
+    pub submissions: Vec<GradeReport>,
+}
+
+/// Grades every subdirectory of `dir` as a separate submission, running up to `jobs` of them at
+/// the same time. Each submission is loaded separately, so they cannot affect each other: one
+/// which cannot be graded is reported with its [`error`](GradeReport::error), and the others are
+/// graded as usual.
+pub fn grade_batch(dir: &Path, limits: Limits, jobs: usize) -> io::Result<BatchReport> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new((0..dirs.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, dirs.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(dir) = dirs.get(index) else {
+                    break;
+                };
+                let report = grade_submission(dir, limits).unwrap_or_else(|e| GradeReport {
+                    name: submission_name(dir),
+                    scripts: Vec::new(),
+                    error: Some(GradeError {
+                        code: e.code(),
+                        message: e.to_string(),
+                    }),
+                });
+                reports.lock().unwrap()[index] = Some(report);
+            });
+        }
+    });
+
+    Ok(BatchReport {
+        submissions: reports
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|x| x.unwrap())
+            .collect(),
+    })
+}
+
+fn table<'a>(
+    heading: &str,
+    rows: impl Iterator<Item = (&'a str, String, Duration)> + Clone,
+) -> String {
+    let width = rows
+        .clone()
+        .map(|(name, ..)| name.len())
+        .chain(once(heading.len()))
+        .max()
+        .unwrap();
+    let mut table = format!("{heading:<width$} |     time | result\n");
+    for (name, result, time) in rows {
+        writeln!(
+            table,
+            "{name:<width$} | {:>7.3}s | {result}",
+            time.as_secs_f64()
+        )
+        .unwrap();
+    }
+    table
+}
+
+pub(crate) fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub(crate) fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl GradeReport {
+    pub fn passed(&self) -> usize {
+        self.scripts.iter().filter(|x| x.passed()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.scripts.len() - self.passed() + self.error.is_some() as usize
+    }
+
+    pub fn time(&self) -> Duration {
+        self.scripts.iter().map(|x| x.time).sum()
+    }
+
+    /// A table with one row per script
+    pub fn summary(&self) -> String {
+        table(
+            "script",
+            self.scripts.iter().map(|script| {
+                let result = match &script.failure {
+                    None => "passed".to_string(),
+                    Some(Failure {
+                        line,
+                        column,
+                        message,
+                    }) => format!("failed at {line}:{column}: {message}"),
+                };
+                (script.name.as_str(), result, script.time)
+            }),
+        )
+    }
+
+    pub fn to_json(&self) -> String {
+        let scripts = self
+            .scripts
+            .iter()
+            .map(|script| {
+                let failure = match &script.failure {
+                    Some(Failure {
+                        line,
+                        column,
+                        message,
+                    }) => format!(
+                        "{{\"line\":{line},\"column\":{column},\"message\":\"{}\"}}",
+                        escape_json(message)
+                    ),
+                    None => "null".to_string(),
+                };
+                format!(
+                    "{{\"name\":\"{}\",\"path\":\"{}\",\"passed\":{},\"time\":{:.6},\"failure\":{failure}}}",
+                    escape_json(&script.name),
+                    escape_json(&script.path.to_string_lossy()),
+                    script.passed(),
+                    script.time.as_secs_f64(),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let error = match &self.error {
+            Some(GradeError { code, message }) => format!(
+                "{{\"code\":\"{code}\",\"message\":\"{}\"}}",
+                escape_json(message)
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"name\":\"{}\",\"passed\":{},\"failed\":{},\"time\":{:.6},\"error\":{error},\"scripts\":[{scripts}]}}",
+            escape_json(&self.name),
+            self.passed(),
+            self.failed(),
+            self.time().as_secs_f64(),
+        )
+    }
+
+    pub fn to_junit(&self) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}",
+            self.junit_suite()
+        )
+    }
+
+    fn junit_suite(&self) -> String {
+        let mut xml = String::new();
+        if let Some(GradeError { code, message }) = &self.error {
+            let name = escape_xml(&self.name);
+            writeln!(
+                xml,
+                "<testsuite name=\"{name}\" tests=\"1\" failures=\"0\" errors=\"1\" time=\"0.000000\">\n  \
+                 <testcase name=\"{name}\" classname=\"{name}\" time=\"0.000000\">\n    \
+                 <error type=\"{code}\" message=\"{}\"/>\n  </testcase>\n</testsuite>",
+                escape_xml(message)
+            )
+            .unwrap();
+            return xml;
+        }
+        writeln!(
+            xml,
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.6}\">",
+            escape_xml(&self.name),
+            self.scripts.len(),
+            self.failed(),
+            self.time().as_secs_f64()
+        )
+        .unwrap();
+        for script in self.scripts.iter() {
+            let open = format!(
+                "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.6}\"",
+                escape_xml(&script.name),
+                escape_xml(&self.name),
+                script.time.as_secs_f64()
+            );
+            match &script.failure {
+                Some(Failure {
+                    line,
+                    column,
+                    message,
+                }) => {
+                    writeln!(xml, "{open}>").unwrap();
+                    writeln!(
+                        xml,
+                        "    <failure message=\"{}\">{}:{line}:{column}</failure>",
+                        escape_xml(message),
+                        escape_xml(&script.path.to_string_lossy()),
+                    )
+                    .unwrap();
+                    writeln!(xml, "  </testcase>").unwrap();
+                }
+                None => writeln!(xml, "{open}/>").unwrap(),
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+impl BatchReport {
+    /// A table with one row per submission
+    pub fn summary(&self) -> String {
+        table(
+            "submission",
+            self.submissions.iter().map(|report| {
+                let result = match &report.error {
+                    Some(GradeError { code, message }) => {
+                        format!("could not be graded: [{code}] {message}")
+                    }
+                    None => format!("{} passed, {} failed", report.passed(), report.failed()),
+                };
+                (report.name.as_str(), result, report.time())
+            }),
+        )
+    }
+
+    pub fn failed(&self) -> usize {
+        self.submissions.iter().map(|x| x.failed()).sum()
+    }
+
+    pub fn to_json(&self) -> String {
+        let submissions = self
+            .submissions
+            .iter()
+            .map(|x| x.to_json())
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{{\"submissions\":[{submissions}]}}")
+    }
+
+    pub fn to_junit(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+        for report in self.submissions.iter() {
+            xml.push_str(report.junit_suite().as_str());
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn report() -> GradeReport {
+        GradeReport {
+            name: "01".to_string(),
+            scripts: vec![
+                ScriptReport {
+                    name: "Not".to_string(),
+                    path: PathBuf::from("01/Not.tst"),
+                    failure: None,
+                    time: Duration::from_millis(2),
+                },
+                ScriptReport {
+                    name: "Xor".to_string(),
+                    path: PathBuf::from("01/Xor.tst"),
+                    failure: Some(Failure {
+                        line: 14,
+                        column: 1,
+                        message: "Pin `out` is not \"1\"".to_string(),
+                    }),
+                    time: Duration::from_millis(3),
+                },
+            ],
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            report().to_json(),
+            "{\"name\":\"01\",\"passed\":1,\"failed\":1,\"time\":0.005000,\"error\":null,\"scripts\":[\
+             {\"name\":\"Not\",\"path\":\"01/Not.tst\",\"passed\":true,\"time\":0.002000,\"failure\":null},\
+             {\"name\":\"Xor\",\"path\":\"01/Xor.tst\",\"passed\":false,\"time\":0.003000,\
+             \"failure\":{\"line\":14,\"column\":1,\"message\":\"Pin `out` is not \\\"1\\\"\"}}]}"
+        );
+    }
+
+    #[test]
+    fn test_junit() {
+        let xml = report().to_junit();
+        assert!(
+            xml.contains("<testsuite name=\"01\" tests=\"2\" failures=\"1\" time=\"0.005000\">")
+        );
+        assert!(xml.contains("<testcase name=\"Not\" classname=\"01\" time=\"0.002000\"/>"));
+        assert!(xml.contains(
+            "<failure message=\"Pin `out` is not &quot;1&quot;\">01/Xor.tst:14:1</failure>"
+        ));
+    }
+
+    #[test]
+    fn test_error() {
+        let report = GradeReport {
+            name: "02".to_string(),
+            scripts: Vec::new(),
+            error: Some(GradeError {
+                code: "E0603",
+                message: "Bad value".to_string(),
+            }),
+        };
+        assert_eq!(report.failed(), 1);
+        assert_eq!(
+            report.to_json(),
+            "{\"name\":\"02\",\"passed\":0,\"failed\":1,\"time\":0.000000,\
+             \"error\":{\"code\":\"E0603\",\"message\":\"Bad value\"},\"scripts\":[]}"
+        );
+        assert!(report
+            .to_junit()
+            .contains("<error type=\"E0603\" message=\"Bad value\"/>"));
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = report().summary();
+        let mut lines = summary.lines();
+        assert_eq!(lines.next(), Some("script |     time | result"));
+        assert_eq!(lines.next(), Some("Not    |   0.002s | passed"));
+        assert_eq!(
+            lines.next(),
+            Some("Xor    |   0.003s | failed at 14:1: Pin `out` is not \"1\"")
+        );
+    }
+}
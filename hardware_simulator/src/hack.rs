@@ -0,0 +1,427 @@
+//! The signals of the Hack CPU in the terms of the machine language, so that a debugger can show
+//! the instruction being run, the ALU flags and the memory bus without decoding pin vectors, along
+//! with random programs to run on it.
+
+use crate::initial_state::StateRng;
+use crate::model::chip::Chip;
+use crate::simulator::{SimulationError, Simulator};
+use std::fmt::{Display, Formatter};
+
+/// A word of a Hack program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// `@value`, which loads the value into the A register
+    Address(u16),
+    /// `dest=comp;jump`
+    Compute {
+        /// The `a` bit and the six control bits of the ALU, `a` first
+        comp: u8,
+        /// The `A`, `D` and `M` bits, in that order from the most significant
+        dest: u8,
+        /// The `lt`, `eq` and `gt` bits, in that order from the most significant
+        jump: u8,
+    },
+}
+
+/// The mnemonics of the computations of the ALU, by the `a` bit and the control bits
+const COMPUTATIONS: &[(u8, &str)] = &[
+    (0b0101010, "0"),
+    (0b0111111, "1"),
+    (0b0111010, "-1"),
+    (0b0001100, "D"),
+    (0b0110000, "A"),
+    (0b0001101, "!D"),
+    (0b0110001, "!A"),
+    (0b0001111, "-D"),
+    (0b0110011, "-A"),
+    (0b0011111, "D+1"),
+    (0b0110111, "A+1"),
+    (0b0001110, "D-1"),
+    (0b0110010, "A-1"),
+    (0b0000010, "D+A"),
+    (0b0010011, "D-A"),
+    (0b0000111, "A-D"),
+    (0b0000000, "D&A"),
+    (0b0010101, "D|A"),
+    (0b1110000, "M"),
+    (0b1110001, "!M"),
+    (0b1110011, "-M"),
+    (0b1110111, "M+1"),
+    (0b1110010, "M-1"),
+    (0b1000010, "D+M"),
+    (0b1010011, "D-M"),
+    (0b1000111, "M-D"),
+    (0b1000000, "D&M"),
+    (0b1010101, "D|M"),
+];
+
+const JUMPS: [&str; 8] = ["", "JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+
+impl Instruction {
+    pub fn decode(word: u16) -> Self {
+        if word >> 15 == 0 {
+            Instruction::Address(word)
+        } else {
+            Instruction::Compute {
+                comp: (word >> 6 & 0b1111111) as u8,
+                dest: (word >> 3 & 0b111) as u8,
+                jump: (word & 0b111) as u8,
+            }
+        }
+    }
+
+    pub fn encode(self) -> u16 {
+        match self {
+            Instruction::Address(value) => value & 0x7fff,
+            Instruction::Compute { comp, dest, jump } => {
+                0b111 << 13
+                    | (comp as u16 & 0b1111111) << 6
+                    | (dest as u16 & 0b111) << 3
+                    | jump as u16 & 0b111
+            }
+        }
+    }
+
+    /// The mnemonic of the computation, such as `D+M`, or `None` for an A-instruction or control
+    /// bits which the language has no mnemonic for
+    pub fn comp_mnemonic(self) -> Option<&'static str> {
+        match self {
+            Instruction::Address(_) => None,
+            Instruction::Compute { comp, .. } => COMPUTATIONS
+                .iter()
+                .find(|(bits, _)| *bits == comp)
+                .map(|(_, mnemonic)| *mnemonic),
+        }
+    }
+
+    /// The mnemonic of the destination, such as `AM`, which is empty if nothing is written
+    pub fn dest_mnemonic(self) -> String {
+        match self {
+            Instruction::Address(_) => String::new(),
+            Instruction::Compute { dest, .. } => [(0b100, 'A'), (0b010, 'D'), (0b001, 'M')]
+                .iter()
+                .filter(|(bit, _)| dest & bit != 0)
+                .map(|(_, register)| register)
+                .collect(),
+        }
+    }
+
+    /// The mnemonic of the jump, such as `JGT`, which is empty if the instruction never jumps
+    pub fn jump_mnemonic(self) -> &'static str {
+        match self {
+            Instruction::Address(_) => "",
+            Instruction::Compute { jump, .. } => JUMPS[jump as usize & 0b111],
+        }
+    }
+
+    /// Whether the instruction writes `M`, the word of the RAM at the address in A
+    pub fn writes_memory(self) -> bool {
+        matches!(self, Instruction::Compute { dest, .. } if dest & 0b001 != 0)
+    }
+}
+
+impl Display for Instruction {
+    /// The instruction in assembly, such as `@21` or `AM=M-1;JGT`. Computations without a
+    /// mnemonic are written as their bits.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Instruction::Compute { comp, .. } = *self else {
+            return write!(f, "@{}", self.encode());
+        };
+        let dest = self.dest_mnemonic();
+        if !dest.is_empty() {
+            write!(f, "{dest}=")?;
+        }
+        match self.comp_mnemonic() {
+            Some(mnemonic) => write!(f, "{mnemonic}")?,
+            None => write!(f, "{comp:07b}")?,
+        }
+        match self.jump_mnemonic() {
+            "" => Ok(()),
+            jump => write!(f, ";{jump}"),
+        }
+    }
+}
+
+/// A program of random instructions for stress testing a CPU, such as by loading it with
+/// [`Simulator::swap_program`], which is the same for the same seed. It is `pairs`
+/// A-instructions, each followed by a C-instruction with a mnemonic, so that A always holds the
+/// constant before it when it is used: an instruction which reads or writes `M` only does so
+/// below the address `memory`, and one which jumps only jumps to the start of a pair. A jump does
+/// not use `M` as well.
+///
+/// Panics if `memory` is 0, or if there are more than 16384 pairs, which would not fit in the
+/// ROM.
+pub fn random_program(seed: u64, pairs: usize, memory: u16) -> Vec<u16> {
+    assert!(memory > 0, "a program needs some memory");
+    assert!(pairs <= 0x4000, "{pairs} pairs do not fit in the ROM");
+    let memory = memory.min(0x8000);
+    let mut rng = StateRng::new(seed);
+    let below = |rng: &mut StateRng, bound: usize| (rng.next_u64() % bound as u64) as u16;
+    let mut words = Vec::with_capacity(pairs * 2);
+    for _ in 0..pairs {
+        // one instruction in four jumps, under one of the seven conditions
+        let jump = match below(&mut rng, 4) {
+            0 => 1 + below(&mut rng, 7) as u8,
+            _ => 0,
+        };
+        let (comp, dest) = loop {
+            let (comp, _) = COMPUTATIONS[below(&mut rng, COMPUTATIONS.len()) as usize];
+            let dest = below(&mut rng, 8) as u8;
+            if jump == 0 || (comp & 0b1000000 == 0 && dest & 0b001 == 0) {
+                break (comp, dest);
+            }
+        };
+        let uses_memory = comp & 0b1000000 != 0 || dest & 0b001 != 0;
+        let value = match (jump, uses_memory) {
+            (0, false) => below(&mut rng, 0x8000),
+            (0, true) => below(&mut rng, memory as usize),
+            _ => 2 * below(&mut rng, pairs),
+        };
+        words.push(Instruction::Address(value).encode());
+        words.push(Instruction::Compute { comp, dest, jump }.encode());
+    }
+    words
+}
+
+/// A word from the bits of a pin, least significant first
+fn word(bits: impl IntoIterator<Item = bool>) -> u16 {
+    bits.into_iter()
+        .enumerate()
+        .fold(0, |x, (i, bit)| x | (bit as u16) << i)
+}
+
+/// The value of a pin of a part, such as `CPU0.outM`, as listed by [`Chip::pin_values`], or
+/// `None` if there is no such pin or the chip does not keep all of its bits
+fn part_pin(values: &[(String, Vec<Option<bool>>)], name: &str) -> Option<u16> {
+    let (_, bits) = values.iter().find(|(pin, _)| pin == name)?;
+    bits.iter().copied().collect::<Option<Vec<_>>>().map(word)
+}
+
+/// The pins of the course CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuSignals {
+    pub instruction: Instruction,
+    pub in_m: u16,
+    pub reset: bool,
+    pub out_m: u16,
+    pub write_m: bool,
+    pub address_m: u16,
+    pub pc: u16,
+}
+
+impl CpuSignals {
+    /// The pins of a simulator whose chip has the interface of the CPU
+    pub fn of_chip(simulator: &Simulator) -> Result<Self, SimulationError> {
+        let get = |pin| simulator.get(pin).map(|x| word(x.iter().copied()));
+        Ok(Self {
+            instruction: Instruction::decode(get("instruction")?),
+            in_m: get("inM")?,
+            reset: get("reset")? == 1,
+            out_m: get("outM")?,
+            write_m: get("writeM")? == 1,
+            address_m: get("addressM")?,
+            pc: get("pc")?,
+        })
+    }
+
+    /// The pins of a CPU which is a part of the chip, such as the `CPU0` of a `Computer`, named as
+    /// for [`Chip::explain`]. Returns `None` if there is no such part, or if it does not keep
+    /// every bit of its pins.
+    pub fn of_part(chip: &Chip, part: &str) -> Option<Self> {
+        let values = chip.pin_values();
+        let get = |pin| part_pin(&values, &format!("{part}.{pin}"));
+        Some(Self {
+            instruction: Instruction::decode(get("instruction")?),
+            in_m: get("inM")?,
+            reset: get("reset")? == 1,
+            out_m: get("outM")?,
+            write_m: get("writeM")? == 1,
+            address_m: get("addressM")?,
+            pc: get("pc")?,
+        })
+    }
+}
+
+/// The flags of the course ALU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AluFlags {
+    pub out: u16,
+    /// Whether `out` is zero
+    pub zr: bool,
+    /// Whether `out` is negative
+    pub ng: bool,
+}
+
+impl AluFlags {
+    /// The outputs of a simulator whose chip has the interface of the ALU
+    pub fn of_chip(simulator: &Simulator) -> Result<Self, SimulationError> {
+        let get = |pin| simulator.get(pin).map(|x| word(x.iter().copied()));
+        Ok(Self {
+            out: get("out")?,
+            zr: get("zr")? == 1,
+            ng: get("ng")? == 1,
+        })
+    }
+
+    /// The outputs of an ALU which is a part of the chip, such as the `CPU0.ALU0` of a
+    /// `Computer`. Returns `None` if there is no such part, or if the bits of its `out` are not
+    /// kept. Flags which are not kept, as they drive nothing, are worked out from `out`.
+    pub fn of_part(chip: &Chip, part: &str) -> Option<Self> {
+        let values = chip.pin_values();
+        let get = |pin| part_pin(&values, &format!("{part}.{pin}"));
+        let out = get("out")?;
+        Some(Self {
+            out,
+            zr: get("zr").map_or(out == 0, |x| x == 1),
+            ng: get("ng").map_or(out >> 15 == 1, |x| x == 1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::chip::build_ctx::ChipBuilder;
+    use crate::simulator::ProgramReset;
+
+    #[test]
+    fn test_instructions() {
+        let text = |word| Instruction::decode(word).to_string();
+        assert_eq!(text(21), "@21");
+        assert_eq!(text(0b1111110010101001), "AM=M-1;JGT");
+        assert_eq!(text(0b1110001100000111), "D;JMP");
+        assert_eq!(text(0b1110101010000000), "0");
+        assert_eq!(text(0b1110111000010000), "D=0111000");
+
+        for word in [21, 0b1111110010101001, 0b1110001100000111] {
+            assert_eq!(Instruction::decode(word).encode(), word);
+        }
+        let instruction = Instruction::decode(0b1111110010101001);
+        assert_eq!(instruction.dest_mnemonic(), "AM");
+        assert!(instruction.writes_memory());
+        assert!(!Instruction::decode(0b1110001100010000).writes_memory());
+    }
+
+    #[test]
+    fn test_random_program() {
+        let program = random_program(7, 500, 16);
+        assert_eq!(program, random_program(7, 500, 16));
+        assert_ne!(program, random_program(8, 500, 16));
+        assert_eq!(program.len(), 1000);
+        let (mut jumps, mut accesses) = (0, 0);
+        for pair in program.chunks(2) {
+            let (Instruction::Address(value), compute) =
+                (Instruction::decode(pair[0]), Instruction::decode(pair[1]))
+            else {
+                panic!("{pair:?} does not start with an A-instruction");
+            };
+            let Instruction::Compute { comp, jump, .. } = compute else {
+                panic!("{pair:?} does not end with a C-instruction");
+            };
+            assert!(compute.comp_mnemonic().is_some(), "{compute}");
+            if comp & 0b1000000 != 0 || compute.writes_memory() {
+                accesses += 1;
+                assert!(value < 16, "{compute} uses M at {value}");
+            }
+            if jump != 0 {
+                jumps += 1;
+                assert!(value % 2 == 0 && value < 1000, "{compute} jumps to {value}");
+                assert!(comp & 0b1000000 == 0 && !compute.writes_memory());
+            }
+        }
+        assert!(jumps > 0 && accesses > 0);
+
+        // the program runs from the ROM of a chip, swapped in without rebuilding it
+        let mut builder = ChipBuilder::new();
+        builder
+            .update_source(
+                "Rom.hdl",
+                "CHIP Rom { IN address[15]; OUT out[16]; PARTS: ROM32K(address=address, out=out); }",
+            )
+            .unwrap();
+        let mut rom = Simulator::new(builder.resolve_chip("Rom").unwrap());
+        assert_eq!(rom.swap_program(&program, ProgramReset::Everything), 1);
+        let bits = |x: u16| (0..15).map(|i| x >> i & 1 == 1).collect::<Vec<_>>();
+        rom.set("address", &bits(999)).unwrap();
+        let out = rom.eval().to_vec();
+        assert_eq!(word(out), program[999]);
+    }
+
+    #[test]
+    fn test_signals() {
+        let dir = std::env::current_dir().unwrap().join("../test_files");
+        let mut builder = ChipBuilder::new();
+        // not the course chips, but with their interfaces
+        builder
+            .update_source(
+                dir.join("ALU.hdl"),
+                "\
+CHIP ALU {
+    IN x[16], y[16], zx, nx, zy, ny, f, no;
+    OUT out[16], zr, ng;
+    PARTS:
+    Mux16(a=x, b=y, sel=f, out=out, out[15]=ng);
+    Or(a=zx, b=nx, out=zr);
+}",
+            )
+            .unwrap();
+        builder
+            .update_source(
+                dir.join("CPU.hdl"),
+                "\
+CHIP CPU {
+    IN inM[16], instruction[16], reset;
+    OUT outM[16], writeM, addressM[15], pc[15];
+    PARTS:
+    ALU(x=instruction, y=inM, zx=reset, nx=reset, zy=reset, ny=reset, f=reset, no=reset,
+        out=outM, out[0..14]=addressM, out[0..14]=pc, zr=writeM);
+}",
+            )
+            .unwrap();
+        builder
+            .update_source(
+                dir.join("Board.hdl"),
+                "\
+CHIP Board {
+    IN inM[16], instruction[16], reset;
+    OUT outM[16], writeM, addressM[15], pc[15];
+    PARTS:
+    CPU(inM=inM, instruction=instruction, reset=reset, outM=outM, writeM=writeM,
+        addressM=addressM, pc=pc);
+}",
+            )
+            .unwrap();
+
+        let bits = |x: u16| (0..16).map(|i| x >> i & 1 == 1).collect::<Vec<_>>();
+        let mut cpu = Simulator::new(builder.resolve_chip("CPU").unwrap());
+        cpu.set("instruction", &bits(0b1111110010101001)).unwrap();
+        cpu.set("inM", &bits(0x8005)).unwrap();
+        cpu.eval();
+        let signals = CpuSignals::of_chip(&cpu).unwrap();
+        assert_eq!(signals.instruction.to_string(), "AM=M-1;JGT");
+        assert_eq!(
+            (signals.out_m, signals.address_m, signals.write_m),
+            (0b1111110010101001, 0b111110010101001, false)
+        );
+
+        let mut board = Simulator::new(builder.resolve_chip("Board").unwrap());
+        board.set("inM", &bits(0x8005)).unwrap();
+        board.set("reset", &[true]).unwrap();
+        board.eval();
+        let signals = CpuSignals::of_part(board.chip(), "CPU0").unwrap();
+        assert_eq!((signals.in_m, signals.reset), (0x8005, true));
+        assert_eq!((signals.out_m, signals.write_m), (0x8005, true));
+        // `ng` drives nothing in the CPU, so its value is not kept but follows from `out`
+        let flags = AluFlags::of_part(board.chip(), "CPU0.ALU0").unwrap();
+        assert_eq!(
+            flags,
+            AluFlags {
+                out: 0x8005,
+                zr: true,
+                ng: true
+            }
+        );
+        assert!(CpuSignals::of_part(board.chip(), "CPU1").is_none());
+        assert!(AluFlags::of_chip(&board).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! A simulator running on a thread of its own, so that a GUI or a server stays responsive while a
+//! chip such as the Computer runs for millions of cycles. The thread is driven by commands sent
+//! through a [`SimulatorHandle`], which are handled between cycles even while the chip is running.
+
+use crate::simulator::{ProgramReset, SimulationError, Simulator};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+enum Command {
+    Set(String, Vec<bool>, Sender<Result<(), SimulationError>>),
+    Step(Sender<Vec<bool>>),
+    Run(Option<u64>),
+    Pause(Sender<usize>),
+    Probe(String, Sender<Result<Vec<bool>, SimulationError>>),
+    SwapProgram(Vec<u16>, ProgramReset, Sender<usize>),
+    Stop,
+}
+
+/// Controls a simulator on a background thread. Dropping the handle stops the thread.
+pub struct SimulatorHandle {
+    commands: Sender<Command>,
+    thread: Option<JoinHandle<Simulator>>,
+}
+
+impl SimulatorHandle {
+    /// Moves the simulator to a new thread, where it waits for commands
+    pub fn spawn(simulator: Simulator) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || serve(simulator, receiver));
+        Self {
+            commands,
+            thread: Some(thread),
+        }
+    }
+
+    /// Sets an input pin, as [`Simulator::set`]
+    pub fn set(&self, pin: &str, value: &[bool]) -> Result<(), SimulationError> {
+        self.request(|reply| Command::Set(pin.to_string(), value.to_vec(), reply))
+    }
+
+    /// Runs a single clock cycle, returning the outputs
+    pub fn step(&self) -> Vec<bool> {
+        self.request(Command::Step)
+    }
+
+    /// Starts running `cycles` clock cycles, or until paused if `None`, and returns at once
+    pub fn run(&self, cycles: Option<u64>) {
+        self.send(Command::Run(cycles));
+    }
+
+    /// Stops a run, returning the time of the clock where it stopped
+    pub fn pause(&self) -> usize {
+        self.request(Command::Pause)
+    }
+
+    /// The current value of a pin, as [`Simulator::get`]. Running chips are probed between
+    /// cycles.
+    pub fn probe(&self, pin: &str) -> Result<Vec<bool>, SimulationError> {
+        self.request(|reply| Command::Probe(pin.to_string(), reply))
+    }
+
+    /// Replaces the program in the `ROM32K`, as [`Simulator::swap_program`]. A running chip
+    /// carries on running with the new program.
+    pub fn swap_program(&self, words: &[u16], reset: ProgramReset) -> usize {
+        self.request(|reply| Command::SwapProgram(words.to_vec(), reset, reply))
+    }
+
+    /// Stops the thread and takes the simulator back
+    pub fn join(mut self) -> Simulator {
+        self.stop().expect("the simulator has not been stopped yet")
+    }
+
+    fn stop(&mut self) -> Option<Simulator> {
+        let thread = self.thread.take()?;
+        self.send(Command::Stop);
+        Some(thread.join().expect("the simulator thread panicked"))
+    }
+
+    fn send(&self, command: Command) {
+        self.commands
+            .send(command)
+            .expect("the simulator thread panicked");
+    }
+
+    fn request<R>(&self, command: impl FnOnce(Sender<R>) -> Command) -> R {
+        let (reply, response) = mpsc::channel();
+        self.send(command(reply));
+        response.recv().expect("the simulator thread panicked")
+    }
+}
+
+impl Drop for SimulatorHandle {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            self.stop();
+        }
+    }
+}
+
+fn serve(mut simulator: Simulator, commands: Receiver<Command>) -> Simulator {
+    // the cycles left in the current run, or `Some(None)` for a run without end
+    let mut running: Option<Option<u64>> = None;
+    loop {
+        let command = if running.is_some() {
+            match commands.try_recv() {
+                Ok(command) => Some(command),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        } else {
+            match commands.recv() {
+                Ok(command) => Some(command),
+                Err(_) => break,
+            }
+        };
+        // replies are ignored if the handle has stopped waiting for them
+        match command {
+            Some(Command::Set(pin, value, reply)) => {
+                let _ = reply.send(simulator.set(&pin, &value));
+            }
+            Some(Command::Step(reply)) => {
+                let _ = reply.send(simulator.cycle().to_vec());
+            }
+            Some(Command::Run(cycles)) => running = (cycles != Some(0)).then_some(cycles),
+            Some(Command::Pause(reply)) => {
+                running = None;
+                let _ = reply.send(simulator.clock().time());
+            }
+            Some(Command::Probe(pin, reply)) => {
+                let _ = reply.send(simulator.get(&pin).map(<[bool]>::to_vec));
+            }
+            Some(Command::SwapProgram(words, reset, reply)) => {
+                let _ = reply.send(simulator.swap_program(&words, reset));
+            }
+            Some(Command::Stop) => break,
+            None => {
+                simulator.cycle();
+                running = match running {
+                    Some(Some(left)) if left > 1 => Some(Some(left - 1)),
+                    Some(None) => Some(None),
+                    _ => None,
+                };
+            }
+        }
+    }
+    simulator
+}
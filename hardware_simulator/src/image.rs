@@ -0,0 +1,260 @@
+//! Memory images, such as programs for the `ROM32K`, in the formats they are stored in: the text
+//! of the course tools, with one 16-bit word per line written in binary, a compact binary format
+//! with two big-endian bytes per word, and the Intel HEX of embedded tools and FPGA flashers.
+
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// A `.hack` file
+    Hack,
+    /// A `.bin` file
+    Binary,
+    /// A `.hex` file
+    IntelHex,
+}
+
+impl ImageFormat {
+    /// The format of a file from its extension. Files which are not `.bin` or `.hex` are read as
+    /// `.hack`.
+    pub fn of(path: &Path) -> Self {
+        Self::from_extension(path).unwrap_or(ImageFormat::Hack)
+    }
+
+    /// The format of a file with one of the extensions of images, or `None` for other files
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "hack" => Some(ImageFormat::Hack),
+            "bin" => Some(ImageFormat::Binary),
+            "hex" => Some(ImageFormat::IntelHex),
+            _ => None,
+        }
+    }
+
+    pub fn parse(self, bytes: &[u8]) -> Result<Vec<u16>, ImageError> {
+        match self {
+            ImageFormat::Hack => parse_hack(&String::from_utf8_lossy(bytes)),
+            ImageFormat::Binary => parse_binary(bytes),
+            ImageFormat::IntelHex => parse_intel_hex(&String::from_utf8_lossy(bytes)),
+        }
+    }
+
+    pub fn write(self, words: &[u16]) -> Vec<u8> {
+        match self {
+            ImageFormat::Hack => write_hack(words).into_bytes(),
+            ImageFormat::Binary => write_binary(words),
+            ImageFormat::IntelHex => write_intel_hex(words).into_bytes(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ImageError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Line {0} of the image is not a 16-bit binary word")]
+    BadLine(usize),
+    #[error("The image ends in the middle of a word")]
+    Truncated,
+    #[error("Line {0} of the image is not a valid Intel HEX record")]
+    BadRecord(usize),
+}
+
+impl ImageError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ImageError::Io(_) => "E0100",
+            ImageError::BadLine(_) => "E0501",
+            ImageError::Truncated => "E0502",
+            ImageError::BadRecord(_) => "E0503",
+        }
+    }
+}
+
+/// Reads the words of a `.hack` file. Blank lines are skipped.
+pub fn parse_hack(text: &str) -> Result<Vec<u16>, ImageError> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            (line.len() == 16)
+                .then(|| u16::from_str_radix(line, 2).ok())
+                .flatten()
+                .ok_or(ImageError::BadLine(i + 1))
+        })
+        .collect()
+}
+
+pub fn write_hack(words: &[u16]) -> String {
+    words.iter().map(|x| format!("{x:016b}\n")).collect()
+}
+
+pub fn parse_binary(bytes: &[u8]) -> Result<Vec<u16>, ImageError> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(ImageError::Truncated);
+    }
+    Ok(bytes
+        .chunks_exact(2)
+        .map(|x| u16::from_be_bytes([x[0], x[1]]))
+        .collect())
+}
+
+pub fn write_binary(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|x| x.to_be_bytes()).collect()
+}
+
+/// The most bytes an Intel HEX image may hold: two for each word in the 15-bit address space of the
+/// Hack computer
+const HEX_LIMIT: usize = 2 << 15;
+
+/// Reads the words of an Intel HEX file, whose bytes are in the order of [`write_binary`]. Gaps
+/// between records are filled with zeros.
+pub fn parse_intel_hex(text: &str) -> Result<Vec<u16>, ImageError> {
+    let mut bytes = Vec::new();
+    let mut base = 0;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bad = || ImageError::BadRecord(i + 1);
+        let record = line
+            .strip_prefix(':')
+            .filter(|x| x.len() % 2 == 0 && x.is_ascii())
+            .ok_or_else(bad)?;
+        let record = (0..record.len())
+            .step_by(2)
+            .map(|x| u8::from_str_radix(&record[x..x + 2], 16).map_err(|_| bad()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let [len, high, low, kind, ..] = record[..] else {
+            return Err(bad());
+        };
+        let data = record.get(4..4 + len as usize).ok_or_else(bad)?;
+        if record.len() != 5 + len as usize
+            || record.iter().fold(0u8, |x, y| x.wrapping_add(*y)) != 0
+        {
+            return Err(bad());
+        }
+        let address = u16::from_be_bytes([high, low]) as usize;
+        match (kind, data) {
+            (0, _) => {
+                let start = base + address;
+                let end = start + data.len();
+                if end > HEX_LIMIT {
+                    return Err(bad());
+                }
+                if bytes.len() < end {
+                    bytes.resize(end, 0);
+                }
+                bytes[start..end].copy_from_slice(data);
+            }
+            (1, _) => break,
+            (2, &[high, low]) => base = (u16::from_be_bytes([high, low]) as usize) << 4,
+            (4, &[high, low]) => base = (u16::from_be_bytes([high, low]) as usize) << 16,
+            // start addresses mean nothing to the Hack computer
+            (3 | 5, _) => {}
+            _ => return Err(bad()),
+        }
+    }
+    parse_binary(&bytes)
+}
+
+/// Writes words as Intel HEX, with 16 bytes to a record
+pub fn write_intel_hex(words: &[u16]) -> String {
+    let mut text = String::new();
+    let mut record = |kind: u8, address: u16, data: &[u8]| {
+        let [high, low] = address.to_be_bytes();
+        let bytes = [&[data.len() as u8, high, low, kind], data].concat();
+        let checksum = bytes.iter().fold(0u8, |x, y| x.wrapping_sub(*y));
+        text.push(':');
+        for byte in bytes.iter().chain([&checksum]) {
+            text.push_str(&format!("{byte:02X}"));
+        }
+        text.push('\n');
+    };
+    for (i, chunk) in write_binary(words).chunks(16).enumerate() {
+        let address = i * 16;
+        if address % (1 << 16) == 0 && address > 0 {
+            record(4, 0, &((address >> 16) as u16).to_be_bytes());
+        }
+        record(0, address as u16, chunk);
+    }
+    record(1, 0, &[]);
+    text
+}
+
+/// Reads an image in the format given by the extension of the file
+pub fn read_image(path: impl AsRef<Path>) -> Result<Vec<u16>, ImageError> {
+    let path = path.as_ref();
+    ImageFormat::of(path).parse(&fs::read(path)?)
+}
+
+/// Writes an image in the format given by the extension of the file
+pub fn write_image(path: impl AsRef<Path>, words: &[u16]) -> Result<(), ImageError> {
+    let path = path.as_ref();
+    Ok(fs::write(path, ImageFormat::of(path).write(words))?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hack() {
+        let text = "0000000000000111\n\n1110110000010000\n";
+        let words = parse_hack(text).unwrap();
+        assert_eq!(words, [7, 0b1110110000010000]);
+        assert_eq!(write_hack(&words), text.replace("\n\n", "\n"));
+        assert!(matches!(
+            parse_hack("0000000000000111\n12\n"),
+            Err(ImageError::BadLine(2))
+        ));
+    }
+
+    #[test]
+    fn test_binary() {
+        let words = [7, 0b1110110000010000, 0xffff];
+        let bytes = write_binary(&words);
+        assert_eq!(bytes, [0x00, 0x07, 0xec, 0x10, 0xff, 0xff]);
+        assert_eq!(parse_binary(&bytes).unwrap(), words);
+        assert!(matches!(
+            parse_binary(&[0, 7, 1]),
+            Err(ImageError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn test_intel_hex() {
+        let words = [7, 0b1110110000010000, 0xffff];
+        let text = write_intel_hex(&words);
+        assert_eq!(text, ":060000000007EC10FFFFF9\n:00000001FF\n");
+        assert_eq!(parse_intel_hex(&text).unwrap(), words);
+
+        // records may come in any order, with gaps between them
+        let text = ":020004000001F9\n:020000000007F7\n:00000001FF\n";
+        assert_eq!(parse_intel_hex(text).unwrap(), [7, 0, 1]);
+
+        let bad = |text| match parse_intel_hex(text) {
+            Err(ImageError::BadRecord(line)) => line,
+            other => panic!("{other:?}"),
+        };
+        // a wrong checksum
+        assert_eq!(bad(":020000000007F7\n:020000000007F8\n"), 2);
+        assert_eq!(bad("0000000007F7\n"), 1);
+        assert_eq!(bad(":0200000000\n"), 1);
+        // past the end of the address space of the Hack computer
+        assert_eq!(bad(":020000040001F9\n:020000000007F7\n"), 2);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(ImageFormat::of(Path::new("Max.bin")), ImageFormat::Binary);
+        assert_eq!(ImageFormat::of(Path::new("Max.hack")), ImageFormat::Hack);
+        assert_eq!(ImageFormat::of(Path::new("Max.hex")), ImageFormat::IntelHex);
+        assert_eq!(ImageFormat::of(Path::new("Max")), ImageFormat::Hack);
+        assert_eq!(ImageFormat::from_extension(Path::new("Max.hdl")), None);
+    }
+}
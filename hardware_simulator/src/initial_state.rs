@@ -0,0 +1,52 @@
+/// How the sequential state of a chip is initialized when it is created or reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InitialState {
+    /// Every register and memory starts out as zero, like in the official simulator
+    #[default]
+    Zero,
+    /// Every register and memory starts out with a pseudo-random pattern, which is the same for
+    /// the same seed. Useful for finding chips which rely on registers being zero.
+    Random { seed: u64 },
+}
+
+/// A small seeded pseudo-random generator (splitmix64) used to fill sequential state
+#[derive(Debug, Clone)]
+pub struct StateRng {
+    state: u64,
+}
+
+impl StateRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u16(&mut self) -> u16 {
+        (self.next_u64() >> 48) as u16
+    }
+
+    pub fn next_bool(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rng_is_deterministic() {
+        let (mut a, mut b) = (StateRng::new(42), StateRng::new(42));
+        let a = (0..8).map(|_| a.next_u64()).collect::<Vec<_>>();
+        let b = (0..8).map(|_| b.next_u64()).collect::<Vec<_>>();
+        assert_eq!(a, b);
+        assert_ne!(a[0], StateRng::new(43).next_u64());
+    }
+}
@@ -0,0 +1,34 @@
+//! A table of the names of chips, pins and parts. Large projects repeat the same few names, such
+//! as `a`, `out` or `Nand0`, in every instance of every chip, so each distinct name is stored once
+//! and shared.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+
+static NAMES: OnceLock<Mutex<HashSet<Arc<str>>>> = OnceLock::new();
+
+/// The shared copy of a name. Equal names are always the same allocation.
+pub fn intern(name: &str) -> Arc<str> {
+    let mut names = NAMES.get_or_init(Default::default).lock().unwrap();
+    match names.get(name) {
+        Some(shared) => shared.clone(),
+        None => {
+            let shared = Arc::<str>::from(name);
+            names.insert(shared.clone());
+            shared
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let a = intern("out");
+        let b = intern(&String::from("out"));
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &intern("in")));
+    }
+}
@@ -1,5 +1,27 @@
 pub mod bus_range;
-mod clock_behavior;
+pub mod catalog;
+pub mod clock_behavior;
+pub mod coverage;
+pub mod deps;
+pub mod diagnostics;
+pub mod error;
+pub mod grade;
+pub mod hack;
+pub mod handle;
+pub mod image;
+pub mod initial_state;
+pub mod intern;
+pub mod manifest;
 pub mod model;
+pub mod scaffold;
+pub mod simulator;
+#[cfg(test)]
+#[path = "../tests/common/temp_dir.rs"]
+mod temp_dir;
+pub mod test_script;
+pub mod trace;
+pub mod vectors;
+
+pub use diagnostics::{parse_hdl, Diagnostic, Diagnostics};
 
 pub type Span<'a> = nom_locate::LocatedSpan<&'a str>;
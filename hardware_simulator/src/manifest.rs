@@ -0,0 +1,285 @@
+//! The optional manifest of a project, an `hdl.toml` file in its directory which tells the tools
+//! where its chips and test scripts are and how to load them, instead of a list of flags:
+//!
+//! ```toml
+//! # the chips, and the scripts which test them, relative to the manifest
+//! chips = ["hdl"]
+//! tests = ["tests"]
+//! # "strict" or "extended"
+//! dialect = "extended"
+//! # whether a chip such as RAM8 is taken from "hdl" or is the "builtin"
+//! prefer = "hdl"
+//! # parts which are always the builtin, whatever is preferred
+//! builtins = ["RAM16K", "ROM32K"]
+//! ```
+//!
+//! Only this subset of TOML is read: one `key = value` pair to a line, where a value is a string
+//! or a list of strings on the same line.
+
+use crate::grade::find_scripts;
+use crate::model::chip::build_ctx::{BuiltinPolicy, ChipBuilder};
+use crate::model::dialect::Dialect;
+use crate::test_script::TestRunner;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The name of the manifest in the directory of a project
+pub const MANIFEST: &str = "hdl.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// The directory of the manifest, which the other paths are relative to
+    pub root: PathBuf,
+    /// The directories holding the chips of the project
+    pub chips: Vec<PathBuf>,
+    /// The directories holding the test scripts of the project
+    pub tests: Vec<PathBuf>,
+    pub dialect: Dialect,
+    pub prefer: BuiltinPolicy,
+    /// The parts which are always the builtin
+    pub builtins: Vec<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum ManifestError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Line {0} of the manifest is not a `key = value` pair")]
+    BadLine(usize),
+    #[error("Line {line} of the manifest has an unknown key `{key}`")]
+    UnknownKey { line: usize, key: String },
+    #[error("Line {line} of the manifest has a bad value for `{key}`")]
+    BadValue { line: usize, key: String },
+}
+
+impl ManifestError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ManifestError::Io(_) => "E0100",
+            ManifestError::BadLine(_) => "E0601",
+            ManifestError::UnknownKey { .. } => "E0602",
+            ManifestError::BadValue { .. } => "E0603",
+        }
+    }
+}
+
+/// A value on the right of a `key = value` pair
+enum Value {
+    String(String),
+    List(Vec<String>),
+}
+
+impl Manifest {
+    /// The manifest of a project without an `hdl.toml`, whose chips and scripts are all in its
+    /// directory
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        Self {
+            chips: vec![root.clone()],
+            tests: vec![root.clone()],
+            root,
+            dialect: Dialect::default(),
+            prefer: BuiltinPolicy::default(),
+            builtins: Vec::new(),
+        }
+    }
+
+    /// Reads the manifest in a project directory, or returns `None` if it has none
+    pub fn find(dir: impl AsRef<Path>) -> Result<Option<Self>, ManifestError> {
+        let path = dir.as_ref().join(MANIFEST);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Self::read(path).map(Some)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ManifestError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Reads the text of a manifest whose paths are relative to `root`. Keys which are left out
+    /// keep the values of [`new`](Self::new).
+    pub fn parse(text: &str, root: impl Into<PathBuf>) -> Result<Self, ManifestError> {
+        let mut manifest = Self::new(root);
+        let root = manifest.root.clone();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or(ManifestError::BadLine(line_number))?;
+            let bad = || ManifestError::BadValue {
+                line: line_number,
+                key: key.to_string(),
+            };
+            let value = parse_value(value).ok_or_else(bad)?;
+            let dirs = |value: Value| match value {
+                Value::List(dirs) => Ok(dirs.iter().map(|x| root.join(x)).collect()),
+                Value::String(_) => Err(bad()),
+            };
+            match key {
+                "chips" => manifest.chips = dirs(value)?,
+                "tests" => manifest.tests = dirs(value)?,
+                "dialect" => {
+                    manifest.dialect = match value {
+                        Value::String(x) if x == "strict" => Dialect::Strict,
+                        Value::String(x) if x == "extended" => Dialect::Extended,
+                        _ => return Err(bad()),
+                    }
+                }
+                "prefer" => {
+                    manifest.prefer = match value {
+                        Value::String(x) if x == "builtin" => BuiltinPolicy::PreferBuiltin,
+                        Value::String(x) if x == "hdl" => BuiltinPolicy::PreferHdl,
+                        _ => return Err(bad()),
+                    }
+                }
+                "builtins" => match value {
+                    Value::List(parts) => manifest.builtins = parts,
+                    Value::String(_) => return Err(bad()),
+                },
+                _ => {
+                    return Err(ManifestError::UnknownKey {
+                        line: line_number,
+                        key: key.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Every test script of the project, in a stable order
+    pub fn scripts(&self) -> io::Result<Vec<PathBuf>> {
+        let mut scripts = Vec::new();
+        for dir in self.tests.iter() {
+            scripts.extend(find_scripts(dir)?);
+        }
+        Ok(scripts)
+    }
+
+    /// Sets up a runner to load chips as the manifest says
+    pub fn configure(&self, runner: TestRunner) -> TestRunner {
+        runner
+            .with_dialect(self.dialect)
+            .with_builtin_policy(self.prefer)
+            .with_builtin_parts(self.builtins.iter().map(String::as_str))
+            .with_chip_dirs(self.chips.clone())
+    }
+
+    /// Sets up a builder to load chips in the dialect and with the builtins of the manifest
+    pub fn configure_builder(&self, builder: &mut ChipBuilder) {
+        builder.set_dialect(self.dialect);
+        builder.set_builtin_policy(self.prefer);
+        builder.use_builtins(self.builtins.iter().map(String::as_str));
+    }
+}
+
+/// The line up to a `#` which is not inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits the items of a list at the commas which are not inside a string
+fn split_list(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut quoted, mut start) = (false, 0);
+    for (i, c) in list.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&list[start..]);
+    items
+}
+
+fn parse_string(text: &str) -> Option<String> {
+    let text = text.strip_prefix('"')?.strip_suffix('"')?;
+    (!text.contains(['"', '\\'])).then(|| text.to_string())
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    match text.strip_prefix('[') {
+        Some(list) => {
+            let list = list.strip_suffix(']')?.trim();
+            let list = list.strip_suffix(',').unwrap_or(list);
+            if list.trim().is_empty() {
+                return Some(Value::List(Vec::new()));
+            }
+            split_list(list)
+                .into_iter()
+                .map(|x| parse_string(x.trim()))
+                .collect::<Option<_>>()
+                .map(Value::List)
+        }
+        None => parse_string(text).map(Value::String),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+# a project with its chips apart from its tests
+chips = ["hdl", "lib"]  # the library holds chips from earlier projects
+tests = ["tests",]
+dialect = "extended"
+prefer = "hdl"
+builtins = []
+builtins = ["RAM16K", "ROM32K"]
+"#;
+        let manifest = Manifest::parse(text, "project").unwrap();
+        assert_eq!(
+            manifest,
+            Manifest {
+                root: PathBuf::from("project"),
+                chips: vec![PathBuf::from("project/hdl"), PathBuf::from("project/lib")],
+                tests: vec![PathBuf::from("project/tests")],
+                dialect: Dialect::Extended,
+                prefer: BuiltinPolicy::PreferHdl,
+                builtins: vec!["RAM16K".to_string(), "ROM32K".to_string()],
+            }
+        );
+        assert_eq!(
+            Manifest::parse(r#"chips = ["a,b"]"#, "project")
+                .unwrap()
+                .chips,
+            [PathBuf::from("project/a,b")]
+        );
+        assert_eq!(
+            Manifest::parse("", "project").unwrap(),
+            Manifest::new("project")
+        );
+
+        let error = |text| Manifest::parse(text, "project").unwrap_err().code();
+        assert_eq!(error("chips"), "E0601");
+        assert_eq!(error("\njobs = \"4\""), "E0602");
+        assert_eq!(error("dialect = \"loose\""), "E0603");
+        assert_eq!(error("chips = \"hdl\""), "E0603");
+        assert_eq!(error("tests = [\"a\" \"b\"]"), "E0603");
+    }
+}
@@ -1,83 +1,657 @@
+use crate::intern::intern;
 use crate::model::chip::builtin::get_builtin;
+use crate::model::chip::canonical::{check_conformance, Deviation};
 use crate::model::chip::error::ModelConstructionError;
 use crate::model::chip::native::build::native_chip;
+use crate::model::chip::plugin::{self, PluginBuiltin, PluginEntry};
 use crate::model::chip::Chip;
-use crate::model::parser::{create_chip, Builtin, Chip as ChipRepr, Form};
+use crate::model::dialect::Dialect;
+use crate::model::generic::{generic_params, specialize, split_arguments};
+use crate::model::parser::{chip_header, create_chip, Builtin, Chip as ChipRepr, Form};
+use crate::model::preprocess::preprocess;
 use crate::Span;
 use anyhow::anyhow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// How deeply generic chips may use each other as parts, so that one which keeps specializing
+/// itself with larger widths fails to load instead of overflowing the stack
+const MAX_NESTING: usize = 64;
+
+/// Which chip a name stands for when it is both a builtin and a chip loaded from HDL, such as a
+/// `RAM8.hdl` written for the course
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuiltinPolicy {
+    /// The builtin, which is faster to simulate. An HDL file of the same name is not loaded as a
+    /// part, as in the course tools.
+    #[default]
+    PreferBuiltin,
+    /// The chip loaded from HDL, so that it can be tested. The builtin is only used when no such
+    /// chip can be found.
+    PreferHdl,
+}
 
 pub struct ChipBuilder {
-    chips: HashMap<String, Chip>,
+    chips: HashMap<Arc<str>, Chip>,
+    /// The chips which have been loaded but not built yet
+    pending: HashSet<Arc<str>>,
+    /// The sources of the chips loaded from HDL, so that they can be built later, or rebuilt when
+    /// one of their parts changes
+    sources: HashMap<Arc<str>, Source>,
+    plugins: HashMap<String, Arc<PluginBuiltin>>,
+    deviations: HashMap<String, Vec<Deviation>>,
+    dialect: Dialect,
+    lazy: bool,
+    policy: BuiltinPolicy,
+    /// Policies which override `policy` for single chips
+    policies: HashMap<Arc<str>, BuiltinPolicy>,
+}
+
+impl Default for ChipBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ChipBuilder {
     pub fn new() -> Self {
         Self {
             chips: HashMap::new(),
+            pending: HashSet::new(),
+            sources: HashMap::new(),
+            plugins: HashMap::new(),
+            deviations: HashMap::new(),
+            dialect: Dialect::default(),
+            lazy: false,
+            policy: BuiltinPolicy::default(),
+            policies: HashMap::new(),
+        }
+    }
+
+    /// Sets the dialect of the HDL files loaded from now on
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Defers building the chips loaded from now on until they are first resolved, either
+    /// directly or as a part of another chip. Files are still read and parsed when they are
+    /// loaded, but errors in connecting the parts of a chip only show up once it is resolved.
+    pub fn set_lazy(&mut self, lazy: bool) {
+        self.lazy = lazy;
+    }
+
+    /// Sets which chip a name stands for when it is both a builtin and a chip loaded from HDL.
+    /// Chips which have already been built keep the parts they were built with.
+    pub fn set_builtin_policy(&mut self, policy: BuiltinPolicy) {
+        self.policy = policy;
+    }
+
+    /// Sets the policy for a single chip, overriding the one given to
+    /// [`set_builtin_policy`](Self::set_builtin_policy). This substitutes the builtin for one part
+    /// of a design, such as the `RAM16K` of a computer whose CPU is under test, or the other way
+    /// around. Chips which use it, directly or through other parts, are rebuilt when they are next
+    /// resolved. A chip whose file was never loaded, because the builtin was used instead, is
+    /// still found as the builtin.
+    pub fn set_chip_policy(&mut self, name: &str, policy: BuiltinPolicy) {
+        let previous = self.builtin_policy(name);
+        self.policies.insert(intern(name), policy);
+        if policy != previous {
+            self.invalidate_users(name);
+        }
+    }
+
+    /// Uses the builtins for these parts, whatever the policy of the builder, see
+    /// [`set_chip_policy`](Self::set_chip_policy)
+    pub fn use_builtins<'a>(&mut self, parts: impl IntoIterator<Item = &'a str>) {
+        for part in parts {
+            self.set_chip_policy(part, BuiltinPolicy::PreferBuiltin);
+        }
+    }
+
+    pub fn builtin_policy(&self, name: &str) -> BuiltinPolicy {
+        self.policies.get(name).copied().unwrap_or(self.policy)
+    }
+
+    /// Registers the builtins provided by a plugin entry point which is linked into the program.
+    ///
+    /// # Safety
+    /// See [`plugin::register_plugin`]
+    pub unsafe fn register_plugin(
+        &mut self,
+        entry: PluginEntry,
+    ) -> Result<(), ModelConstructionError> {
+        self.add_plugin_builtins(plugin::register_plugin(entry)?);
+        Ok(())
+    }
+
+    /// Registers the builtins provided by a plugin shared library.
+    ///
+    /// # Safety
+    /// See [`plugin::load_plugin`]
+    #[cfg(feature = "plugins")]
+    pub unsafe fn load_plugin(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), ModelConstructionError> {
+        self.add_plugin_builtins(plugin::load_plugin(path.as_ref())?);
+        Ok(())
+    }
+
+    fn add_plugin_builtins(&mut self, builtins: Vec<PluginBuiltin>) {
+        for builtin in builtins {
+            self.plugins
+                .insert(builtin.name().to_string(), Arc::new(builtin));
+        }
+    }
+
+    fn builtin(&self, name: &str) -> Option<Chip> {
+        get_builtin(name)
+            .or_else(|| self.plugins.get(name).map(|x| x.instantiate()))
+            .map(Chip::Builtin)
+    }
+
+    /// Loads a chip from an HDL file. Parts which are neither builtins nor already loaded are
+    /// first loaded from files of the same name in the same directory.
+    pub fn add_hdl(&mut self, path: impl AsRef<Path>) -> Result<(), ModelConstructionError> {
+        self.add_hdl_inner(path.as_ref(), &mut Vec::new())
+    }
+
+    /// Reloads a chip from the edited text of its file, such as the buffer of an editor, keeping
+    /// everything else which has been loaded. Nothing is parsed if the text is the same as when
+    /// the chip was last loaded. Otherwise the chip is rebuilt, and so is every chip which uses
+    /// it, directly or through other parts, once it is next resolved. Returns whether the chip
+    /// changed.
+    pub fn update_source(
+        &mut self,
+        path: impl AsRef<Path>,
+        source: &str,
+    ) -> Result<bool, ModelConstructionError> {
+        let path = path.as_ref();
+        let source = match self.dialect {
+            Dialect::Strict => source.to_string(),
+            Dialect::Extended => preprocess(source, path)?,
+        };
+        self.add_text(path, &source, &mut Vec::new())
+    }
+
+    /// Loads every HDL file in a directory, using up to `jobs` threads. The files are parsed at
+    /// the same time, and then the chips are built in rounds: each round builds, at the same
+    /// time, every chip whose parts are all builtins or already built. Chips with parts from
+    /// outside the directory, such as specializations of generic chips, are left for last and
+    /// loaded one by one as by [`add_hdl`](Self::add_hdl).
+    ///
+    /// If the builder is [lazy](Self::set_lazy), the files are only parsed.
+    ///
+    /// Chips which cannot be loaded are left out, and returned with their errors.
+    pub fn add_project(
+        &mut self,
+        dir: impl AsRef<Path>,
+        jobs: usize,
+    ) -> Vec<(PathBuf, ModelConstructionError)> {
+        let dir = dir.as_ref();
+        let mut paths = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|x| Some(x.ok()?.path()))
+                .filter(|x| x.extension().is_some_and(|x| x == "hdl"))
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                return vec![(
+                    dir.to_path_buf(),
+                    ModelConstructionError::Unk(Some(e.into())),
+                )]
+            }
+        };
+        paths.sort();
+
+        let dialect = self.dialect;
+        let mut failures = Vec::new();
+        let mut pending = Vec::new();
+        for (path, scanned) in paths
+            .iter()
+            .zip(parallel_map(&paths, jobs, |x| scan(x, dialect)))
+        {
+            match scanned {
+                Ok(Some(scanned)) => pending.push(scanned),
+                // generic chips are only loaded through their specializations
+                Ok(None) => {}
+                Err(e) => failures.push((path.clone(), e)),
+            }
+        }
+
+        if self.lazy {
+            let names = pending
+                .iter()
+                .map(|x| x.name.clone())
+                .collect::<HashSet<_>>();
+            for chip in pending {
+                if chip
+                    .parts
+                    .iter()
+                    .all(|part| names.contains(part) || self.is_loaded(part))
+                {
+                    let name = intern(&chip.name);
+                    self.set_deviations(&name, chip.deviations);
+                    self.chips.remove(&name);
+                    self.pending.insert(name.clone());
+                    self.set_source(name, chip.source, &chip.parts);
+                } else if let Err(e) = self.add_hdl(&chip.path) {
+                    failures.push((chip.path, e));
+                }
+            }
+            failures.sort_by(|a, b| a.0.cmp(&b.0));
+            return failures;
+        }
+
+        loop {
+            let waiting = pending
+                .iter()
+                .map(|x| x.name.clone())
+                .collect::<HashSet<_>>();
+            let (ready, blocked) = pending.into_iter().partition::<Vec<_>, _>(|chip| {
+                chip.parts
+                    .iter()
+                    .all(|part| !waiting.contains(part) && self.is_loaded(part))
+            });
+            pending = blocked;
+            if ready.is_empty() {
+                break;
+            }
+            // the parts are resolved up front, so that the chips of a round are built without
+            // waiting on each other for the builder
+            let parts = ready
+                .iter()
+                .flat_map(|chip| chip.parts.iter())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .filter_map(|part| Some((part.clone(), self.resolve_chip(part).ok()?)))
+                .collect::<HashMap<_, _>>();
+            let builder = &*self;
+            let built = parallel_map(&ready, jobs, |chip| chip.build(builder, &parts));
+            for (chip, built) in ready.into_iter().zip(built) {
+                match built {
+                    Ok(built) => {
+                        let name = intern(&chip.name);
+                        self.set_deviations(&name, chip.deviations);
+                        self.pending.remove(&name);
+                        self.chips.insert(name.clone(), built);
+                        self.set_source(name, chip.source, &chip.parts);
+                    }
+                    Err(e) => failures.push((chip.path, e)),
+                }
+            }
+        }
+
+        for chip in pending {
+            if let Err(e) = self.add_hdl(&chip.path) {
+                failures.push((chip.path, e));
+            }
         }
+        failures.sort_by(|a, b| a.0.cmp(&b.0));
+        failures
+    }
+
+    /// Whether a part is a chip which has been loaded, or a builtin which is used instead of
+    /// loading a chip of the same name
+    fn is_loaded(&self, part: &str) -> bool {
+        self.chips.contains_key(part)
+            || self.pending.contains(part)
+            || (self.builtin_policy(part) == BuiltinPolicy::PreferBuiltin
+                && self.builtin(part).is_some())
+    }
+
+    fn set_source(&mut self, name: Arc<str>, text: String, parts: &[impl AsRef<str>]) {
+        let parts = parts.iter().map(|x| intern(x.as_ref())).collect();
+        let dialect = self.dialect;
+        self.sources.insert(
+            name,
+            Source {
+                text,
+                parts,
+                dialect,
+            },
+        );
     }
 
-    pub unsafe fn add_hdl(&mut self, path: impl AsRef<Path>) -> Result<(), ModelConstructionError> {
-        fn inner(ctx: &mut ChipBuilder, path: &Path) -> Result<Chip, ModelConstructionError> {
-            let name = path
-                .file_stem()
-                .ok_or(ModelConstructionError::Unk(Some(anyhow!(
-                    "Could not read the path: {path:?}"
-                ))))?
-                .to_string_lossy()
-                .to_string();
-            match path.extension() {
-                Some(x) if x == OsStr::new("hdl") => {
-                    let str = fs::read_to_string(path)
-                        .map_err(|_| ModelConstructionError::ChipNotFound(name))?;
-                    let buf = Span::from(str.as_str());
-                    let chip =
-                        create_chip(buf).map_err(|_| ModelConstructionError::HdlParseError)?;
-                    ctx.make_hdl(chip)
-                        .map_err(|_| ModelConstructionError::ConstructionError)
+    /// Marks every chip which uses the chip, directly or through other parts, to be built again
+    /// when it is next resolved
+    fn invalidate_users(&mut self, chip: &str) {
+        let mut changed = vec![intern(chip)];
+        while let Some(chip) = changed.pop() {
+            for (user, source) in self.sources.iter() {
+                if source.parts.contains(&chip) && self.chips.remove(user).is_some() {
+                    self.pending.insert(user.clone());
+                    changed.push(user.clone());
                 }
-                Some(_) => Err(ModelConstructionError::ChipNotFound(name)),
-                None => Err(ModelConstructionError::Unk(None)),
             }
         }
+    }
 
-        let chip = inner(self, path.as_ref())?;
-        self.chips.insert(chip.interface().name, chip);
+    fn set_deviations(&mut self, chip: &str, deviations: Vec<Deviation>) {
+        if deviations.is_empty() {
+            self.deviations.remove(chip);
+        } else {
+            self.deviations.insert(chip.to_string(), deviations);
+        }
+    }
+
+    /// `loading` holds the names of the chips whose parts are being loaded, outermost first
+    fn add_hdl_inner(
+        &mut self,
+        path: &Path,
+        loading: &mut Vec<String>,
+    ) -> Result<(), ModelConstructionError> {
+        let source = read_hdl(path, self.dialect)?;
+        self.add_text(path, &source, loading)?;
         Ok(())
     }
 
+    /// Loads the preprocessed text of an HDL file unless it is the same as the text the chip was
+    /// loaded from before, returning whether it was loaded
+    fn add_text(
+        &mut self,
+        path: &Path,
+        source: &str,
+        loading: &mut Vec<String>,
+    ) -> Result<bool, ModelConstructionError> {
+        if self.dialect == Dialect::Extended {
+            if let Some((name, _)) = generic_params(source) {
+                return Err(ModelConstructionError::GenericChip(name));
+            }
+        }
+        let name = chip_header(Span::new(source)).map_or("", |(_, name)| *name);
+        let previous = self.sources.get(name).map(|x| x.text == source);
+        if previous == Some(true) && (self.chips.contains_key(name) || self.pending.contains(name))
+        {
+            return Ok(false);
+        }
+        self.add_source(path, source, loading)?;
+        if previous.is_some() {
+            self.invalidate_users(name);
+        }
+        Ok(true)
+    }
+
+    /// Loads a part such as `Mux<16>` from the generic chip in the file of its base name, next
+    /// to the file at `path`
+    fn add_specialization(
+        &mut self,
+        path: &Path,
+        part: &str,
+        loading: &mut Vec<String>,
+    ) -> Result<(), ModelConstructionError> {
+        if loading.len() >= MAX_NESTING {
+            return Err(ModelConstructionError::NestingLimit(MAX_NESTING));
+        }
+        let bad = || ModelConstructionError::BadSpecialization(part.to_string());
+        let (base, _) = split_arguments(part).ok_or_else(bad)?;
+        let source = read_hdl(&path.with_file_name(format!("{base}.hdl")), self.dialect)?;
+        let source = specialize(&source, part).ok_or_else(bad)?;
+        self.add_source(path, &source, loading)
+    }
+
+    fn add_source(
+        &mut self,
+        path: &Path,
+        source: &str,
+        loading: &mut Vec<String>,
+    ) -> Result<(), ModelConstructionError> {
+        let chip =
+            create_chip(Span::from(source)).map_err(ModelConstructionError::syntax(source))?;
+        check_dialect(self.dialect, &chip)?;
+        // a part which is being loaded further up is part of a cycle, and is left to fail when
+        // it is resolved
+        loading.push(chip.name.to_string());
+        for part in chip.parts() {
+            if self.is_loaded(part) || loading.iter().any(|x| x == part) {
+                continue;
+            }
+            if self.dialect == Dialect::Extended && part.contains('<') {
+                self.add_specialization(path, part, loading)?;
+            } else {
+                let dependency = path.with_file_name(format!("{part}.hdl"));
+                if dependency.is_file() {
+                    self.add_hdl_inner(&dependency, loading)?;
+                }
+            }
+        }
+        loading.pop();
+
+        self.set_deviations(&chip.name, check_conformance(&chip.interface()));
+        let name = intern(&chip.name);
+        let parts = chip.parts();
+        self.set_source(name.clone(), source.to_string(), &parts);
+        if self.lazy {
+            self.chips.remove(&name);
+            self.pending.insert(name);
+        } else {
+            let chip = self.make_hdl(chip)?;
+            self.pending.remove(&name);
+            self.chips.insert(name, chip);
+        }
+        Ok(())
+    }
+
+    /// The ways in which a loaded chip differs from the course interface of the same name
+    pub fn deviations(&self, chip: &str) -> &[Deviation] {
+        self.deviations.get(chip).map_or(&[], |x| x.as_slice())
+    }
+
+    /// The builtin of the given name, even if a chip of the same name has been loaded from HDL
+    pub fn builtin_chip(&self, name: &str) -> Option<Chip> {
+        self.builtin(name)
+    }
+
+    /// The chip of the given name which was loaded from HDL, even if it is shadowed by a builtin
+    pub fn hdl_chip(&mut self, name: &str) -> Option<Chip> {
+        self.loaded(name).ok().flatten()
+    }
+
+    /// The chip of the given name, either a builtin or a chip loaded from HDL as chosen by the
+    /// [policy](Self::set_builtin_policy)
     pub fn resolve_chip(&mut self, target: &str) -> Result<Chip, ModelConstructionError> {
-        get_builtin(target)
-            .map(|x| Chip::Builtin(x))
-            .or_else(|| self.chips.get(&target.to_string()).cloned())
-            .ok_or(ModelConstructionError::ChipNotFound(target.to_string()))
+        let chip = match self.builtin_policy(target) {
+            BuiltinPolicy::PreferBuiltin => match self.builtin(target) {
+                Some(chip) => Some(chip),
+                None => self.loaded(target)?,
+            },
+            BuiltinPolicy::PreferHdl => match self.loaded(target)? {
+                Some(chip) => Some(chip),
+                None => self.builtin(target),
+            },
+        };
+        chip.ok_or(ModelConstructionError::ChipNotFound(target.to_string()))
+    }
+
+    /// A chip loaded from HDL, which is built first if it was loaded lazily
+    fn loaded(&mut self, name: &str) -> Result<Option<Chip>, ModelConstructionError> {
+        // the chip stops being pending while it is built, so that a chip which is part of
+        // itself is not found instead of being built forever
+        if let Some(name) = self.pending.take(name) {
+            let source = &self.sources[&name];
+            let (source, dialect) = (source.text.clone(), source.dialect);
+            let chip = create_chip(Span::from(source.as_str()))
+                .map_err(ModelConstructionError::syntax(&source))?;
+            check_dialect(dialect, &chip)?;
+            let chip = self.make_hdl(chip)?;
+            self.chips.insert(name, chip);
+        }
+        Ok(self.chips.get(name).cloned())
     }
 
     fn make_hdl(&mut self, chip_repr: ChipRepr) -> Result<Chip, ModelConstructionError> {
-        let interface = chip_repr.interface();
-        match chip_repr.logic {
-            Form::Native(connections) => native_chip(self, interface, connections)
-                .map(|x| Chip::Native(x))
-                .map_err(|_| {
-                    ModelConstructionError::Unk(Some(anyhow!(
-                        "Error somewhere in construction of native chip"
-                    )))
-                }),
-            Form::Builtin(Builtin { name, .. }) => get_builtin(*name)
-                .map(|x| Chip::Builtin(x))
-                .ok_or(ModelConstructionError::ChipNotFound(name.to_string())),
+        let builtin = match &chip_repr.logic {
+            Form::Builtin(Builtin { name, .. }) => self.builtin(name),
+            Form::Native(_) => None,
+        };
+        make_chip(chip_repr, |_| builtin, |x| self.resolve_chip(x).ok())
+    }
+}
+
+/// The source of a chip loaded from HDL, after preprocessing
+struct Source {
+    text: String,
+    parts: Vec<Arc<str>>,
+    /// The dialect the chip was loaded in, which it is built in even if the dialect changes
+    dialect: Dialect,
+}
+
+/// A chip of a project which has been parsed, but not built
+struct Scanned {
+    path: PathBuf,
+    source: String,
+    name: String,
+    parts: Vec<String>,
+    deviations: Vec<Deviation>,
+}
+
+/// Reads and parses an HDL file, or returns `None` for a generic chip
+fn scan(path: &Path, dialect: Dialect) -> Result<Option<Scanned>, ModelConstructionError> {
+    let source = read_hdl(path, dialect)?;
+    if dialect == Dialect::Extended && generic_params(&source).is_some() {
+        return Ok(None);
+    }
+    let chip = create_chip(Span::from(source.as_str()))
+        .map_err(ModelConstructionError::syntax(&source))?;
+    check_dialect(dialect, &chip)?;
+    let (name, parts, deviations) = (
+        chip.name.to_string(),
+        chip.parts().into_iter().map(String::from).collect(),
+        check_conformance(&chip.interface()),
+    );
+    Ok(Some(Scanned {
+        path: path.to_path_buf(),
+        source,
+        name,
+        parts,
+        deviations,
+    }))
+}
+
+impl Scanned {
+    /// Builds the chip from its parts, which have already been resolved, and the builtins of a
+    /// builder which is shared between threads
+    fn build(
+        &self,
+        builder: &ChipBuilder,
+        parts: &HashMap<String, Chip>,
+    ) -> Result<Chip, ModelConstructionError> {
+        let chip = create_chip(Span::from(self.source.as_str()))
+            .map_err(ModelConstructionError::syntax(&self.source))?;
+        check_dialect(builder.dialect, &chip)?;
+        make_chip(chip, |x| builder.builtin(x), |x| parts.get(x).cloned())
+    }
+}
+
+/// Runs `f` on every item on up to `jobs` threads, keeping the results in order
+fn parallel_map<T: Sync, R: Send>(items: &[T], jobs: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|x| x.unwrap())
+        .collect()
+}
+
+/// Builds a chip from its parsed form, looking up a `BUILTIN` implementation with `builtin` and
+/// every part with `resolve`
+fn make_chip(
+    chip_repr: ChipRepr,
+    builtin: impl FnOnce(&str) -> Option<Chip>,
+    resolve: impl FnMut(&str) -> Option<Chip>,
+) -> Result<Chip, ModelConstructionError> {
+    let interface = chip_repr.interface();
+    match chip_repr.logic {
+        Form::Native(connections) => Ok(Chip::Native(Box::new(native_chip(
+            resolve,
+            interface,
+            connections,
+        )?))),
+        Form::Builtin(Builtin { name, .. }) => {
+            builtin(*name).ok_or(ModelConstructionError::ChipNotFound(name.to_string()))
         }
     }
 }
 
+fn read_hdl(path: &Path, dialect: Dialect) -> Result<String, ModelConstructionError> {
+    let name = path
+        .file_stem()
+        .ok_or(ModelConstructionError::Unk(Some(anyhow!(
+            "Could not read the path: {path:?}"
+        ))))?
+        .to_string_lossy()
+        .to_string();
+    match path.extension() {
+        Some(x) if x == OsStr::new("hdl") => {
+            let source =
+                fs::read_to_string(path).map_err(|_| ModelConstructionError::ChipNotFound(name))?;
+            match dialect {
+                Dialect::Strict => Ok(source),
+                Dialect::Extended => Ok(preprocess(&source, path)?),
+            }
+        }
+        Some(_) => Err(ModelConstructionError::ChipNotFound(name)),
+        None => Err(ModelConstructionError::Unk(None)),
+    }
+}
+
+/// Fails on the first extension used by the chip which the dialect does not accept
+fn check_dialect(dialect: Dialect, chip: &ChipRepr) -> Result<(), ModelConstructionError> {
+    match dialect.rejected(chip).first() {
+        Some(&(extension, _)) => Err(ModelConstructionError::Extension(extension)),
+        None => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use petgraph::dot::Dot;
     use super::*;
+    use crate::model::dialect::Extension;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn tree() {
+        let dir = std::env::current_dir().unwrap().join("../test_files");
+        let mut ctx = ChipBuilder::new();
+        ctx.add_hdl(dir.join("DMux.hdl")).unwrap();
+        let chip = ctx.resolve_chip("DMux").unwrap();
+        assert_eq!(
+            format!("{chip:?}"),
+            "\
+DMux
+  Not0: Not
+    Nand0: Nand
+  And0: And
+    Nand0: Nand
+    Nand1: Nand
+  And1: And
+    Nand0: Nand
+    Nand1: Nand"
+        );
+        assert_eq!(format!("{:?}", ctx.resolve_chip("Nand").unwrap()), "Nand");
+    }
 
     #[test]
     fn general() {
@@ -85,15 +659,231 @@ mod test {
         dir.push("../test_files");
 
         let mut ctx = ChipBuilder::new();
-        assert!(matches!(ctx.add_hdl(dir.join("Not.hdl")), Ok(_)));
-        assert!(matches!(ctx.add_hdl(dir.join("And.hdl")), Ok(_)));
-        assert!(matches!(ctx.add_hdl(dir.join("DMux.hdl")), Ok(_)));
-        assert!(matches!(ctx.add_hdl(dir.join("DMux4Way.hdl")), Ok(_)));
-        assert!(matches!(ctx.add_hdl(dir.join("DMux8Way.hdl")), Ok(_)));
+        assert!(ctx.add_hdl(dir.join("Not.hdl")).is_ok());
+        assert!(ctx.add_hdl(dir.join("And.hdl")).is_ok());
+        assert!(ctx.add_hdl(dir.join("DMux.hdl")).is_ok());
+        assert!(ctx.add_hdl(dir.join("DMux4Way.hdl")).is_ok());
+        assert!(ctx.add_hdl(dir.join("DMux8Way.hdl")).is_ok());
         let chip = ctx.resolve_chip("DMux8Way").unwrap();
         if let Chip::Native(chip) = chip {
-            println!("{}", Dot::new(&chip.conn_graph))
+            println!("{}", chip.dot())
         }
         // assert!(matches!(ctx.resolve_chip("DMux8Way"), Ok(_)));
     }
+
+    #[test]
+    fn project() {
+        let dir = std::env::current_dir().unwrap().join("../test_files");
+        let mut ctx = ChipBuilder::new();
+        let failures = ctx
+            .add_project(&dir, 4)
+            .into_iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        // the chips which are still left as exercises load with no parts, and output 0
+        assert!(failures.is_empty(), "{failures:?}");
+        assert_eq!(
+            ctx.resolve_chip("Xor").unwrap().eval(&[true, false]),
+            [false]
+        );
+
+        let mut serial = ChipBuilder::new();
+        serial.add_hdl(dir.join("Mux8Way16.hdl")).unwrap();
+        let (mut a, mut b) = (
+            ctx.resolve_chip("Mux8Way16").unwrap(),
+            serial.resolve_chip("Mux8Way16").unwrap(),
+        );
+        let input = (0..a.interface().input_width())
+            .map(|x| x % 3 == 0)
+            .collect::<Vec<_>>();
+        assert_eq!(a.eval(&input), b.eval(&input));
+        assert!(ctx.resolve_chip("Bit").is_ok());
+    }
+
+    #[test]
+    fn lazy() {
+        let dir = std::env::current_dir().unwrap().join("../test_files");
+        let mut ctx = ChipBuilder::new();
+        ctx.set_lazy(true);
+        ctx.add_hdl(dir.join("Mux8Way16.hdl")).unwrap();
+        assert!(ctx.chips.is_empty());
+        assert!(ctx.pending.contains("Mux4Way16"));
+
+        assert!(ctx.resolve_chip("Mux8Way16").is_ok());
+        assert!(ctx.chips.contains_key("Mux4Way16"));
+        assert!(!ctx.pending.contains("Mux4Way16"));
+
+        // a part with a pin it does not have is only found once the chip is built
+        let dir = TempDir::new("lazy");
+        fs::write(
+            dir.join("Bad.hdl"),
+            "CHIP Bad { IN a; OUT out; PARTS: Not(x=a, out=out); }",
+        )
+        .unwrap();
+        let loaded = ctx.add_hdl(dir.join("Bad.hdl"));
+        assert!(loaded.is_ok());
+        assert!(ctx.resolve_chip("Bad").is_err());
+        assert!(ctx.resolve_chip("Bad").is_err());
+    }
+
+    #[test]
+    fn strict_project() {
+        let dir = TempDir::new("strict");
+        let files = [
+            (
+                "Labelled",
+                "CHIP Labelled { IN a; OUT out; PARTS: inv: Nand(a=a, b=a, out=out); }",
+            ),
+            (
+                "Number",
+                "CHIP Number { IN a; OUT out; PARTS: Nand(a=a, b=1, out=out); }",
+            ),
+            (
+                "Generic",
+                "CHIP Generic { IN a; OUT out; PARTS: Nand<1>(a=a, b=a, out=out); }",
+            ),
+            (
+                "Plain",
+                "CHIP Plain { IN a; OUT out; PARTS: Nand(a=a, b=a, out=out); }",
+            ),
+        ];
+        for (name, source) in files {
+            fs::write(dir.join(format!("{name}.hdl")), source).unwrap();
+        }
+
+        for lazy in [false, true] {
+            let mut ctx = ChipBuilder::new();
+            ctx.set_lazy(lazy);
+            let failures = ctx
+                .add_project(&dir, 4)
+                .into_iter()
+                .map(|(path, e)| match e {
+                    ModelConstructionError::Extension(extension) => (
+                        path.file_stem().unwrap().to_string_lossy().to_string(),
+                        extension,
+                    ),
+                    e => panic!("{e}"),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                failures,
+                [
+                    ("Generic".to_string(), Extension::Generic),
+                    ("Labelled".to_string(), Extension::Label),
+                    ("Number".to_string(), Extension::Number),
+                ]
+            );
+            for name in ["Generic", "Labelled", "Number"] {
+                assert!(ctx.resolve_chip(name).is_err());
+            }
+            assert!(ctx.resolve_chip("Plain").is_ok());
+        }
+
+        // a chip loaded lazily is built in the dialect it was loaded in
+        let mut ctx = ChipBuilder::new();
+        ctx.set_lazy(true);
+        ctx.set_dialect(Dialect::Extended);
+        ctx.add_hdl(dir.join("Labelled.hdl")).unwrap();
+        ctx.set_dialect(Dialect::Strict);
+        let labelled = ctx.resolve_chip("Labelled");
+        assert!(labelled.is_ok());
+    }
+
+    #[test]
+    fn update_source() {
+        use crate::simulator::Simulator;
+
+        let dir = std::env::current_dir().unwrap().join("../test_files");
+        let mut ctx = ChipBuilder::new();
+        ctx.add_hdl(dir.join("Mux8Way16.hdl")).unwrap();
+        let before = ctx.resolve_chip("Mux8Way16").unwrap();
+
+        let path = dir.join("Mux4Way16.hdl");
+        let source = fs::read_to_string(&path).unwrap();
+        assert!(!ctx.update_source(&path, &source).unwrap());
+        assert!(ctx.chips.contains_key("Mux8Way16"));
+
+        // a Mux4Way16 which always selects `a`
+        let edited = "\
+CHIP Mux4Way16 {
+    IN a[16], b[16], c[16], d[16], sel[2];
+    OUT out[16];
+    PARTS:
+    Mux16(a=a, b=a, sel=sel[0], out=out);
+}";
+        assert!(ctx.update_source(&path, edited).unwrap());
+        assert!(ctx.pending.contains("Mux8Way16"));
+        let after = ctx.resolve_chip("Mux8Way16").unwrap();
+        let select_b = |chip: Chip| {
+            let mut simulator = Simulator::new(chip);
+            simulator.set("b", &[true; 16]).unwrap();
+            simulator.set("sel", &[true, false, false]).unwrap();
+            simulator.eval().to_vec()
+        };
+        assert_eq!(select_b(before), vec![true; 16]);
+        assert_eq!(select_b(after), vec![false; 16]);
+        // the edited chip is kept when the unchanged file is loaded again
+        ctx.add_hdl(dir.join("Mux8Way16.hdl")).unwrap();
+        assert_eq!(ctx.sources["Mux4Way16"].text, edited);
+    }
+
+    #[test]
+    fn builtin_policy() {
+        use crate::simulator::Simulator;
+
+        // a DFF which is really an inverter, to tell it apart from the builtin
+        let dir = TempDir::new("policy");
+        fs::write(
+            dir.join("DFF.hdl"),
+            "CHIP DFF { IN in; OUT out; PARTS: Nand(a=in, b=in, out=out); }",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("Top.hdl"),
+            "CHIP Top { IN in; OUT out; PARTS: DFF(in=in, out=out); }",
+        )
+        .unwrap();
+        let out = |policy: Option<BuiltinPolicy>, per_chip: bool| {
+            let mut ctx = ChipBuilder::new();
+            if let Some(policy) = policy {
+                if per_chip {
+                    ctx.set_chip_policy("DFF", policy);
+                } else {
+                    ctx.set_builtin_policy(policy);
+                }
+            }
+            ctx.add_hdl(dir.join("Top.hdl")).unwrap();
+            let dff = Simulator::new(ctx.resolve_chip("DFF").unwrap()).eval()[0];
+            let top = Simulator::new(ctx.resolve_chip("Top").unwrap()).eval()[0];
+            (dff, top)
+        };
+        let results = [
+            out(None, false),
+            out(Some(BuiltinPolicy::PreferHdl), false),
+            out(Some(BuiltinPolicy::PreferHdl), true),
+            out(Some(BuiltinPolicy::PreferBuiltin), true),
+        ];
+        assert_eq!(
+            results,
+            [(false, false), (true, true), (true, true), (false, false)]
+        );
+
+        // a design loaded with its own DFF, which is then swapped for the builtin and back
+        let mut ctx = ChipBuilder::new();
+        ctx.set_builtin_policy(BuiltinPolicy::PreferHdl);
+        ctx.add_hdl(dir.join("Top.hdl")).unwrap();
+        let top =
+            |ctx: &mut ChipBuilder| Simulator::new(ctx.resolve_chip("Top").unwrap()).eval()[0];
+        assert!(top(&mut ctx));
+        ctx.use_builtins(["DFF"]);
+        assert!(ctx.pending.contains("Top"));
+        assert!(!top(&mut ctx));
+        ctx.set_chip_policy("DFF", BuiltinPolicy::PreferHdl);
+        assert!(top(&mut ctx));
+
+        // without an HDL file the builtin is used either way
+        let mut ctx = ChipBuilder::new();
+        ctx.set_builtin_policy(BuiltinPolicy::PreferHdl);
+        assert!(matches!(ctx.resolve_chip("DFF"), Ok(Chip::Builtin(_))));
+    }
 }
@@ -1,4 +1,6 @@
 use crate::bus_range::BusRange;
+use crate::initial_state::StateRng;
+use crate::intern::intern;
 use crate::model::chip::ChipObject;
 use crate::model::parser::Interface;
 use std::iter::once;
@@ -6,6 +8,13 @@ use std::iter::once;
 pub fn get_builtin(name: &str) -> Option<Box<dyn ChipObject>> {
     match name {
         "Nand" => Some(Box::new(Nand)),
+        "DFF" => Some(Box::<Dff>::default()),
+        "RAM8" => Some(Box::new(Ram::new("RAM8", 3))),
+        "RAM64" => Some(Box::new(Ram::new("RAM64", 6))),
+        "RAM512" => Some(Box::new(Ram::new("RAM512", 9))),
+        "RAM4K" => Some(Box::new(Ram::new("RAM4K", 12))),
+        "RAM16K" => Some(Box::new(Ram::new("RAM16K", 14))),
+        "ROM32K" => Some(Box::<Rom>::default()),
         _ => None,
     }
 }
@@ -14,19 +23,21 @@ struct Nand;
 impl ChipObject for Nand {
     fn interface(&self) -> Interface {
         Interface {
-            name: "Nand".to_string(),
+            name: intern("Nand"),
             com_in: [
-                ("a".to_string(), BusRange { start: 0, end: 0 }),
-                ("b".to_string(), BusRange { start: 1, end: 1 }),
+                (intern("a"), BusRange::bit(0)),
+                (intern("b"), BusRange::bit(1)),
             ]
             .into_iter()
             .collect(),
-            com_out: once(("out".to_string(), BusRange { start: 1, end: 1 })).collect(),
+            com_out: once((intern("out"), BusRange::bit(0))).collect(),
             seq_in: Default::default(),
             seq_out: Default::default(),
+            order: vec![intern("a"), intern("b"), intern("out")],
+            doc: None,
         }
     }
-    
+
     fn clock(&mut self) {
         // nothing
     }
@@ -37,3 +48,279 @@ impl ChipObject for Nand {
         Box::new(Nand)
     }
 }
+
+/// out(t) = in(t - 1)
+#[derive(Clone, Default)]
+struct Dff {
+    input: bool,
+    latched: bool,
+    state: bool,
+}
+impl ChipObject for Dff {
+    fn interface(&self) -> Interface {
+        Interface {
+            name: intern("DFF"),
+            com_in: Default::default(),
+            com_out: once((intern("out"), BusRange::bit(0))).collect(),
+            seq_in: once((intern("in"), BusRange::bit(0))).collect(),
+            seq_out: Default::default(),
+            order: vec![intern("in"), intern("out")],
+            doc: None,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.latched = self.input;
+    }
+    fn clock(&mut self) {
+        self.state = self.latched;
+    }
+    fn reset(&mut self, _: bool) {
+        *self = Self::default();
+    }
+    fn randomize(&mut self, rng: &mut StateRng, _: bool) {
+        self.state = rng.next_bool();
+        self.latched = self.state;
+    }
+    fn eval(&mut self, pins: &[bool]) -> Vec<bool> {
+        self.input = pins[0];
+        vec![self.state]
+    }
+    fn chip_clone(&self) -> Box<dyn ChipObject> {
+        Box::new(self.clone())
+    }
+}
+
+fn to_word(bits: &[bool]) -> u16 {
+    bits.iter()
+        .enumerate()
+        .fold(0, |word, (i, bit)| word | (*bit as u16) << i)
+}
+
+fn from_word(word: u16) -> Vec<bool> {
+    (0..16).map(|i| word >> i & 1 == 1).collect()
+}
+
+/// A bank of 16-bit registers. Writes are latched on the clock, but `out` follows `address`
+/// combinationally.
+#[derive(Clone)]
+struct Ram {
+    name: &'static str,
+    address_width: u16,
+    words: Vec<u16>,
+    input: u16,
+    load: bool,
+    address: usize,
+    latched: Option<(usize, u16)>,
+}
+
+impl Ram {
+    fn new(name: &'static str, address_width: u16) -> Self {
+        Self {
+            name,
+            address_width,
+            words: vec![0; 1 << address_width],
+            input: 0,
+            load: false,
+            address: 0,
+            latched: None,
+        }
+    }
+}
+
+impl ChipObject for Ram {
+    fn interface(&self) -> Interface {
+        Interface {
+            name: intern(self.name),
+            com_in: once((
+                intern("address"),
+                BusRange::with_width(17, self.address_width).unwrap(),
+            ))
+            .collect(),
+            com_out: once((intern("out"), BusRange::with_width(0, 16).unwrap())).collect(),
+            seq_in: [
+                (intern("in"), BusRange::with_width(0, 16).unwrap()),
+                (intern("load"), BusRange::bit(16)),
+            ]
+            .into_iter()
+            .collect(),
+            seq_out: Default::default(),
+            order: ["in", "load", "address", "out"].map(intern).to_vec(),
+            doc: None,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.latched = self.load.then_some((self.address, self.input));
+    }
+    fn clock(&mut self) {
+        if let Some((address, word)) = self.latched.take() {
+            self.words[address] = word;
+        }
+    }
+    fn reset(&mut self, clear_memory: bool) {
+        let words = std::mem::take(&mut self.words);
+        *self = Self::new(self.name, self.address_width);
+        if !clear_memory {
+            self.words = words;
+        }
+    }
+    fn randomize(&mut self, rng: &mut StateRng, memory: bool) {
+        if memory {
+            self.words.fill_with(|| rng.next_u16());
+        }
+    }
+    fn load_memory(&mut self, words: &[u16]) -> bool {
+        load_words(&mut self.words, words);
+        true
+    }
+    fn memory(&self) -> Option<&[u16]> {
+        Some(&self.words)
+    }
+    fn write_memory(&mut self, address: usize, words: &[u16]) -> bool {
+        write_words(&mut self.words, address, words);
+        true
+    }
+    fn eval(&mut self, pins: &[bool]) -> Vec<bool> {
+        self.input = to_word(&pins[0..16]);
+        self.load = pins[16];
+        self.address = to_word(&pins[17..]) as usize;
+        from_word(self.words[self.address])
+    }
+    fn chip_clone(&self) -> Box<dyn ChipObject> {
+        Box::new(self.clone())
+    }
+}
+
+// words past the end of the memory are dropped, and the rest of the memory is cleared
+fn load_words(memory: &mut [u16], words: &[u16]) {
+    memory.fill(0);
+    let count = words.len().min(memory.len());
+    memory[..count].copy_from_slice(&words[..count]);
+}
+
+// words past the end of the memory are dropped
+fn write_words(memory: &mut [u16], address: usize, words: &[u16]) {
+    let start = address.min(memory.len());
+    let count = words.len().min(memory.len() - start);
+    memory[start..start + count].copy_from_slice(&words[..count]);
+}
+
+/// The instruction memory of the computer. It cannot be written by the chip itself, only loaded
+/// with a program from outside.
+#[derive(Clone)]
+struct Rom {
+    words: Vec<u16>,
+}
+
+impl Default for Rom {
+    fn default() -> Self {
+        Self {
+            words: vec![0; 1 << 15],
+        }
+    }
+}
+
+impl ChipObject for Rom {
+    fn interface(&self) -> Interface {
+        Interface {
+            name: intern("ROM32K"),
+            com_in: once((intern("address"), BusRange::with_width(0, 15).unwrap())).collect(),
+            com_out: once((intern("out"), BusRange::with_width(0, 16).unwrap())).collect(),
+            seq_in: Default::default(),
+            seq_out: Default::default(),
+            order: vec![intern("address"), intern("out")],
+            doc: None,
+        }
+    }
+
+    fn clock(&mut self) {
+        // nothing
+    }
+    fn reset(&mut self, clear_memory: bool) {
+        if clear_memory {
+            self.words.fill(0);
+        }
+    }
+    fn randomize(&mut self, rng: &mut StateRng, memory: bool) {
+        if memory {
+            self.words.fill_with(|| rng.next_u16());
+        }
+    }
+    fn load_memory(&mut self, words: &[u16]) -> bool {
+        load_words(&mut self.words, words);
+        true
+    }
+    fn memory(&self) -> Option<&[u16]> {
+        Some(&self.words)
+    }
+    fn write_memory(&mut self, address: usize, words: &[u16]) -> bool {
+        write_words(&mut self.words, address, words);
+        true
+    }
+    fn eval(&mut self, pins: &[bool]) -> Vec<bool> {
+        from_word(self.words[to_word(pins) as usize])
+    }
+    fn chip_clone(&self) -> Box<dyn ChipObject> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ram_pins(input: u16, load: bool, address: u16) -> Vec<bool> {
+        let mut pins = from_word(input);
+        pins.push(load);
+        pins.extend((0..3).map(|i| address >> i & 1 == 1));
+        pins
+    }
+
+    #[test]
+    fn test_ram_reads_combinationally() {
+        let mut ram = get_builtin("RAM8").unwrap();
+        assert_eq!(ram.interface().input_width(), 20);
+
+        ram.eval(&ram_pins(1234, true, 5));
+        ram.tick();
+        ram.clock();
+        assert_eq!(to_word(&ram.eval(&ram_pins(0, false, 5))), 1234);
+        // changing the address is visible without a clock
+        assert_eq!(to_word(&ram.eval(&ram_pins(0, false, 4))), 0);
+        // but the input is not written until the clock
+        assert_eq!(to_word(&ram.eval(&ram_pins(99, true, 4))), 0);
+        ram.tick();
+        ram.clock();
+        assert_eq!(to_word(&ram.eval(&ram_pins(0, false, 4))), 99);
+        assert_eq!(to_word(&ram.eval(&ram_pins(0, false, 5))), 1234);
+    }
+
+    #[test]
+    fn test_ram_reset() {
+        let mut ram = get_builtin("RAM8").unwrap();
+        ram.eval(&ram_pins(1234, true, 2));
+        ram.tick();
+        ram.clock();
+
+        ram.reset(false);
+        assert_eq!(to_word(&ram.eval(&ram_pins(0, false, 2))), 1234);
+        ram.reset(true);
+        assert_eq!(to_word(&ram.eval(&ram_pins(0, false, 2))), 0);
+    }
+
+    #[test]
+    fn test_rom_load() {
+        let mut rom = get_builtin("ROM32K").unwrap();
+        assert!(rom.load_memory(&[7, 8, 9]));
+        let address = |n: u16| (0..15).map(|i| n >> i & 1 == 1).collect::<Vec<_>>();
+        assert_eq!(to_word(&rom.eval(&address(1))), 8);
+        assert_eq!(to_word(&rom.eval(&address(3))), 0);
+
+        assert!(rom.write_memory(2, &[10, 11]));
+        assert_eq!(&rom.memory().unwrap()[..5], [7, 8, 10, 11, 0]);
+        // words past the end are dropped
+        assert!(rom.write_memory((1 << 15) - 1, &[12, 13]));
+        assert_eq!(rom.memory().unwrap().last(), Some(&12));
+    }
+}
@@ -0,0 +1,184 @@
+//! The interfaces of the chips which are part of the nand2tetris course. A user-defined chip
+//! sharing its name with one of these is expected to have exactly the same pins, since the course
+//! test scripts and the builtin implementations depend on them.
+
+use crate::model::parser::{Direction, Interface};
+use thiserror::Error;
+
+pub struct CanonicalChip {
+    pub name: &'static str,
+    pub inputs: &'static [(&'static str, u16)],
+    pub outputs: &'static [(&'static str, u16)],
+}
+
+macro_rules! canonical {
+    ($($name:ident ( $($i:ident : $iw:literal),* ; $($o:ident : $ow:literal),* ))*) => {
+        &[$(CanonicalChip {
+            name: stringify!($name),
+            inputs: &[$((stringify!($i), $iw)),*],
+            outputs: &[$((stringify!($o), $ow)),*],
+        }),*]
+    };
+}
+
+pub const CANONICAL_CHIPS: &[CanonicalChip] = canonical! {
+    // project 1
+    Nand(a: 1, b: 1; out: 1)
+    Not(in: 1; out: 1)
+    And(a: 1, b: 1; out: 1)
+    Or(a: 1, b: 1; out: 1)
+    Xor(a: 1, b: 1; out: 1)
+    Mux(a: 1, b: 1, sel: 1; out: 1)
+    DMux(in: 1, sel: 1; a: 1, b: 1)
+    Not16(in: 16; out: 16)
+    And16(a: 16, b: 16; out: 16)
+    Or16(a: 16, b: 16; out: 16)
+    Mux16(a: 16, b: 16, sel: 1; out: 16)
+    Or8Way(in: 8; out: 1)
+    Mux4Way16(a: 16, b: 16, c: 16, d: 16, sel: 2; out: 16)
+    Mux8Way16(a: 16, b: 16, c: 16, d: 16, e: 16, f: 16, g: 16, h: 16, sel: 3; out: 16)
+    DMux4Way(in: 1, sel: 2; a: 1, b: 1, c: 1, d: 1)
+    DMux8Way(in: 1, sel: 3; a: 1, b: 1, c: 1, d: 1, e: 1, f: 1, g: 1, h: 1)
+    // project 2
+    HalfAdder(a: 1, b: 1; sum: 1, carry: 1)
+    FullAdder(a: 1, b: 1, c: 1; sum: 1, carry: 1)
+    Add16(a: 16, b: 16; out: 16)
+    Inc16(in: 16; out: 16)
+    ALU(x: 16, y: 16, zx: 1, nx: 1, zy: 1, ny: 1, f: 1, no: 1; out: 16, zr: 1, ng: 1)
+    // project 3
+    DFF(in: 1; out: 1)
+    Bit(in: 1, load: 1; out: 1)
+    Register(in: 16, load: 1; out: 16)
+    ARegister(in: 16, load: 1; out: 16)
+    DRegister(in: 16, load: 1; out: 16)
+    RAM8(in: 16, load: 1, address: 3; out: 16)
+    RAM64(in: 16, load: 1, address: 6; out: 16)
+    RAM512(in: 16, load: 1, address: 9; out: 16)
+    RAM4K(in: 16, load: 1, address: 12; out: 16)
+    RAM16K(in: 16, load: 1, address: 14; out: 16)
+    PC(in: 16, load: 1, inc: 1, reset: 1; out: 16)
+    // project 5
+    ROM32K(address: 15; out: 16)
+    Screen(in: 16, load: 1, address: 13; out: 16)
+    Keyboard(; out: 16)
+    Memory(in: 16, load: 1, address: 15; out: 16)
+    CPU(inM: 16, instruction: 16, reset: 1; outM: 16, writeM: 1, addressM: 15, pc: 15)
+    Computer(reset: 1;)
+};
+
+pub fn canonical(name: &str) -> Option<&'static CanonicalChip> {
+    CANONICAL_CHIPS.iter().find(|chip| chip.name == name)
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Deviation {
+    #[error("Pin `{0}` is missing")]
+    MissingPin(String),
+    #[error("Pin `{0}` is not part of the interface")]
+    ExtraPin(String),
+    #[error("Pin `{name}` should be {expected} bits wide, but is {found}")]
+    WrongWidth {
+        name: String,
+        expected: u16,
+        found: u16,
+    },
+    #[error("Pin `{name}` should be an {expected} pin")]
+    WrongDirection { name: String, expected: Direction },
+}
+
+/// Compares an interface against the course interface of the same name. Chips which are not part
+/// of the course never deviate.
+pub fn check_conformance(interface: &Interface) -> Vec<Deviation> {
+    let canonical = match canonical(&interface.name) {
+        Some(x) => x,
+        None => return Vec::new(),
+    };
+    let expected = canonical
+        .inputs
+        .iter()
+        .map(|(name, width)| (*name, *width, Direction::In))
+        .chain(
+            canonical
+                .outputs
+                .iter()
+                .map(|(name, width)| (*name, *width, Direction::Out)),
+        )
+        .collect::<Vec<_>>();
+
+    let mut deviations = Vec::new();
+    for (name, width, direction) in expected.iter().copied() {
+        match interface.pins().find(|pin| pin.name == name) {
+            None => deviations.push(Deviation::MissingPin(name.to_string())),
+            Some(pin) if pin.direction != direction => deviations.push(Deviation::WrongDirection {
+                name: name.to_string(),
+                expected: direction,
+            }),
+            Some(pin) if pin.width != width => deviations.push(Deviation::WrongWidth {
+                name: name.to_string(),
+                expected: width,
+                found: pin.width,
+            }),
+            Some(_) => {}
+        }
+    }
+    for pin in interface.pins() {
+        if !expected.iter().any(|(name, ..)| *name == pin.name) {
+            deviations.push(Deviation::ExtraPin(pin.name.to_string()));
+        }
+    }
+
+    deviations
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::parser::create_chip;
+    use crate::Span;
+    use std::fs;
+
+    #[test]
+    fn test_course_files_conform() {
+        let dir = std::env::current_dir().unwrap().join("../test_files");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|x| x != "hdl") {
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            // some of the files are still unimplemented and have an empty PARTS section
+            let Ok(chip) = create_chip(Span::from(source.as_str())) else {
+                continue;
+            };
+            assert_eq!(check_conformance(&chip.interface()), vec![], "{path:?}");
+        }
+    }
+
+    #[test]
+    fn test_deviations() {
+        let source = "\
+CHIP Xor {
+    IN a[2], c;
+    OUT b;
+    PARTS:
+    Nand(a=a, b=c, out=b);
+}";
+        let chip = create_chip(Span::from(source)).unwrap();
+        assert_eq!(
+            check_conformance(&chip.interface()),
+            vec![
+                Deviation::WrongWidth {
+                    name: "a".to_string(),
+                    expected: 1,
+                    found: 2
+                },
+                Deviation::WrongDirection {
+                    name: "b".to_string(),
+                    expected: Direction::In
+                },
+                Deviation::MissingPin("out".to_string()),
+                Deviation::ExtraPin("c".to_string()),
+            ]
+        );
+    }
+}
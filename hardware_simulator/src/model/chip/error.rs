@@ -1,13 +1,134 @@
+use crate::bus_range::BusRange;
+use crate::model::dialect::Extension;
+use crate::model::parser::error::SyntaxError;
+use crate::model::preprocess::PreprocessError;
+use crate::Span;
+use nom_supreme::error::ErrorTree;
+use std::ops::Range;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ModelConstructionError {
     #[error("Chip `{0}` cannot be found with the given path")]
     ChipNotFound(String),
-    #[error("An error occurred while parsing the file containing the chip")]
-    HdlParseError, //TODO: Include ErrorTree with the error
-    #[error("An error occurred while building the model from a file")]
-    ConstructionError,
+    #[error("Could not parse the file containing the chip: {0}")]
+    HdlParseError(SyntaxError),
+    #[error("Could not preprocess the file containing the chip: {0}")]
+    PreprocessError(#[from] PreprocessError),
+    #[error("Chip `{0}` has width parameters, and can only be used as a part such as `{0}<16>`")]
+    GenericChip(String),
+    #[error("Part `{0}` does not give a number for every width parameter of its chip")]
+    BadSpecialization(String),
+    #[error("Parts are nested more than {0} deep")]
+    NestingLimit(usize),
+    #[error("{0}")]
+    ConstructionError(#[from] BuildError),
+    #[error("The chip uses {0}, which are only accepted in the extended dialect")]
+    Extension(Extension),
+    #[error("Could not load plugin: {0}")]
+    PluginError(String),
     #[error("An unknown error occurred")]
     Unk(Option<anyhow::Error>),
 }
+
+impl ModelConstructionError {
+    /// Describes an error of the parser of `source`, for use with `map_err`
+    pub(crate) fn syntax(source: &str) -> impl Fn(nom::Err<ErrorTree<Span>>) -> Self + '_ {
+        move |e| ModelConstructionError::HdlParseError(SyntaxError::new(&e, source))
+    }
+
+    /// The bytes of the source of the chip which the error is about, if it is about some of them
+    pub fn range(&self) -> Option<Range<usize>> {
+        match self {
+            ModelConstructionError::HdlParseError(e) => Some(e.range.clone()),
+            ModelConstructionError::ConstructionError(e) => Some(e.range.clone()),
+            _ => None,
+        }
+    }
+
+    /// A code which stays the same for each kind of error, see [`crate::error`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            ModelConstructionError::ChipNotFound(_) => "E0101",
+            ModelConstructionError::HdlParseError(_) => "E0102",
+            ModelConstructionError::PreprocessError(e) => e.code(),
+            ModelConstructionError::GenericChip(_) => "E0104",
+            ModelConstructionError::BadSpecialization(_) => "E0105",
+            ModelConstructionError::NestingLimit(_) => "E0106",
+            ModelConstructionError::ConstructionError(e) => e.kind.code(),
+            ModelConstructionError::PluginError(_) => "E0108",
+            ModelConstructionError::Extension(_) => "E0109",
+            ModelConstructionError::Unk(_) => "E0199",
+        }
+    }
+}
+
+/// Why the parts of a chip cannot be wired together
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{kind}")]
+pub struct BuildError {
+    pub kind: BuildErrorKind,
+    /// The bytes of the source of the chip the error is about, after preprocessing in the
+    /// extended dialect
+    pub range: Range<usize>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BuildErrorKind {
+    #[error("Chip `{0}` cannot be found with the given path")]
+    UnknownPart(String),
+    #[error("Chip `{chip}` has no pin `{pin}`")]
+    UnknownPin { chip: String, pin: String },
+    #[error("`{pin}` has no bits {range}")]
+    BadSlice { pin: String, range: BusRange },
+    #[error("`{0}` is not a pin of the chip, so it cannot be sliced")]
+    SlicedInternal(String),
+    #[error("`{0}` is driven by more than one output")]
+    MultipleDrivers(String),
+    #[error("`{0}` is read, but nothing drives it")]
+    Undriven(String),
+    #[error("Bits of the input `{0}` are driven more than once")]
+    InputDrivenTwice(String),
+    #[error("Bits of the input `{0}` are given more than one constant")]
+    ConstantTwice(String),
+    #[error("`{0}` is an output, so it cannot be given a constant")]
+    ConstantOutput(String),
+    #[error("A constant has no bits to slice")]
+    SlicedConstant,
+    #[error("{value} does not fit in the {width} bits of `{pin}`")]
+    ConstantTooWide {
+        pin: String,
+        value: usize,
+        width: u16,
+    },
+    #[error("The label `{0}` is given to more than one part")]
+    DuplicateLabel(String),
+    #[error("`{pin}` has {to} bits, but is connected to {from} bits of `{wire}`")]
+    WidthMismatch {
+        pin: String,
+        wire: String,
+        from: u16,
+        to: u16,
+    },
+}
+
+impl BuildErrorKind {
+    /// A code which stays the same for each kind of error, see [`crate::error`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            BuildErrorKind::UnknownPart(_) => "E0101",
+            BuildErrorKind::UnknownPin { .. } => "E0110",
+            BuildErrorKind::BadSlice { .. } => "E0111",
+            BuildErrorKind::SlicedInternal(_) => "E0112",
+            BuildErrorKind::MultipleDrivers(_) => "E0113",
+            BuildErrorKind::Undriven(_) => "E0114",
+            BuildErrorKind::InputDrivenTwice(_) => "E0115",
+            BuildErrorKind::ConstantTwice(_) => "E0116",
+            BuildErrorKind::ConstantOutput(_) => "E0117",
+            BuildErrorKind::SlicedConstant => "E0118",
+            BuildErrorKind::ConstantTooWide { .. } => "E0119",
+            BuildErrorKind::DuplicateLabel(_) => "E0120",
+            BuildErrorKind::WidthMismatch { .. } => "E0121",
+        }
+    }
+}
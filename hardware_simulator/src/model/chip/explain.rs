@@ -0,0 +1,25 @@
+//! Answers to "why does this pin have this value?": the signals which a bit depends on.
+
+use std::fmt::{Display, Formatter};
+
+/// A bit which influences the one being explained, with its current value. Pins are named as
+/// for [`StuckAt`](super::fault::StuckAt) faults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Influence {
+    pub pin: String,
+    pub bit: u16,
+    /// `None` for bits which the chip does not keep
+    pub value: Option<bool>,
+    /// How many connections away from the explained bit this one is
+    pub depth: usize,
+}
+
+impl Display for Influence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let value = match self.value {
+            Some(value) => (value as u8).to_string(),
+            None => "?".to_string(),
+        };
+        write!(f, "{}[{}] = {value}", self.pin, self.bit)
+    }
+}
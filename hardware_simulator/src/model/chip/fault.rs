@@ -0,0 +1,43 @@
+//! Stuck-at faults, which hold a single bit of a pin at a fixed value whatever drives it. Running
+//! a test suite against every possible fault shows how much of a chip the tests actually check.
+
+use std::fmt::{Display, Formatter};
+
+/// A bit of a pin held at `value`. The pin is named by the labels of the parts leading to it,
+/// such as `Mux0.Not0.in`, or by its name alone for a pin of the chip itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StuckAt {
+    pub pin: String,
+    pub bit: u16,
+    pub value: bool,
+}
+
+impl Display for StuckAt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}[{}]` stuck at {}",
+            self.pin, self.bit, self.value as u8
+        )
+    }
+}
+
+/// A pin which faults can be injected into, and how many bits wide it is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaultSite {
+    pub pin: String,
+    pub width: u16,
+}
+
+impl FaultSite {
+    /// Both faults for every bit of the pin
+    pub fn faults(&self) -> impl Iterator<Item = StuckAt> + '_ {
+        (0..self.width).flat_map(move |bit| {
+            [false, true].map(|value| StuckAt {
+                pin: self.pin.clone(),
+                bit,
+                value,
+            })
+        })
+    }
+}
@@ -1,22 +1,30 @@
+use crate::initial_state::StateRng;
 use crate::model::parser::Interface;
 use build_ctx::ChipBuilder;
+use error::ModelConstructionError;
+use explain::Influence;
+use fault::{FaultSite, StuckAt};
 use native::NativeChip;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 
 pub mod build_ctx;
-mod builtin;
+pub(crate) mod builtin;
+pub mod canonical;
+pub mod error;
+pub mod explain;
+pub mod fault;
 mod native;
+pub mod plugin;
 mod vchip;
-mod error;
 
 pub enum Chip {
-    Native(NativeChip),
+    Native(Box<NativeChip>),
     Builtin(Box<dyn ChipObject>),
 }
 
 impl Chip {
-    pub fn build(name: &str, ctx: &mut ChipBuilder) -> Result<Self, ()> {
-        ctx.resolve_chip(name).map_err(|_|())
+    pub fn build(name: &str, ctx: &mut ChipBuilder) -> Result<Self, ModelConstructionError> {
+        ctx.resolve_chip(name)
     }
 
     pub fn interface(&self) -> Interface {
@@ -25,18 +33,148 @@ impl Chip {
             Chip::Builtin(v) => v.interface(),
         }
     }
+    pub fn tick(&mut self) {
+        match self {
+            Chip::Native(v) => v.tick(),
+            Chip::Builtin(v) => v.tick(),
+        }
+    }
     pub fn clock(&mut self) {
         match self {
             Chip::Native(v) => v.clock(),
             Chip::Builtin(v) => v.clock(),
         }
     }
+    pub fn reset(&mut self, clear_memory: bool) {
+        match self {
+            Chip::Native(v) => v.reset(clear_memory),
+            Chip::Builtin(v) => v.reset(clear_memory),
+        }
+    }
+    pub fn randomize(&mut self, rng: &mut StateRng, memory: bool) {
+        match self {
+            Chip::Native(v) => v.randomize(rng, memory),
+            Chip::Builtin(v) => v.randomize(rng, memory),
+        }
+    }
+    /// Loads words into the memory of every part named `name`, including this chip itself,
+    /// returning how many memories were loaded
+    pub fn load_memory(&mut self, name: &str, words: &[u16]) -> usize {
+        match self {
+            Chip::Native(v) => v
+                .parts_mut()
+                .iter_mut()
+                .map(|chip| chip.load_memory(name, words))
+                .sum(),
+            Chip::Builtin(v) => (&*v.interface().name == name && v.load_memory(words)) as usize,
+        }
+    }
+    /// The contents of the memory of the first part named `name`, or of this chip itself
+    pub fn memory(&self, name: &str) -> Option<&[u16]> {
+        match self {
+            Chip::Native(v) => v.parts().iter().find_map(|chip| chip.memory(name)),
+            Chip::Builtin(v) => (&*v.interface().name == name).then(|| v.memory()).flatten(),
+        }
+    }
+    /// Writes words from `address` on into the memory of every part named `name`, including this
+    /// chip itself, keeping the rest of their contents. Returns how many memories were written.
+    pub fn write_memory(&mut self, name: &str, address: usize, words: &[u16]) -> usize {
+        match self {
+            Chip::Native(v) => v
+                .parts_mut()
+                .iter_mut()
+                .map(|chip| chip.write_memory(name, address, words))
+                .sum(),
+            Chip::Builtin(v) => {
+                (&*v.interface().name == name && v.write_memory(address, words)) as usize
+            }
+        }
+    }
+    /// Injects a fault, which stays until [`clear_faults`](Self::clear_faults) is called.
+    /// Returns false if the pin cannot be found, which is always the case for builtins.
+    pub fn inject_fault(&mut self, fault: &StuckAt) -> bool {
+        match self {
+            Chip::Native(v) => v.inject_fault(&fault.pin, fault.bit, fault.value),
+            Chip::Builtin(_) => false,
+        }
+    }
+    pub fn clear_faults(&mut self) {
+        if let Chip::Native(v) = self {
+            v.clear_faults();
+        }
+    }
+    /// Every pin which faults can be injected into: the pins of the chip itself, then the pins of
+    /// every part, recursively
+    pub fn fault_sites(&self) -> Vec<FaultSite> {
+        match self {
+            Chip::Native(v) => v.fault_sites(),
+            Chip::Builtin(_) => Vec::new(),
+        }
+    }
+    /// The current value of every pin listed by [`fault_sites`](Self::fault_sites), in the same
+    /// order. Bits which are not kept by the chip, such as outputs of parts which drive nothing,
+    /// are `None`.
+    pub fn pin_values(&self) -> Vec<(String, Vec<Option<bool>>)> {
+        match self {
+            Chip::Native(v) => v.pin_values(),
+            Chip::Builtin(_) => Vec::new(),
+        }
+    }
+    /// Every bit which the given bit currently depends on, nearest first. The pin is either an
+    /// output of the chip or a pin of one of its parts, named as for faults. Inputs of builtins
+    /// are all assumed to matter, and the walk stops at their state. Returns `None` if there is no
+    /// such bit.
+    pub fn explain(&self, pin: &str, bit: u16) -> Option<Vec<Influence>> {
+        match self {
+            Chip::Native(v) => v.explain(pin, bit),
+            Chip::Builtin(_) => None,
+        }
+    }
     pub fn eval(&mut self, args: &[bool]) -> Vec<bool> {
         match self {
             Chip::Native(v) => v.eval(args),
             Chip::Builtin(v) => v.eval(args),
         }
     }
+    /// Runs a clock cycle for each vector of inputs, lazily, yielding the outputs at the end of
+    /// the cycle. For combinational chips this is the same as calling [`eval`](Self::eval) on
+    /// each vector.
+    pub fn drive<I>(&mut self, inputs: I) -> Drive<'_, I::IntoIter>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[bool]>,
+    {
+        Drive {
+            chip: self,
+            inputs: inputs.into_iter(),
+        }
+    }
+}
+
+/// The iterator returned by [`Chip::drive`]
+pub struct Drive<'a, I> {
+    chip: &'a mut Chip,
+    inputs: I,
+}
+
+impl<I> Iterator for Drive<'_, I>
+where
+    I: Iterator,
+    I::Item: AsRef<[bool]>,
+{
+    type Item = Vec<bool>;
+
+    fn next(&mut self) -> Option<Vec<bool>> {
+        let input = self.inputs.next()?;
+        self.chip.eval(input.as_ref());
+        self.chip.tick();
+        self.chip.clock();
+        Some(self.chip.eval(input.as_ref()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inputs.size_hint()
+    }
 }
 
 impl Clone for Chip {
@@ -54,10 +192,63 @@ impl Display for Chip {
     }
 }
 
-pub trait ChipObject {
+impl Debug for Chip {
+    /// The name of the chip, then a line for each of its parts, with the parts of those indented
+    /// under them:
+    ///
+    /// ```text
+    /// And
+    ///   Nand0: Nand
+    ///   Not0: Not
+    ///     Nand0: Nand
+    /// ```
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")?;
+        match self {
+            Chip::Native(v) => v.write_tree(f, 1),
+            Chip::Builtin(_) => Ok(()),
+        }
+    }
+}
+
+/// A chip implemented in Rust rather than HDL. Chips may be moved to other threads, such as when
+/// a project is loaded in parallel or a chip is run behind a [`crate::handle`], and shared
+/// between them by reference, such as by graders reading the same chip from several threads.
+pub trait ChipObject: Send + Sync {
     fn interface(&self) -> Interface;
 
+    /// The rising edge of the clock, where clocked inputs are latched
+    fn tick(&mut self) {}
+    /// The falling edge of the clock, where latched inputs are committed to the chip's state
     fn clock(&mut self);
+    /// Returns all sequential state to how it was when the chip was created. The contents of
+    /// memories such as RAM are only cleared if `clear_memory` is set.
+    fn reset(&mut self, _clear_memory: bool) {}
+    /// Fills the sequential state with values drawn from `rng`, as if the chip had just been
+    /// powered on. Memories are only filled if `memory` is set.
+    fn randomize(&mut self, _rng: &mut StateRng, _memory: bool) {}
+    /// Replaces the contents of the chip's memory, starting at address 0. Returns false if the
+    /// chip has no memory which can be loaded.
+    fn load_memory(&mut self, _words: &[u16]) -> bool {
+        false
+    }
+    /// The contents of the chip's memory, or `None` if it has no memory
+    fn memory(&self) -> Option<&[u16]> {
+        None
+    }
+    /// Writes words into the chip's memory from `address` on, keeping the rest of it. Words past
+    /// the end of the memory are dropped. Returns false if the chip has no memory.
+    fn write_memory(&mut self, _address: usize, _words: &[u16]) -> bool {
+        false
+    }
     fn eval(&mut self, _: &[bool]) -> Vec<bool>;
     fn chip_clone(&self) -> Box<dyn ChipObject>;
 }
+
+// chips, and what builds and drives them, can be sent to and shared between threads
+const _: fn() = || {
+    fn thread_safe<T: Send + Sync>() {}
+    thread_safe::<Chip>();
+    thread_safe::<ChipBuilder>();
+    thread_safe::<crate::simulator::Simulator>();
+};
@@ -1,13 +1,20 @@
-use super::edge_set::{EdgeSetMap, Endpoint};
+use super::edge_set::{coalesce, EdgeSetMap, Endpoint};
+use crate::bus_range::BusRange;
 use crate::clock_behavior::ClockBehavior;
-use crate::model::chip::build_ctx::ChipBuilder;
-use crate::model::chip::native::{ConnEdge, NativeChip};
+use crate::intern::intern;
+use crate::model::chip::error::{BuildError, BuildErrorKind};
+use crate::model::chip::native::{ConnEdge, Constant, NativeChip};
 use crate::model::chip::vchip::VirtualBus;
 use crate::model::chip::Chip;
-use crate::model::parser::{Argument, Connection, Interface, Symbol};
+use crate::model::parser::{Argument, Connection, Interface, Symbol, Value};
+use crate::Span;
+use petgraph::algo::{has_path_connecting, toposort};
 use petgraph::graph::NodeIndex;
+use petgraph::visit::{EdgeFiltered, EdgeRef};
 use petgraph::Graph;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
 
 struct Dependency<'a> {
     index: NodeIndex,
@@ -15,24 +22,73 @@ struct Dependency<'a> {
     connections: Vec<Argument<'a>>,
 }
 
+/// A constant with the input it is given to, and where
+struct Given {
+    constant: Constant,
+    pin: String,
+    location: Range<usize>,
+}
+
 pub fn native_chip(
-    ctx: &mut ChipBuilder,
+    mut resolve: impl FnMut(&str) -> Option<Chip>,
     top_interface: Interface,
     connections: Vec<Connection>,
-) -> Result<NativeChip, ()> {
+) -> Result<NativeChip, BuildError> {
     let Interface {
-        com_in, com_out, ..
+        mut com_in,
+        com_out,
+        seq_in,
+        ..
     } = top_interface.clone();
+    com_in.extend(seq_in);
     let (input, output) = (VirtualBus::new_in(com_in), VirtualBus::new_out(com_out));
 
     let mut conn_graph = Graph::<_, ConnEdge>::new();
+    let mut labels = Vec::new();
 
     // instantiate all chips this chip depends on
     let dependents = {
         let mut dependents = vec![];
-        for Connection { chip_name, inputs } in connections {
+        let mut instance_counts = HashMap::new();
+        let given = connections
+            .iter()
+            .filter_map(|x| x.label.map(|x| *x))
+            .collect::<Vec<_>>();
+        for Connection {
+            label,
+            chip_name,
+            inputs,
+            ..
+        } in connections
+        {
+            // unlabelled parts are named after their chip and how many came before them,
+            // skipping names which are given to another part, so that only the labels of the HDL
+            // can clash
+            let count = instance_counts.entry(*chip_name).or_insert(0);
+            let name = match label {
+                Some(label) => {
+                    *count += 1;
+                    intern(&label)
+                }
+                None => loop {
+                    let name = intern(&format!("{chip_name}{count}"));
+                    *count += 1;
+                    if !given.contains(&&*name) && !labels.contains(&name) {
+                        break name;
+                    }
+                },
+            };
+            if labels.contains(&name) {
+                let span = label.unwrap_or(chip_name);
+                return Err(BuildError {
+                    kind: BuildErrorKind::DuplicateLabel(name.to_string()),
+                    range: span_range(span),
+                });
+            }
+            labels.push(name);
+
             dependents.push(
-                ctx.resolve_chip(*chip_name)
+                resolve(*chip_name)
                     .map(|chip| {
                         let interface = chip.interface();
                         let index = conn_graph.add_node(chip);
@@ -42,7 +98,10 @@ pub fn native_chip(
                             connections: inputs,
                         }
                     })
-                    .map_err(|_| ())?,
+                    .ok_or_else(|| BuildError {
+                        kind: BuildErrorKind::UnknownPart(chip_name.to_string()),
+                        range: span_range(chip_name),
+                    })?,
             );
         }
 
@@ -50,36 +109,115 @@ pub fn native_chip(
     };
     // including the input and output virtual chips
     let (input_index, output_index) = (conn_graph.add_node(input), conn_graph.add_node(output));
+    labels.push(intern("_Input"));
+    labels.push(intern("_Output"));
 
-    let edge_sets = make_edge_set(input_index, output_index, &mut conn_graph, dependents)?;
-
-    for (name, set) in edge_sets.iter() {
-        for (input, output) in set.iter()? {
-            (input.range.size() == output.range.size()).then(|| {
-                if matches!(
-                    input.clocked.and(&output.clocked),
-                    ClockBehavior::Sequential
-                ) {
-                    conn_graph.add_edge(
-                        input.index,
-                        output.index,
-                        ConnEdge::new_seq(name.clone(), input.range.clone(), output.range.clone()),
-                    )
-                } else {
-                    conn_graph.add_edge(
-                        input.index,
-                        output.index,
-                        ConnEdge::new_com(name.clone(), input.range.clone(), output.range.clone()),
-                    )
-                }
-            });
-        }
+    let (edge_sets, constants) =
+        make_edge_set(input_index, output_index, &mut conn_graph, dependents)?;
+
+    let wires = edge_sets.wires()?;
+    // a wire connects each bit at one end to one bit at the other
+    if let Some(wire) = wires
+        .iter()
+        .find(|x| x.from.range.width() != x.to.range.width())
+    {
+        return Err(BuildError {
+            kind: BuildErrorKind::WidthMismatch {
+                pin: wire.to.pin.clone(),
+                wire: wire.name.clone(),
+                from: wire.from.range.width(),
+                to: wire.to.range.width(),
+            },
+            range: wire.to.location.clone(),
+        });
     }
+    let wires = coalesce(wires)?;
+    // bits given a constant cannot also be driven by a wire or by another constant
+    for (i, given) in constants.iter().enumerate() {
+        let overlaps = |node, range: &BusRange| {
+            node == given.constant.node && range.overlaps(&given.constant.range)
+        };
+        let kind = if wires.iter().any(|x| overlaps(x.to.index, &x.to.range)) {
+            BuildErrorKind::InputDrivenTwice(given.pin.clone())
+        } else if constants[..i]
+            .iter()
+            .any(|x| overlaps(x.constant.node, &x.constant.range))
+        {
+            BuildErrorKind::ConstantTwice(given.pin.clone())
+        } else {
+            continue;
+        };
+        return Err(BuildError {
+            kind,
+            range: given.location.clone(),
+        });
+    }
+    let constants = constants.into_iter().map(|x| x.constant).collect();
+    for wire in wires {
+        let edge = match wire.clocked() {
+            ClockBehavior::Sequential => {
+                ConnEdge::new_seq(wire.name, wire.from.range, wire.to.range)
+            }
+            ClockBehavior::Combinatorial => {
+                ConnEdge::new_com(wire.name, wire.from.range, wire.to.range)
+            }
+        };
+        conn_graph.add_edge(wire.from.index, wire.to.index, edge);
+    }
+
+    // a combinatorial loop has no topological order; the evaluation then settles over several
+    // passes instead
+    let order = toposort(
+        &EdgeFiltered::from_fn(&conn_graph, |edge| {
+            matches!(edge.weight(), ConnEdge::Combinatorial { .. })
+        }),
+        None,
+    )
+    .unwrap_or_else(|_| conn_graph.node_indices().collect());
+    let interface = infer_clocked(top_interface, &conn_graph, input_index, output_index);
 
-    Ok(NativeChip {
-        conn_graph,
-        interface: top_interface,
-    })
+    Ok(NativeChip::new(
+        conn_graph, labels, interface, order, constants,
+    ))
+}
+
+/// Moves every input which only reaches the outputs through clocked pins of its parts into
+/// `seq_in`, so that chips using this one do not need to order their evaluation around it
+fn infer_clocked(
+    mut interface: Interface,
+    conn_graph: &Graph<Chip, ConnEdge>,
+    input_index: NodeIndex,
+    output_index: NodeIndex,
+) -> Interface {
+    let combinatorial = EdgeFiltered::from_fn(conn_graph, |edge| {
+        matches!(edge.weight(), ConnEdge::Combinatorial { .. })
+    });
+    let clocked = interface
+        .com_in
+        .iter()
+        .filter(|(_, range)| {
+            let mut edges = conn_graph
+                .edges(input_index)
+                .filter(|edge| {
+                    let from = edge.weight().in_range();
+                    from.overlaps(range)
+                })
+                .peekable();
+            // unconnected inputs are left alone
+            edges.peek().is_some()
+                && !edges.any(|edge| {
+                    matches!(edge.weight(), ConnEdge::Combinatorial { .. })
+                        && has_path_connecting(&combinatorial, edge.target(), output_index, None)
+                })
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+
+    for name in clocked {
+        let range = interface.com_in.remove(&name).unwrap();
+        interface.seq_in.insert(name, range);
+    }
+    interface
 }
 
 fn make_edge_set(
@@ -87,12 +225,13 @@ fn make_edge_set(
     output_index: NodeIndex,
     conn_graph: &mut Graph<Chip, ConnEdge>,
     dependents: Vec<Dependency>,
-) -> Result<EdgeSetMap, ()> {
+) -> Result<(EdgeSetMap, Vec<Given>), BuildError> {
     // insert the input and output
     let input_interface = conn_graph[input_index].interface();
     let output_interface = conn_graph[output_index].interface();
 
     let mut edge_sets = EdgeSetMap::new();
+    let mut constants = Vec::new();
     for Dependency {
         index,
         interface,
@@ -100,6 +239,7 @@ fn make_edge_set(
     } in dependents
     {
         for argument in connections {
+            let location = span_range(argument.internal);
             match argument.external {
                 Symbol::Name(pin_name) => {
                     let Argument {
@@ -108,45 +248,55 @@ fn make_edge_set(
                         external_bus,
                         ..
                     } = argument;
-                    let pin_name = *pin_name;
                     let canonical_pin_name = if let Some(ref external_bus) = external_bus {
-                        Cow::Owned(format!(
-                            "{pin_name}.{}.{}",
-                            external_bus.start, external_bus.end
-                        ))
+                        Cow::Owned(format!("{pin_name}{external_bus}"))
                     } else {
-                        Cow::Borrowed(pin_name)
+                        Cow::Borrowed(*pin_name)
                     };
 
                     // automatic hooking to input/output pins
                     if !edge_sets.contains_key(&*canonical_pin_name) {
+                        let endpoint = |index, range| Endpoint {
+                            range,
+                            index,
+                            clocked: ClockBehavior::Combinatorial,
+                            pin: pin_name.to_string(),
+                            location: span_range(pin_name),
+                        };
                         if let Ok(range) =
-                            input_interface.real_range(pin_name, external_bus.as_ref())
+                            input_interface.real_range(*pin_name, external_bus.as_ref())
                         {
                             edge_sets.insert(
                                 canonical_pin_name.to_string(),
-                                Endpoint {
-                                    range,
-                                    index: input_index,
-                                    clocked: ClockBehavior::Combinatorial,
-                                },
+                                endpoint(input_index, range),
                                 true,
                             )?;
                         } else if let Ok(range) =
-                            output_interface.real_range(pin_name, external_bus.as_ref())
+                            output_interface.real_range(*pin_name, external_bus.as_ref())
                         {
                             edge_sets.insert(
                                 canonical_pin_name.to_string(),
-                                Endpoint {
-                                    range,
-                                    index: output_index,
-                                    clocked: ClockBehavior::Combinatorial,
-                                },
+                                endpoint(output_index, range),
                                 false,
                             )?;
                         } else {
-                            if matches!(external_bus, Some(_)) {
-                                return Err(());
+                            // the pin is on neither side, and so an internal pin
+                            if let Some(range) = external_bus {
+                                let on_chip = input_interface
+                                    .real_range(*pin_name, None)
+                                    .or_else(|_| output_interface.real_range(*pin_name, None));
+                                return Err(BuildError {
+                                    kind: match on_chip {
+                                        Ok(_) => BuildErrorKind::BadSlice {
+                                            pin: pin_name.to_string(),
+                                            range,
+                                        },
+                                        Err(_) => {
+                                            BuildErrorKind::SlicedInternal(pin_name.to_string())
+                                        }
+                                    },
+                                    range: span_range(pin_name),
+                                });
                             }
                         }
                     }
@@ -155,17 +305,432 @@ fn make_edge_set(
                         canonical_pin_name.to_string(),
                         Endpoint {
                             index,
-                            range: interface.real_range(*internal, internal_bus.as_ref())?,
-                            clocked: interface.clocked(pin_name),
+                            range: part_range(&interface, internal, internal_bus)?,
+                            clocked: interface.clocked(*internal),
+                            pin: internal.to_string(),
+                            location,
                         },
                         !interface.is_input(*internal),
                     )?;
                 }
-                Symbol::Value(_) => todo!(),
-                Symbol::Number(_) => panic!("Numbers are not supported by this hack hdl version"),
+                Symbol::Value(_) | Symbol::Number(_) => {
+                    let pin = argument.internal.to_string();
+                    let error = |kind| BuildError {
+                        kind,
+                        range: location.clone(),
+                    };
+                    if argument.external_bus.is_some() {
+                        return Err(error(BuildErrorKind::SlicedConstant));
+                    }
+                    let range = part_range(&interface, argument.internal, argument.internal_bus)?;
+                    if !interface.is_input(&pin) {
+                        return Err(error(BuildErrorKind::ConstantOutput(pin)));
+                    }
+                    let width = range.width();
+                    let bits = constant_bits(&argument.external, width).ok_or_else(|| {
+                        let Symbol::Number(value) = argument.external else {
+                            unreachable!("only a number can be too wide")
+                        };
+                        error(BuildErrorKind::ConstantTooWide {
+                            pin: pin.clone(),
+                            value,
+                            width,
+                        })
+                    })?;
+                    constants.push(Given {
+                        constant: Constant {
+                            node: index,
+                            bits,
+                            range,
+                        },
+                        pin,
+                        location,
+                    });
+                }
             }
         }
     }
 
-    Ok(edge_sets)
+    Ok((edge_sets, constants))
+}
+
+/// The bits of a pin of a part, which fails if the part has no such pin or bits
+fn part_range(
+    interface: &Interface,
+    pin: Span,
+    bus: Option<BusRange>,
+) -> Result<BusRange, BuildError> {
+    interface
+        .real_range(*pin, bus.as_ref())
+        .map_err(|_| BuildError {
+            kind: match (interface.real_range(*pin, None), bus) {
+                (Ok(_), Some(range)) => BuildErrorKind::BadSlice {
+                    pin: pin.to_string(),
+                    range,
+                },
+                _ => BuildErrorKind::UnknownPin {
+                    chip: interface.name.to_string(),
+                    pin: pin.to_string(),
+                },
+            },
+            range: span_range(pin),
+        })
+}
+
+fn span_range(span: Span) -> Range<usize> {
+    span.location_offset()..span.location_offset() + span.len()
+}
+
+/// The bits of a constant connected to `width` bits of an input, lowest first. `true` and `false`
+/// fill every bit, as in the course tools, while a number must fit in them.
+fn constant_bits(symbol: &Symbol, width: u16) -> Option<Vec<bool>> {
+    let width = width as usize;
+    match symbol {
+        Symbol::Value(value) => Some(vec![*value == Value::True; width]),
+        Symbol::Number(n) => {
+            let bits = (0..width)
+                .map(|i| i < usize::BITS as usize && n >> i & 1 == 1)
+                .collect();
+            (width >= usize::BITS as usize || n >> width == 0).then_some(bits)
+        }
+        Symbol::Name(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::chip::build_ctx::ChipBuilder;
+    use crate::model::chip::ChipObject;
+    use crate::model::parser::{create_chip, Form};
+    use crate::Span;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_infer_clocked() {
+        let source = "\
+CHIP Memory {
+    IN in[16], load, address[3];
+    OUT out[16];
+    PARTS:
+    RAM8(in=in, load=load, address=address, out=out);
+}";
+        let chip = create_chip(Span::from(source)).unwrap();
+        let interface = chip.interface();
+        let Form::Native(connections) = chip.logic else {
+            unreachable!()
+        };
+        let mut builder = ChipBuilder::new();
+        let native = native_chip(|x| builder.resolve_chip(x).ok(), interface, connections).unwrap();
+        let interface = native.interface();
+        let clocked = interface
+            .inputs()
+            .map(|pin| (pin.name, pin.clocked))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            clocked,
+            vec![
+                ("in", ClockBehavior::Sequential),
+                ("load", ClockBehavior::Sequential),
+                ("address", ClockBehavior::Combinatorial),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let build = |parts: &str| {
+            let source = format!(
+                "CHIP Split {{ IN in[16], load, address[3]; OUT out[16]; PARTS: {parts} }}"
+            );
+            let chip = create_chip(Span::from(source.as_str())).unwrap();
+            let interface = chip.interface();
+            let Form::Native(connections) = chip.logic else {
+                unreachable!()
+            };
+            let mut builder = ChipBuilder::new();
+            native_chip(|x| builder.resolve_chip(x).ok(), interface, connections)
+                .map(|x| x.conn_graph().edge_count())
+                .map_err(|e| e.kind)
+        };
+        // `in` and `load` are both clocked and next to each other, so they share an edge
+        assert_eq!(
+            build("RAM8(in=in, load=load, address=address, out=out);"),
+            Ok(3)
+        );
+        assert_eq!(
+            build(
+                "RAM8(in[0..7]=in[0..7], in[8..15]=in[8..15], load=load, load=load, \
+                 address=address, out[0..3]=out[0..3], out[4..15]=out[4..15]);"
+            ),
+            Ok(3)
+        );
+        assert_eq!(
+            build("RAM8(in=in, in[4..7]=in[0..3], load=load, address=address, out=out);"),
+            Err(BuildErrorKind::InputDrivenTwice("in".into()))
+        );
+    }
+
+    #[test]
+    fn test_width_mismatch() {
+        let build = |parts: &str| {
+            let source = format!(
+                "CHIP Split {{ IN in[16], load, address[3]; OUT out[16]; PARTS: {parts} }}"
+            );
+            let chip = create_chip(Span::from(source.as_str())).unwrap();
+            let interface = chip.interface();
+            let Form::Native(connections) = chip.logic else {
+                unreachable!()
+            };
+            let mut builder = ChipBuilder::new();
+            let error = native_chip(|x| builder.resolve_chip(x).ok(), interface, connections)
+                .map(|_| ())
+                .unwrap_err();
+            (error.kind, source[error.range].to_string())
+        };
+        // neither end of a wire may be left with bits the other does not have
+        assert_eq!(
+            build("RAM8(in=load, load=load, address=address, out=out);"),
+            (
+                BuildErrorKind::WidthMismatch {
+                    pin: "in".into(),
+                    wire: "load".into(),
+                    from: 1,
+                    to: 16
+                },
+                "in".into()
+            )
+        );
+        assert_eq!(
+            build("RAM8(in=in, load=load, address=address, out=out[0..7]);"),
+            (
+                BuildErrorKind::WidthMismatch {
+                    pin: "out".into(),
+                    wire: "out[0..7]".into(),
+                    from: 16,
+                    to: 8
+                },
+                "out".into()
+            )
+        );
+        assert_eq!(
+            build(
+                "RAM8(in=in, load=load, address=address, out=x); \
+                 RAM8(in=in, load=load, address=x, out=out);"
+            )
+            .0,
+            BuildErrorKind::WidthMismatch {
+                pin: "address".into(),
+                wire: "x".into(),
+                from: 16,
+                to: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_constants() {
+        let build = |parts: &str| {
+            let source = format!("CHIP Consts {{ IN a; OUT out[16], x; PARTS: {parts} }}");
+            let chip = create_chip(Span::from(source.as_str())).unwrap();
+            let interface = chip.interface();
+            let Form::Native(connections) = chip.logic else {
+                unreachable!()
+            };
+            let mut builder = ChipBuilder::new();
+            native_chip(|x| builder.resolve_chip(x).ok(), interface, connections)
+        };
+        // `address=false` clears all three bits of the address
+        let mut chip = build(
+            "RAM8(in[0..7]=false, in[8..15]=true, load=true, address=false, out=out); \
+             Nand(a=true, b=a, out=x);",
+        )
+        .unwrap();
+        assert!(!chip.eval(&[true])[16]);
+        chip.tick();
+        chip.clock();
+        let out = chip.eval(&[false]);
+        assert_eq!(out[..16], [[false; 8], [true; 8]].concat());
+        assert!(out[16]);
+        // the constants stay in place however the chip is reset
+        chip.reset(true);
+        assert!(!chip.eval(&[false])[8]);
+        chip.tick();
+        chip.clock();
+        assert!(chip.eval(&[false])[8]);
+
+        let mut chip =
+            build("RAM8(in=1025, load=true, address=5, out=out); Nand(a=a, b=a, out=x);").unwrap();
+        chip.eval(&[false]);
+        chip.tick();
+        chip.clock();
+        let out = chip.eval(&[false]);
+        assert_eq!(
+            out[..12],
+            [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0].map(|x| x == 1)
+        );
+
+        // an input may only be given one value, and a number must fit in it
+        let error = |parts| build(parts).map(|_| ()).unwrap_err();
+        assert_eq!(
+            error("Nand(a=true, a=a, b=a, out=x);").kind,
+            BuildErrorKind::InputDrivenTwice("a".into())
+        );
+        assert_eq!(
+            error("Nand(a=true, b=a, a=false, out=x);").kind,
+            BuildErrorKind::ConstantTwice("a".into())
+        );
+        assert_eq!(
+            error("Nand(a=true, b=false, out=true);").kind,
+            BuildErrorKind::ConstantOutput("out".into())
+        );
+        assert_eq!(
+            error("Nand(a=2, b=a, out=x);").kind,
+            BuildErrorKind::ConstantTooWide {
+                pin: "a".into(),
+                value: 2,
+                width: 1
+            }
+        );
+        assert_eq!(
+            error("Nand(a=true[0], b=a, out=x);").kind,
+            BuildErrorKind::SlicedConstant
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = |parts: &str| format!("CHIP Errors {{ IN a, b[2]; OUT out; PARTS: {parts} }}");
+        let error = |parts: &str| {
+            let source = source(parts);
+            let chip = create_chip(Span::from(source.as_str())).unwrap();
+            let interface = chip.interface();
+            let Form::Native(connections) = chip.logic else {
+                unreachable!()
+            };
+            let mut builder = ChipBuilder::new();
+            let error = native_chip(|x| builder.resolve_chip(x).ok(), interface, connections)
+                .map(|_| ())
+                .unwrap_err();
+            (error.kind, source[error.range].to_string())
+        };
+        assert_eq!(
+            error("Nand(a=a, b=a, out=out); Nope(in=a, out=x);"),
+            (BuildErrorKind::UnknownPart("Nope".into()), "Nope".into())
+        );
+        assert_eq!(
+            error("Nand(a=a, c=a, out=out);"),
+            (
+                BuildErrorKind::UnknownPin {
+                    chip: "Nand".into(),
+                    pin: "c".into()
+                },
+                "c".into()
+            )
+        );
+        assert_eq!(
+            error("Nand(a=b[2], b=a, out=out);"),
+            (
+                BuildErrorKind::BadSlice {
+                    pin: "b".into(),
+                    range: BusRange::new(2, 2).unwrap()
+                },
+                "b".into()
+            )
+        );
+        assert_eq!(
+            error("Nand(a=a, b=a, out=x); Nand(a=x[0], b=x[0], out=out);"),
+            (BuildErrorKind::SlicedInternal("x".into()), "x".into())
+        );
+        assert_eq!(
+            error("Nand(a=a, b=a, out=x); Nand(a=a, b=a, out=x); Nand(a=x, b=x, out=out);").0,
+            BuildErrorKind::MultipleDrivers("x".into())
+        );
+        assert_eq!(
+            error("Nand(a=a, b=y, out=out);"),
+            (BuildErrorKind::Undriven("y".into()), "b".into())
+        );
+        assert_eq!(
+            error("Nand(a=a, a=b[0], b=a, out=out);"),
+            (BuildErrorKind::InputDrivenTwice("a".into()), "a".into())
+        );
+        assert_eq!(
+            error("same: Nand(a=a, b=a, out=x); same: Nand(a=x, b=x, out=out);"),
+            (BuildErrorKind::DuplicateLabel("same".into()), "same".into())
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut builder = ChipBuilder::new();
+        for name in ["Not", "Not1"] {
+            builder
+                .update_source(
+                    format!("{name}.hdl"),
+                    &format!("CHIP {name} {{ IN in; OUT out; PARTS: Nand(a=in, b=in, out=out); }}"),
+                )
+                .unwrap();
+        }
+        let mut labels = |parts: &str| {
+            let source = format!("CHIP Labels {{ IN in; OUT out; PARTS: {parts} }}");
+            let chip = create_chip(Span::from(source.as_str())).unwrap();
+            let interface = chip.interface();
+            let Form::Native(connections) = chip.logic else {
+                unreachable!()
+            };
+            native_chip(|x| builder.resolve_chip(x).ok(), interface, connections).map(|x| {
+                x.conn_graph()
+                    .node_weights()
+                    .filter(|x| !x.starts_with('_'))
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+            })
+        };
+        // the names of unlabelled parts step around the labels, wherever they are given
+        assert_eq!(
+            labels("Not(in=in, out=x); Not(in=x, out=y); Not1: Not(in=y, out=out);").unwrap(),
+            ["Not0", "Not2", "Not1"]
+        );
+        let parts = (0..12)
+            .map(|i| format!("Not(in=in, out=x{i});"))
+            .chain((0..2).map(|i| format!("Not1(in=in, out=y{i});")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let names = labels(&format!("{parts} Not(in=in, out=out);")).unwrap();
+        assert_eq!(names[11], "Not11");
+        assert_eq!(names[12..], ["Not12", "Not13", "Not14"]);
+
+        // a label given twice is an error of the HDL
+        let source = "x: Not(in=in, out=a); x: Not(in=a, out=out);";
+        let error = labels(source).unwrap_err();
+        assert_eq!(error.kind, BuildErrorKind::DuplicateLabel("x".into()));
+        let offset = "CHIP Labels { IN in; OUT out; PARTS: x: Not(in=in, out=a); ".len();
+        assert_eq!(error.range, offset..offset + 1);
+    }
+
+    #[test]
+    fn test_shared_structure() {
+        let source = "CHIP Copy { IN in; OUT out; PARTS: Not(in=in, out=x); Not(in=x, out=out); }";
+        let chip = create_chip(Span::from(source)).unwrap();
+        let interface = chip.interface();
+        let Form::Native(connections) = chip.logic else {
+            unreachable!()
+        };
+        let mut builder = ChipBuilder::new();
+        builder
+            .update_source(
+                "Not.hdl",
+                "CHIP Not { IN in; OUT out; PARTS: Nand(a=in, b=in, out=out); }",
+            )
+            .unwrap();
+        let mut a = native_chip(|x| builder.resolve_chip(x).ok(), interface, connections).unwrap();
+        let mut b = a.clone();
+        // only the state of each copy is its own
+        assert!(Arc::ptr_eq(&a.structure, &b.structure));
+        assert_eq!(a.eval(&[true]), [true]);
+        assert_eq!(b.part(NodeIndex::new(0)).interface().name.as_ref(), "Not");
+        assert_eq!(b.pin_values()[0].1, [Some(false)]);
+        assert_eq!(b.eval(&[false]), [false]);
+        assert_eq!(a.pin_values()[0].1, [Some(true)]);
+    }
 }
@@ -0,0 +1,162 @@
+use super::NativeChip;
+use crate::model::chip::explain::Influence;
+use crate::model::chip::Chip;
+use crate::model::parser::{Direction, Interface};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction as EdgeDirection;
+use std::collections::{HashSet, VecDeque};
+
+/// A single input or output bit of a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum NodeBit {
+    In(NodeIndex, usize),
+    Out(NodeIndex, usize),
+}
+
+/// The pin of an interface which a bit belongs to, and the index of the bit within it
+fn pin_at(interface: &Interface, direction: Direction, bit: usize) -> Option<(String, u16)> {
+    interface
+        .pins()
+        .find(|pin| pin.direction == direction && pin.range.indices().contains(&bit))
+        .map(|pin| {
+            (
+                pin.name.to_string(),
+                (bit - pin.range.start() as usize) as u16,
+            )
+        })
+}
+
+impl NativeChip {
+    /// See [`Chip::explain`]
+    pub(crate) fn explain(&self, pin: &str, bit: u16) -> Option<Vec<Influence>> {
+        let start = match pin.split_once('.') {
+            None => {
+                let pin = self.structure.interface.outputs().find(|x| x.name == pin)?;
+                (bit < pin.width).then_some(NodeBit::In(
+                    self.output_index(),
+                    (pin.range.start() + bit) as usize,
+                ))?
+            }
+            Some((label, rest)) => {
+                let node = self.node(label)?;
+                if rest.contains('.') {
+                    // the walk stays inside the part, and so ends at its inputs
+                    let Chip::Native(chip) = self.part(node) else {
+                        return None;
+                    };
+                    let mut influences = chip.explain(rest, bit)?;
+                    for influence in influences.iter_mut() {
+                        influence.pin = format!("{label}.{}", influence.pin);
+                    }
+                    return Some(influences);
+                }
+                let pin = self
+                    .part(node)
+                    .interface()
+                    .pins()
+                    .find(|x| x.name == rest)
+                    .filter(|x| bit < x.width)
+                    .map(|x| (x.direction, (x.range.start() + bit) as usize))?;
+                match pin {
+                    (Direction::In, bit) => NodeBit::In(node, bit),
+                    (Direction::Out, bit) => NodeBit::Out(node, bit),
+                }
+            }
+        };
+        let mut influences = Vec::new();
+        self.trace(start, &mut influences);
+        Some(influences)
+    }
+
+    /// Walks back from a bit, breadth first, adding every bit it depends on to `influences`.
+    /// Returns the input bits of this chip which were reached. Parts are treated as a whole, but
+    /// native parts are walked through to find which of their inputs an output depends on.
+    fn trace(&self, start: NodeBit, influences: &mut Vec<Influence>) -> Vec<usize> {
+        let mut inputs = Vec::new();
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([(start, 0)]);
+        while let Some((current, depth)) = queue.pop_front() {
+            let mut push = |bit: NodeBit, depth| {
+                if seen.insert(bit) {
+                    queue.push_back((bit, depth));
+                }
+            };
+            let (node, direction, index) = match current {
+                NodeBit::In(node, index) => (node, Direction::In, index),
+                NodeBit::Out(node, index) => (node, Direction::Out, index),
+            };
+            if node == self.input_index() {
+                inputs.push(index);
+            }
+            let interface = self.part(node).interface();
+            if let Some((name, bit)) = pin_at(&interface, direction, index) {
+                let pin = if node == self.input_index() || node == self.output_index() {
+                    name
+                } else {
+                    format!("{}.{name}", self.label(node))
+                };
+                influences.push(Influence {
+                    pin,
+                    bit,
+                    value: self.bit_value(current),
+                    depth,
+                });
+            }
+
+            match current {
+                NodeBit::In(node, index) => {
+                    // the edge driving the bit, if it is connected
+                    if let Some(edge) = self
+                        .conn_graph()
+                        .edges_directed(node, EdgeDirection::Incoming)
+                        .find(|edge| {
+                            let range = edge.weight().out_range();
+                            range.indices().contains(&index)
+                        })
+                    {
+                        let offset = index - edge.weight().out_range().start() as usize;
+                        let source = edge.weight().in_range().start() as usize + offset;
+                        push(NodeBit::Out(edge.source(), source), depth + 1);
+                    }
+                }
+                NodeBit::Out(node, _) if node == self.input_index() => {}
+                NodeBit::Out(node, index) => match self.part(node) {
+                    Chip::Native(chip) => {
+                        let start = NodeBit::In(chip.output_index(), index);
+                        for input in chip.trace(start, &mut Vec::new()) {
+                            push(NodeBit::In(node, input), depth + 1);
+                        }
+                    }
+                    // every combinational input of a builtin is assumed to matter, and the
+                    // walk stops at its state
+                    Chip::Builtin(chip) => {
+                        let interface = chip.interface();
+                        let mut ranges = interface.com_in.values().collect::<Vec<_>>();
+                        ranges.sort_by_key(|x| x.start());
+                        for input in ranges.into_iter().flat_map(|x| x.iter()) {
+                            push(NodeBit::In(node, input as usize), depth + 1);
+                        }
+                    }
+                },
+            }
+        }
+        inputs
+    }
+
+    fn bit_value(&self, bit: NodeBit) -> Option<bool> {
+        match bit {
+            NodeBit::In(node, index) => self.pins[node.index()].get(index).copied(),
+            NodeBit::Out(node, index) if node == self.input_index() => {
+                self.pins[node.index()].get(index).copied()
+            }
+            NodeBit::Out(node, index) => self.conn_graph().edges(node).find_map(|edge| {
+                let range = edge.weight().in_range();
+                range
+                    .indices()
+                    .contains(&index)
+                    .then(|| self.buf(edge.id())[index - range.start() as usize])
+            }),
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use crate::bus_range::BusRange;
 use crate::clock_behavior::ClockBehavior;
+use crate::model::chip::error::{BuildError, BuildErrorKind};
 use derive_more::{Deref, DerefMut};
 use petgraph::graph::NodeIndex;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::ops::Range;
 
 #[derive(Debug, Deref, DerefMut)]
 pub struct EdgeSetMap(HashMap<String, EdgeSet>);
@@ -15,7 +17,7 @@ pub struct EdgeSet {
 }
 
 impl EdgeSet {
-    pub fn new_with(endpoint: Endpoint, as_input: bool) -> Result<Self, ()> {
+    pub fn new_with(endpoint: Endpoint, as_input: bool) -> Result<Self, Endpoint> {
         let mut new = EdgeSet {
             input: None,
             outputs: Vec::new(),
@@ -24,10 +26,11 @@ impl EdgeSet {
         Ok(new)
     }
 
-    pub fn add(&mut self, endpoint: Endpoint, as_input: bool) -> Result<(), ()> {
+    /// Fails with the endpoint if the set already has an input
+    pub fn add(&mut self, endpoint: Endpoint, as_input: bool) -> Result<(), Endpoint> {
         if as_input {
-            if matches!(self.input, Some(_)) {
-                return Err(());
+            if self.input.is_some() {
+                return Err(endpoint);
             } else {
                 self.input = Some(endpoint)
             }
@@ -38,11 +41,10 @@ impl EdgeSet {
         Ok(())
     }
 
-    pub fn iter(&self) -> Result<impl Iterator<Item = (&Endpoint, &Endpoint)>, ()> {
+    pub fn iter(&self) -> Option<impl Iterator<Item = (&Endpoint, &Endpoint)>> {
         self.input
             .as_ref()
             .map(|i| self.outputs.iter().map(move |x| (i, x)))
-            .ok_or(())
     }
 }
 
@@ -51,16 +53,38 @@ impl EdgeSetMap {
         Self(HashMap::new())
     }
 
-    pub fn insert(&mut self, k: String, v: Endpoint, input: bool) -> Result<(), ()> {
-        match self.entry(k) {
-            Entry::Occupied(mut e) => {
-                e.get_mut().add(v, input)?;
-            }
-            Entry::Vacant(e) => {
-                e.insert(EdgeSet::new_with(v, input)?);
+    /// Every wire from the input of a set to each of its outputs
+    pub fn wires(&self) -> Result<Vec<Wire>, BuildError> {
+        let mut wires = Vec::new();
+        for (name, set) in self.iter() {
+            let Some(edges) = set.iter() else {
+                return Err(BuildError {
+                    kind: BuildErrorKind::Undriven(name.clone()),
+                    range: set.outputs[0].location.clone(),
+                });
+            };
+            for (from, to) in edges {
+                wires.push(Wire {
+                    name: name.clone(),
+                    from: from.clone(),
+                    to: to.clone(),
+                });
             }
+        }
+        Ok(wires)
+    }
+
+    pub fn insert(&mut self, k: String, v: Endpoint, input: bool) -> Result<(), BuildError> {
+        let added = match self.entry(k.clone()) {
+            Entry::Occupied(mut e) => e.get_mut().add(v, input),
+            Entry::Vacant(e) => EdgeSet::new_with(v, input).map(|x| {
+                e.insert(x);
+            }),
         };
-        Ok(())
+        added.map_err(|endpoint| BuildError {
+            kind: BuildErrorKind::MultipleDrivers(k),
+            range: endpoint.location,
+        })
     }
 }
 
@@ -69,4 +93,76 @@ pub struct Endpoint {
     pub index: NodeIndex,
     pub range: BusRange,
     pub clocked: ClockBehavior,
+    /// The pin of the chip at `index`
+    pub pin: String,
+    /// The bytes of the HDL which connect the pin
+    pub location: Range<usize>,
+}
+
+/// A connection from bits of the outputs of one chip to the same number of bits of the inputs of
+/// another
+#[derive(Debug, Clone)]
+pub struct Wire {
+    pub name: String,
+    pub from: Endpoint,
+    pub to: Endpoint,
+}
+
+impl Wire {
+    pub fn clocked(&self) -> ClockBehavior {
+        self.from.clocked.and(&self.to.clocked)
+    }
+
+    fn repeats(&self, other: &Wire) -> bool {
+        self.from.index == other.from.index
+            && self.to.index == other.to.index
+            && self.from.range == other.from.range
+            && self.to.range == other.to.range
+    }
+
+    /// Whether `next` picks up at both ends where this wire stops, such as `in[8..15]=a[8..15]`
+    /// after `in[0..7]=a[0..7]`
+    fn continued_by(&self, next: &Wire) -> bool {
+        self.from.index == next.from.index
+            && self.to.index == next.to.index
+            && self.from.range.end() + 1 == next.from.range.start()
+            && self.to.range.end() + 1 == next.to.range.start()
+            && self.clocked() == next.clocked()
+    }
+}
+
+/// Merges the wires between the same chips which continue each other into one, and leaves out
+/// those which are repeated, so that the graph of a chip has as few edges as it can. Fails if two
+/// different wires drive the same input bit. The merged wire keeps the name of the first.
+pub fn coalesce(mut wires: Vec<Wire>) -> Result<Vec<Wire>, BuildError> {
+    wires.sort_by_key(|x| {
+        (
+            x.to.index,
+            x.to.range.start(),
+            x.from.index,
+            x.from.range.start(),
+        )
+    });
+    wires.dedup_by(|x, y| x.repeats(y));
+    let mut merged: Vec<Wire> = Vec::new();
+    for wire in wires {
+        match merged.last_mut() {
+            // sorted by where they start, a wire can only overlap the one before it
+            Some(last)
+                if last.to.index == wire.to.index && last.to.range.overlaps(&wire.to.range) =>
+            {
+                return Err(BuildError {
+                    kind: BuildErrorKind::InputDrivenTwice(wire.to.pin),
+                    range: wire.to.location,
+                })
+            }
+            Some(last) if last.continued_by(&wire) => {
+                last.from.range =
+                    BusRange::new(last.from.range.start(), wire.from.range.end()).unwrap();
+                last.to.range = BusRange::new(last.to.range.start(), wire.to.range.end()).unwrap();
+            }
+            _ => merged.push(wire),
+        }
+    }
+    Ok(merged)
 }
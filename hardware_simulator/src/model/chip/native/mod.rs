@@ -1,26 +1,34 @@
 pub mod build;
+mod cone;
 mod edge_set;
 
 use crate::bus_range::BusRange;
+use crate::initial_state::StateRng;
+use crate::model::chip::fault::FaultSite;
 use crate::model::chip::{Chip, ChipObject};
-use crate::model::parser::Interface;
+use crate::model::parser::{Direction, Interface};
+use petgraph::dot::{Config, Dot};
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::visit::EdgeRef;
 use petgraph::Graph;
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 
+/// A wire between two nodes of a chip. The bits on it belong to each instance of the chip, and
+/// are kept in [`NativeChip`].
 #[derive(Clone, Debug)]
 pub enum ConnEdge {
     Combinatorial {
         name: String,
         in_range: BusRange,
         out_range: BusRange,
-        buf: Vec<bool>,
     },
+    /// Connects to a clocked input, so the value is only needed once the clock ticks and the
+    /// edge can be ignored when ordering evaluation
     Sequential {
         name: String,
         in_range: BusRange,
         out_range: BusRange,
-        waiting: Vec<bool>,
-        buf: Vec<bool>,
     },
 }
 
@@ -39,43 +47,486 @@ impl Display for ConnEdge {
 
 impl ConnEdge {
     fn new_com(name: String, in_range: BusRange, out_range: BusRange) -> Self {
-        let size = in_range.size() as usize;
         Self::Combinatorial {
             name,
             in_range,
             out_range,
-            buf: Vec::with_capacity(size),
         }
     }
     fn new_seq(name: String, in_range: BusRange, out_range: BusRange) -> Self {
-        let size = in_range.size() as usize;
         Self::Sequential {
             name,
             in_range,
             out_range,
-            waiting: Vec::with_capacity(size),
-            buf: Vec::with_capacity(size),
+        }
+    }
+
+    /// The range of the source's outputs the edge is driven by
+    pub fn in_range(&self) -> &BusRange {
+        match self {
+            Self::Combinatorial { in_range, .. } => in_range,
+            Self::Sequential { in_range, .. } => in_range,
+        }
+    }
+
+    /// The range of the target's inputs the edge drives
+    pub fn out_range(&self) -> &BusRange {
+        match self {
+            Self::Combinatorial { out_range, .. } => out_range,
+            Self::Sequential { out_range, .. } => out_range,
         }
     }
 }
 
+/// Bits of the inputs of a part which are given in the HDL, such as `sel=true`, rather than
+/// driven by an edge
+#[derive(Clone, Debug)]
+pub(crate) struct Constant {
+    pub node: NodeIndex,
+    /// The range of the node's inputs which holds the bits
+    pub range: BusRange,
+    pub bits: Vec<bool>,
+}
+
+/// What every instance of a chip has in common. It never changes once the chip is built, so
+/// copies of the chip, such as the many identical parts of a memory, share it instead of each
+/// having their own.
+struct Structure {
+    /// The label of every part, and the wires between them
+    conn_graph: Graph<Arc<str>, ConnEdge>,
+    interface: Interface,
+    input_index: NodeIndex,
+    output_index: NodeIndex,
+    /// The order in which nodes are evaluated, respecting combinatorial edges where possible
+    order: Vec<NodeIndex>,
+    constants: Vec<Constant>,
+}
+
 #[derive(Clone)]
 pub struct NativeChip {
-    pub conn_graph: Graph<Chip, ConnEdge>,
-    pub interface: Interface,
+    structure: Arc<Structure>,
+    /// The chip of every node, indexed by node
+    parts: Vec<Chip>,
+    /// The bits on every edge, indexed by edge
+    bufs: Vec<Vec<bool>>,
+    /// The input pins of every node, indexed by node
+    pins: Vec<Vec<bool>>,
+    faults: Vec<NodeFault>,
+    counters: Counters,
+}
+
+/// The work done by evaluating a chip, see [`crate::simulator::Metrics`]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Counters {
+    pub primitive_evals: u64,
+    pub traversals: u64,
+}
+
+/// A stuck-at fault on the inputs or outputs of a node
+#[derive(Clone, Debug)]
+struct NodeFault {
+    node: NodeIndex,
+    direction: Direction,
+    bit: usize,
+    value: bool,
+}
+
+/// Applies the faults of a node to some of its pins, which start at bit `offset`
+fn force(
+    faults: &[NodeFault],
+    node: NodeIndex,
+    direction: Direction,
+    offset: usize,
+    bits: &mut [bool],
+) {
+    for fault in faults {
+        if fault.node == node
+            && fault.direction == direction
+            && (offset..offset + bits.len()).contains(&fault.bit)
+        {
+            bits[fault.bit - offset] = fault.value;
+        }
+    }
+}
+
+impl NativeChip {
+    /// A chip whose nodes are `graph`, named by `labels`, of which the last two are its input and
+    /// output
+    pub(crate) fn new(
+        graph: Graph<Chip, ConnEdge>,
+        labels: Vec<Arc<str>>,
+        interface: Interface,
+        order: Vec<NodeIndex>,
+        constants: Vec<Constant>,
+    ) -> Self {
+        let conn_graph = graph.map(|index, _| labels[index.index()].clone(), |_, x| x.clone());
+        let bufs = conn_graph
+            .edge_weights()
+            .map(|x| vec![false; x.in_range().width() as usize])
+            .collect();
+        let parts = graph
+            .into_nodes_edges()
+            .0
+            .into_iter()
+            .map(|x| x.weight)
+            .collect::<Vec<_>>();
+        let mut pins = parts
+            .iter()
+            .map(|chip| vec![false; chip.interface().input_width()])
+            .collect::<Vec<_>>();
+        // the input node holds the inputs of the chip, which it passes on as its outputs
+        let input = parts.len() - 2;
+        pins[input] = vec![false; parts[input].interface().output_width()];
+        let mut chip = Self {
+            structure: Arc::new(Structure {
+                input_index: NodeIndex::new(input),
+                output_index: NodeIndex::new(parts.len() - 1),
+                conn_graph,
+                interface,
+                order,
+                constants,
+            }),
+            parts,
+            bufs,
+            pins,
+            faults: Vec::new(),
+            counters: Default::default(),
+        };
+        chip.hold_constants();
+        chip
+    }
+
+    /// Writes the constants onto the inputs they are given to. Nothing else writes those bits,
+    /// so they only need to be written again when all pins are cleared.
+    fn hold_constants(&mut self) {
+        for constant in self.structure.constants.iter() {
+            self.pins[constant.node.index()][constant.range.indices()]
+                .copy_from_slice(&constant.bits);
+        }
+    }
+
+    pub(crate) fn conn_graph(&self) -> &Graph<Arc<str>, ConnEdge> {
+        &self.structure.conn_graph
+    }
+
+    fn input_index(&self) -> NodeIndex {
+        self.structure.input_index
+    }
+
+    fn output_index(&self) -> NodeIndex {
+        self.structure.output_index
+    }
+
+    /// The chip at a node
+    pub(crate) fn part(&self, index: NodeIndex) -> &Chip {
+        &self.parts[index.index()]
+    }
+
+    /// The chips of the parts, and of the input and output
+    pub(crate) fn parts(&self) -> &[Chip] {
+        &self.parts
+    }
+
+    pub(crate) fn parts_mut(&mut self) -> &mut [Chip] {
+        &mut self.parts
+    }
+
+    /// The bits on an edge
+    fn buf(&self, edge: EdgeIndex) -> &[bool] {
+        &self.bufs[edge.index()]
+    }
+
+    /// The work done by this chip and every native part in it, at any depth
+    pub(crate) fn counters(&self) -> Counters {
+        let mut counters = self.counters;
+        for chip in self.parts.iter() {
+            if let Chip::Native(chip) = chip {
+                let part = chip.counters();
+                counters.primitive_evals += part.primitive_evals;
+                counters.traversals += part.traversals;
+            }
+        }
+        counters
+    }
+
+    pub(crate) fn clear_counters(&mut self) {
+        self.counters = Counters::default();
+        for chip in self.parts.iter_mut() {
+            if let Chip::Native(chip) = chip {
+                chip.clear_counters();
+            }
+        }
+    }
+
+    pub fn label(&self, index: NodeIndex) -> &str {
+        &self.structure.conn_graph[index]
+    }
+
+    /// Renders the connection graph in the graphviz format, naming nodes by their labels
+    pub fn dot(&self) -> String {
+        format!(
+            "{}",
+            Dot::with_attr_getters(
+                self.conn_graph(),
+                &[Config::NodeNoLabel],
+                &|_, _| String::new(),
+                &|_, (index, label)| format!("label = \"{label}: {}\"", self.part(index)),
+            )
+        )
+    }
+
+    /// Writes a line for every part, and for the parts of those in turn, indented by `depth`. See
+    /// [`Chip`]'s `Debug` implementation.
+    pub(crate) fn write_tree(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        // the input and output nodes are not parts
+        for node in (0..self.parts.len() - 2).map(NodeIndex::new) {
+            let (label, chip) = (self.label(node), self.part(node));
+            write!(f, "\n{:indent$}{label}: {chip}", "", indent = 2 * depth)?;
+            if let Chip::Native(chip) = chip {
+                chip.write_tree(f, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn node(&self, label: &str) -> Option<NodeIndex> {
+        // the input and output nodes are not parts
+        (0..self.parts.len() - 2)
+            .map(NodeIndex::new)
+            .find(|&i| self.label(i) == label)
+    }
+
+    /// See [`Chip::inject_fault`]
+    pub(crate) fn inject_fault(&mut self, pin: &str, bit: u16, value: bool) -> bool {
+        let (node, pin) = match pin.split_once('.') {
+            // a pin of this chip is seen as an output of the input node, or as an input of the
+            // output node
+            None => (None, pin),
+            Some((label, rest)) => match self.node(label) {
+                Some(node) if rest.contains('.') => {
+                    return match &mut self.parts[node.index()] {
+                        Chip::Native(chip) => chip.inject_fault(rest, bit, value),
+                        Chip::Builtin(_) => false,
+                    }
+                }
+                Some(node) => (Some(node), rest),
+                None => return false,
+            },
+        };
+        let interface = match node {
+            Some(node) => self.part(node).interface(),
+            None => self.structure.interface.clone(),
+        };
+        let Some(found) = interface.pins().find(|x| x.name == pin) else {
+            return false;
+        };
+        if bit >= found.width {
+            return false;
+        }
+        let (node, direction) = match (node, found.direction) {
+            (Some(node), direction) => (node, direction),
+            (None, Direction::In) => (self.input_index(), Direction::Out),
+            (None, Direction::Out) => (self.output_index(), Direction::In),
+        };
+        self.faults.push(NodeFault {
+            node,
+            direction,
+            bit: (found.range.start() + bit) as usize,
+            value,
+        });
+        self.force_inputs();
+        true
+    }
+
+    /// Applies faults on inputs which are not written by the next evaluation, as their value
+    /// has not changed
+    fn force_inputs(&mut self) {
+        for fault in self.faults.iter() {
+            if fault.direction == Direction::In {
+                self.pins[fault.node.index()][fault.bit] = fault.value;
+            }
+        }
+    }
+
+    pub(crate) fn clear_faults(&mut self) {
+        self.faults.clear();
+        for chip in self.parts.iter_mut() {
+            chip.clear_faults();
+        }
+        // inputs which were held go back to the values driven onto them
+        let structure = self.structure.clone();
+        for edge in structure.conn_graph.edge_references() {
+            let range = edge.weight().out_range();
+            self.pins[edge.target().index()][range.indices()]
+                .copy_from_slice(&self.bufs[edge.id().index()]);
+        }
+        self.hold_constants();
+    }
+
+    /// See [`Chip::fault_sites`]
+    pub(crate) fn fault_sites(&self) -> Vec<FaultSite> {
+        self.pin_values()
+            .into_iter()
+            .map(|(pin, bits)| FaultSite {
+                pin,
+                width: bits.len() as u16,
+            })
+            .collect()
+    }
+
+    /// The current value of every pin which faults can be injected into, in the same order as
+    /// [`fault_sites`](Self::fault_sites). Outputs of parts which drive nothing are unknown.
+    pub(crate) fn pin_values(&self) -> Vec<(String, Vec<Option<bool>>)> {
+        let mut values = self
+            .structure
+            .interface
+            .pins()
+            .map(|pin| {
+                let node = match pin.direction {
+                    Direction::In => self.input_index(),
+                    Direction::Out => self.output_index(),
+                };
+                let bits = &self.pins[node.index()][pin.range.indices()];
+                (
+                    pin.name.to_string(),
+                    bits.iter().map(|x| Some(*x)).collect(),
+                )
+            })
+            .collect();
+        self.part_values("", &mut values);
+        values
+    }
+
+    fn part_values(&self, prefix: &str, values: &mut Vec<(String, Vec<Option<bool>>)>) {
+        for node in self.conn_graph().node_indices() {
+            if node == self.input_index() || node == self.output_index() {
+                continue;
+            }
+            let label = format!("{prefix}{}.", self.label(node));
+            let chip = self.part(node);
+            let interface = chip.interface();
+
+            // the outputs of a part are only kept in the edges they drive
+            let mut outputs = vec![None; interface.output_width()];
+            for edge in self.conn_graph().edges(node) {
+                let start = edge.weight().in_range().start() as usize;
+                for (i, bit) in self.buf(edge.id()).iter().enumerate() {
+                    outputs[start + i] = Some(*bit);
+                }
+            }
+            values.extend(interface.pins().map(|pin| {
+                let range = pin.range.indices();
+                let bits = match pin.direction {
+                    Direction::In => self.pins[node.index()][range]
+                        .iter()
+                        .map(|x| Some(*x))
+                        .collect(),
+                    Direction::Out => outputs[range].to_vec(),
+                };
+                (format!("{label}{}", pin.name), bits)
+            }));
+            if let Chip::Native(chip) = chip {
+                chip.part_values(&label, values);
+            }
+        }
+    }
 }
 
 impl ChipObject for NativeChip {
     fn interface(&self) -> Interface {
-        self.interface.clone()
+        self.structure.interface.clone()
+    }
+
+    fn tick(&mut self) {
+        for chip in self.parts.iter_mut() {
+            chip.tick();
+        }
     }
 
     fn clock(&mut self) {
-        todo!()
+        for chip in self.parts.iter_mut() {
+            chip.clock();
+        }
+    }
+
+    fn reset(&mut self, clear_memory: bool) {
+        for chip in self.parts.iter_mut() {
+            chip.reset(clear_memory);
+        }
+        for pins in self.pins.iter_mut() {
+            pins.fill(false);
+        }
+        self.hold_constants();
+        self.force_inputs();
+    }
+
+    fn randomize(&mut self, rng: &mut StateRng, memory: bool) {
+        for chip in self.parts.iter_mut() {
+            chip.randomize(rng, memory);
+        }
     }
 
-    fn eval(&mut self, _: &[bool]) -> Vec<bool> {
-        todo!()
+    fn eval(&mut self, pins: &[bool]) -> Vec<bool> {
+        let structure = self.structure.clone();
+        let (input, output) = (structure.input_index, structure.output_index);
+        self.pins[input.index()] = pins.to_vec();
+
+        // Nodes are evaluated in order until their inputs settle. Nodes only reached through
+        // sequential edges (or through combinatorial loops) may need more than one pass.
+        let mut dirty = vec![true; self.parts.len()];
+        for _ in 0..=structure.order.len() {
+            self.counters.traversals += 1;
+            let mut settled = true;
+            for &node in structure.order.iter() {
+                if !dirty[node.index()] {
+                    continue;
+                }
+                dirty[node.index()] = false;
+                settled = false;
+                // the input and output nodes only pass bits along
+                if matches!(self.parts[node.index()], Chip::Builtin(_))
+                    && node != input
+                    && node != output
+                {
+                    self.counters.primitive_evals += 1;
+                }
+
+                let mut outputs = self.parts[node.index()].eval(&self.pins[node.index()]);
+                force(&self.faults, node, Direction::Out, 0, &mut outputs);
+                for edge in structure.conn_graph.edges(node) {
+                    let buf = &mut self.bufs[edge.id().index()];
+                    buf.copy_from_slice(&outputs[edge.weight().in_range().indices()]);
+                    let range = edge.weight().out_range();
+                    let target = edge.target();
+                    // pins held by a fault are compared as held, or they would never settle
+                    let forced;
+                    let value = if self.faults.is_empty() {
+                        buf.as_slice()
+                    } else {
+                        let mut bits = buf.clone();
+                        force(
+                            &self.faults,
+                            target,
+                            Direction::In,
+                            range.start() as usize,
+                            &mut bits,
+                        );
+                        forced = bits;
+                        forced.as_slice()
+                    };
+                    let target_pins = &mut self.pins[target.index()][range.indices()];
+                    if target_pins != value {
+                        target_pins.copy_from_slice(value);
+                        dirty[target.index()] = true;
+                    }
+                }
+            }
+            if settled {
+                break;
+            }
+        }
+
+        self.pins[output.index()].clone()
     }
 
     fn chip_clone(&self) -> Box<dyn ChipObject> {
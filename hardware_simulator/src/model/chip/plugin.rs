@@ -0,0 +1,520 @@
+//! Builtin chips provided by external plugins.
+//!
+//! A plugin is a shared library exporting a single C-ABI entry point named `hdl_plugin_register`
+//! with the signature of [`PluginEntry`]. When loaded, the simulator calls it with the ABI version
+//! it understands and a callback; the plugin calls the callback once for every chip it provides,
+//! passing a [`PluginChip`] descriptor. Everything crossing the boundary is `#[repr(C)]`, so plugins
+//! do not need to be compiled with the same compiler as the simulator.
+//!
+//! The state of an instance may be handed to the plugin from any thread, but never from two
+//! threads at once.
+
+use crate::bus_range::BusRange;
+use crate::intern::intern;
+use crate::model::chip::error::ModelConstructionError;
+use crate::model::chip::ChipObject;
+use crate::model::parser::interface::PinMap;
+use crate::model::parser::Interface;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// The version of the plugin ABI described by this module. Plugins should refuse to register
+/// anything if they are handed a version they were not built against.
+pub const HDL_PLUGIN_ABI_VERSION: u32 = 1;
+
+/// The name of the symbol the simulator looks up in a plugin library.
+pub const HDL_PLUGIN_ENTRY: &[u8] = b"hdl_plugin_register";
+
+/// A single pin of a plugin chip.
+#[repr(C)]
+pub struct PluginPin {
+    /// Nul-terminated pin name
+    pub name: *const c_char,
+    pub width: u16,
+    /// Whether the pin only changes state on the clock, like the `CLOCKED` declaration in HDL
+    pub clocked: bool,
+}
+
+/// Describes a chip provided by a plugin. The name and pin arrays only need to live for the
+/// duration of the registration call; the function pointers must live as long as the library.
+/// A chip with a null pointer, other than for an empty pin array, is refused. The function
+/// pointers are `Option`s so that a null one can be told apart, with the same layout as in C.
+#[repr(C)]
+pub struct PluginChip {
+    /// Nul-terminated chip name
+    pub name: *const c_char,
+    pub inputs: *const PluginPin,
+    pub input_count: usize,
+    pub outputs: *const PluginPin,
+    pub output_count: usize,
+    /// Creates the state of a new instance of the chip
+    pub new: Option<unsafe extern "C" fn() -> *mut c_void>,
+    /// Evaluates the chip, reading `pin_count` input bits and writing `out_count` output bits.
+    /// Bits are ordered as in [`Interface`]: clocked pins first, in declaration order.
+    pub eval: Option<
+        unsafe extern "C" fn(
+            state: *mut c_void,
+            pins: *const bool,
+            pin_count: usize,
+            out: *mut bool,
+            out_count: usize,
+        ),
+    >,
+    pub clock: Option<unsafe extern "C" fn(state: *mut c_void)>,
+    pub clone: Option<unsafe extern "C" fn(state: *const c_void) -> *mut c_void>,
+    pub drop: Option<unsafe extern "C" fn(state: *mut c_void)>,
+}
+
+/// Opaque handle passed to plugins, only to be handed back to the [`RegisterFn`] callback.
+pub struct PluginRegistrar {
+    chips: Vec<PluginBuiltin>,
+    /// Why chips could not be registered
+    errors: Vec<String>,
+}
+
+/// Callback used by a plugin to register one of its chips.
+pub type RegisterFn =
+    unsafe extern "C" fn(registrar: *mut PluginRegistrar, chip: *const PluginChip);
+
+/// Signature of `hdl_plugin_register`. Returns `false` if the plugin does not support the given
+/// ABI version.
+pub type PluginEntry = unsafe extern "C" fn(
+    abi_version: u32,
+    registrar: *mut PluginRegistrar,
+    register: RegisterFn,
+) -> bool;
+
+#[derive(Clone, Copy)]
+struct VTable {
+    new: unsafe extern "C" fn() -> *mut c_void,
+    eval: unsafe extern "C" fn(*mut c_void, *const bool, usize, *mut bool, usize),
+    clock: unsafe extern "C" fn(*mut c_void),
+    clone: unsafe extern "C" fn(*const c_void) -> *mut c_void,
+    drop: unsafe extern "C" fn(*mut c_void),
+}
+
+/// A chip definition which has been copied out of a plugin
+pub struct PluginBuiltin {
+    interface: Interface,
+    in_width: usize,
+    out_width: usize,
+    vtable: VTable,
+    // keeps the code behind `vtable` mapped for as long as any instance is alive
+    #[cfg(feature = "plugins")]
+    _library: Option<Arc<libloading::Library>>,
+}
+
+impl PluginBuiltin {
+    pub fn name(&self) -> &str {
+        &self.interface.name
+    }
+
+    pub fn instantiate(self: &Arc<Self>) -> Box<dyn ChipObject> {
+        Box::new(PluginInstance {
+            state: Mutex::new(State(unsafe { (self.vtable.new)() })),
+            builtin: self.clone(),
+        })
+    }
+}
+
+/// A string from a plugin, or `None` if the pointer is null
+unsafe fn read_str(ptr: *const c_char) -> Option<Arc<str>> {
+    (!ptr.is_null()).then(|| intern(&CStr::from_ptr(ptr).to_string_lossy()))
+}
+
+unsafe fn read_pins(
+    ptr: *const PluginPin,
+    count: usize,
+) -> Result<Vec<(Arc<str>, u16, bool)>, String> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    if ptr.is_null() {
+        return Err(format!("The array of {count} pins is null"));
+    }
+    std::slice::from_raw_parts(ptr, count)
+        .iter()
+        .enumerate()
+        .map(|(i, pin)| {
+            let name = read_str(pin.name).ok_or_else(|| format!("Pin {i} has no name"))?;
+            Ok((name, pin.width, pin.clocked))
+        })
+        .collect()
+}
+
+// lays out the pins the same way as the HDL interface of a builtin: clocked pins first
+fn split_pins(pins: &[(Arc<str>, u16, bool)]) -> Result<(PinMap, PinMap, u16), String> {
+    let mut next = 0u16;
+    let mut place = |clocked: bool| {
+        let mut map = HashMap::new();
+        for (name, width, _) in pins.iter().filter(|(_, _, c)| *c == clocked) {
+            let range = BusRange::with_width(next, *width)
+                .ok_or_else(|| format!("Pin `{name}` has a width of {width}"))?;
+            next = range.end().checked_add(1).ok_or("The pins are too wide")?;
+            map.insert(name.clone(), range);
+        }
+        Ok::<_, String>(map)
+    };
+    let seq = place(true)?;
+    let com = place(false)?;
+    Ok((seq, com, next))
+}
+
+// panicking here would unwind into the plugin, so mistakes are reported once it returns
+unsafe extern "C" fn register(registrar: *mut PluginRegistrar, chip: *const PluginChip) {
+    // without a registrar there is nowhere to report to
+    let Some(registrar) = registrar.as_mut() else {
+        return;
+    };
+    let Some(chip) = chip.as_ref() else {
+        registrar
+            .errors
+            .push("Cannot register a chip without a descriptor".to_string());
+        return;
+    };
+    let Some(name) = read_str(chip.name) else {
+        registrar
+            .errors
+            .push("Cannot register a chip without a name".to_string());
+        return;
+    };
+    match read_chip(name.clone(), chip) {
+        Ok(builtin) => registrar.chips.push(builtin),
+        Err(e) => registrar
+            .errors
+            .push(format!("Cannot register chip `{name}`: {e}")),
+    }
+}
+
+unsafe fn read_chip(name: Arc<str>, chip: &PluginChip) -> Result<PluginBuiltin, String> {
+    let inputs = read_pins(chip.inputs, chip.input_count).map_err(|e| format!("Inputs: {e}"))?;
+    let outputs =
+        read_pins(chip.outputs, chip.output_count).map_err(|e| format!("Outputs: {e}"))?;
+    let (seq_in, com_in, in_width) = split_pins(&inputs)?;
+    let (seq_out, com_out, out_width) = split_pins(&outputs)?;
+    let missing = |function| format!("The `{function}` function is null");
+    let vtable = VTable {
+        new: chip.new.ok_or_else(|| missing("new"))?,
+        eval: chip.eval.ok_or_else(|| missing("eval"))?,
+        clock: chip.clock.ok_or_else(|| missing("clock"))?,
+        clone: chip.clone.ok_or_else(|| missing("clone"))?,
+        drop: chip.drop.ok_or_else(|| missing("drop"))?,
+    };
+
+    Ok(PluginBuiltin {
+        interface: Interface {
+            name,
+            com_in,
+            com_out,
+            seq_in,
+            seq_out,
+            order: inputs
+                .iter()
+                .chain(outputs.iter())
+                .map(|(name, ..)| name.clone())
+                .collect(),
+            doc: None,
+        },
+        in_width: in_width as usize,
+        out_width: out_width as usize,
+        vtable,
+        #[cfg(feature = "plugins")]
+        _library: None,
+    })
+}
+
+/// Calls a plugin entry point and collects every chip it registers.
+///
+/// # Safety
+/// `entry` must follow the contract described in the [module documentation](self).
+pub unsafe fn register_plugin(
+    entry: PluginEntry,
+) -> Result<Vec<PluginBuiltin>, ModelConstructionError> {
+    let mut registrar = PluginRegistrar {
+        chips: Vec::new(),
+        errors: Vec::new(),
+    };
+    if !entry(HDL_PLUGIN_ABI_VERSION, &mut registrar, register) {
+        return Err(ModelConstructionError::PluginError(format!(
+            "Plugin does not support ABI version {HDL_PLUGIN_ABI_VERSION}"
+        )));
+    }
+    match registrar.errors.into_iter().next() {
+        Some(e) => Err(ModelConstructionError::PluginError(e)),
+        None => Ok(registrar.chips),
+    }
+}
+
+/// Loads a plugin from a shared library, collecting every chip it registers.
+///
+/// # Safety
+/// Loading a library runs arbitrary code; the library must follow the contract described in the
+/// [module documentation](self).
+#[cfg(feature = "plugins")]
+pub unsafe fn load_plugin(
+    path: &std::path::Path,
+) -> Result<Vec<PluginBuiltin>, ModelConstructionError> {
+    let library = Arc::new(
+        libloading::Library::new(path)
+            .map_err(|e| ModelConstructionError::PluginError(e.to_string()))?,
+    );
+    let entry = *library
+        .get::<PluginEntry>(HDL_PLUGIN_ENTRY)
+        .map_err(|e| ModelConstructionError::PluginError(e.to_string()))?;
+    let mut chips = register_plugin(entry)?;
+    for chip in chips.iter_mut() {
+        chip._library = Some(library.clone());
+    }
+    Ok(chips)
+}
+
+/// The state of an instance of a plugin chip
+struct State(*mut c_void);
+
+// the state belongs to one instance alone, and plugins promise not to tie it to a thread
+unsafe impl Send for State {}
+
+struct PluginInstance {
+    builtin: Arc<PluginBuiltin>,
+    // only copying an instance reads the state through a shared reference, which the lock keeps
+    // from happening on two threads at once
+    state: Mutex<State>,
+}
+
+impl PluginInstance {
+    fn state(&mut self) -> *mut c_void {
+        self.state
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+    }
+
+    fn replace_state(&mut self, state: *mut c_void) {
+        self.state
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0 = state;
+    }
+}
+
+impl ChipObject for PluginInstance {
+    fn interface(&self) -> Interface {
+        self.builtin.interface.clone()
+    }
+
+    fn clock(&mut self) {
+        unsafe { (self.builtin.vtable.clock)(self.state()) }
+    }
+
+    // plugins cannot tell their memory apart from the rest of their state, so they are only reset
+    // by being recreated
+    fn reset(&mut self, clear_memory: bool) {
+        if clear_memory {
+            unsafe {
+                (self.builtin.vtable.drop)(self.state());
+                self.replace_state((self.builtin.vtable.new)());
+            }
+        }
+    }
+
+    fn eval(&mut self, pins: &[bool]) -> Vec<bool> {
+        let mut out = vec![false; self.builtin.out_width];
+        let pin_count = pins.len().min(self.builtin.in_width);
+        let state = self.state();
+        unsafe {
+            (self.builtin.vtable.eval)(state, pins.as_ptr(), pin_count, out.as_mut_ptr(), out.len())
+        }
+        out
+    }
+
+    fn chip_clone(&self) -> Box<dyn ChipObject> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        Box::new(PluginInstance {
+            builtin: self.builtin.clone(),
+            state: Mutex::new(State(unsafe { (self.builtin.vtable.clone)(state.0) })),
+        })
+    }
+}
+
+impl Drop for PluginInstance {
+    fn drop(&mut self) {
+        let state = self.state();
+        unsafe { (self.builtin.vtable.drop)(state) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ptr::null_mut;
+
+    unsafe extern "C" fn new() -> *mut c_void {
+        null_mut()
+    }
+    unsafe extern "C" fn eval(
+        _: *mut c_void,
+        pins: *const bool,
+        n: usize,
+        out: *mut bool,
+        m: usize,
+    ) {
+        let pins = std::slice::from_raw_parts(pins, n);
+        let out = std::slice::from_raw_parts_mut(out, m);
+        out[0] = pins[0] && pins[1];
+    }
+    unsafe extern "C" fn clock(_: *mut c_void) {}
+    unsafe extern "C" fn clone(_: *const c_void) -> *mut c_void {
+        null_mut()
+    }
+    unsafe extern "C" fn drop(_: *mut c_void) {}
+
+    unsafe extern "C" fn entry(
+        version: u32,
+        registrar: *mut PluginRegistrar,
+        register: RegisterFn,
+    ) -> bool {
+        version == HDL_PLUGIN_ABI_VERSION && register_and(registrar, register, 1)
+    }
+
+    unsafe extern "C" fn zero_width_entry(
+        _: u32,
+        registrar: *mut PluginRegistrar,
+        register: RegisterFn,
+    ) -> bool {
+        register_and(registrar, register, 0)
+    }
+
+    /// Registers an And gate whose `b` input is `width` bits wide
+    unsafe fn register_and(
+        registrar: *mut PluginRegistrar,
+        register: RegisterFn,
+        width: u16,
+    ) -> bool {
+        with_and(width, |chip| register(registrar, chip));
+        true
+    }
+
+    /// Describes an And gate whose `b` input is `width` bits wide to `f`
+    fn with_and(width: u16, f: impl FnOnce(&PluginChip)) {
+        let inputs = [
+            PluginPin {
+                name: c"a".as_ptr(),
+                width: 1,
+                clocked: false,
+            },
+            PluginPin {
+                name: c"b".as_ptr(),
+                width,
+                clocked: false,
+            },
+        ];
+        let outputs = [PluginPin {
+            name: c"out".as_ptr(),
+            width: 1,
+            clocked: false,
+        }];
+        f(&PluginChip {
+            name: c"PluginAnd".as_ptr(),
+            inputs: inputs.as_ptr(),
+            input_count: inputs.len(),
+            outputs: outputs.as_ptr(),
+            output_count: outputs.len(),
+            new: Some(new),
+            eval: Some(eval),
+            clock: Some(clock),
+            clone: Some(clone),
+            drop: Some(drop),
+        });
+    }
+
+    /// The errors of registering the And gate after `edit` has changed its descriptor
+    fn register_errors(edit: impl FnOnce(&mut PluginChip)) -> Vec<String> {
+        let mut registrar = PluginRegistrar {
+            chips: Vec::new(),
+            errors: Vec::new(),
+        };
+        with_and(1, |chip| {
+            let mut chip = PluginChip { ..*chip };
+            edit(&mut chip);
+            unsafe { register(&mut registrar, &chip) };
+        });
+        registrar.errors
+    }
+
+    #[test]
+    fn test_register_plugin() {
+        let chips = unsafe { register_plugin(entry) }.unwrap();
+        assert_eq!(chips.len(), 1);
+        let builtin = Arc::new(chips.into_iter().next().unwrap());
+        assert_eq!(builtin.name(), "PluginAnd");
+        assert_eq!(
+            builtin.interface.com_in.get("b"),
+            Some(&BusRange::new(1, 1).unwrap())
+        );
+
+        let mut chip = builtin.instantiate();
+        assert_eq!(chip.eval(&[true, true]), vec![true]);
+        assert_eq!(chip.chip_clone().eval(&[true, false]), vec![false]);
+    }
+
+    #[test]
+    fn test_zero_width_pin() {
+        let Err(ModelConstructionError::PluginError(e)) =
+            (unsafe { register_plugin(zero_width_entry) })
+        else {
+            panic!("a pin without bits was registered");
+        };
+        assert_eq!(
+            e,
+            "Cannot register chip `PluginAnd`: Pin `b` has a width of 0"
+        );
+    }
+
+    #[test]
+    fn test_null_pointers() {
+        let pin = PluginPin {
+            name: std::ptr::null(),
+            width: 1,
+            clocked: false,
+        };
+        assert_eq!(
+            register_errors(|x| x.name = std::ptr::null()),
+            ["Cannot register a chip without a name"]
+        );
+        assert_eq!(
+            register_errors(|x| x.inputs = std::ptr::null()),
+            ["Cannot register chip `PluginAnd`: Inputs: The array of 2 pins is null"]
+        );
+        assert_eq!(
+            register_errors(|x| {
+                x.outputs = &pin;
+                x.output_count = 1;
+            }),
+            ["Cannot register chip `PluginAnd`: Outputs: Pin 0 has no name"]
+        );
+        assert_eq!(
+            register_errors(|x| x.clock = None),
+            ["Cannot register chip `PluginAnd`: The `clock` function is null"]
+        );
+        // an empty array may be null
+        assert!(register_errors(|x| {
+            x.outputs = std::ptr::null();
+            x.output_count = 0;
+        })
+        .is_empty());
+
+        let mut registrar = PluginRegistrar {
+            chips: Vec::new(),
+            errors: Vec::new(),
+        };
+        unsafe {
+            register(&mut registrar, std::ptr::null());
+            register(std::ptr::null_mut(), std::ptr::null());
+        }
+        assert_eq!(
+            registrar.errors,
+            ["Cannot register a chip without a descriptor"]
+        );
+    }
+}
@@ -4,48 +4,49 @@
 //! defined here.
 
 use crate::bus_range::BusRange;
+use crate::intern::intern;
 use crate::model::chip::{Chip, ChipObject};
 use crate::model::parser::Interface;
 use std::collections::HashMap;
 use std::iter::once;
+use std::sync::Arc;
 
-fn all_out(size: u16, name: String) -> Interface {
+fn all_out(size: u16, name: Arc<str>) -> Interface {
     Interface {
-        com_out: once((
-            name,
-            BusRange {
-                start: 0,
-                end: size - 1,
-            },
-        ))
-        .collect(),
+        order: vec![name.clone()],
+        com_out: once((name, BusRange::with_width(0, size).unwrap())).collect(),
         ..Default::default()
     }
 }
 
+fn by_position(h: &HashMap<Arc<str>, BusRange>) -> Vec<Arc<str>> {
+    let mut pins = h.iter().collect::<Vec<_>>();
+    pins.sort_by_key(|(_, range)| range.start());
+    pins.into_iter().map(|(name, _)| name.clone()).collect()
+}
+
 /// Represents a bus. For edges which connect to IN or OUT pins, connect to these instead
 #[derive(Debug, Clone)]
 pub struct VirtualBus {
-    size: u16,
     interface: Interface,
 }
 
 impl VirtualBus {
-    pub fn new_in(h: HashMap<String, BusRange>) -> Chip {
+    pub fn new_in(h: HashMap<Arc<str>, BusRange>) -> Chip {
         Chip::Builtin(Box::new(Self {
-            size: h.iter().map(|(_, x)| x.size()).sum(),
             interface: Interface {
-                name: "_Input".to_string(),
+                name: intern("_Input"),
+                order: by_position(&h),
                 com_out: h,
                 ..Default::default()
             },
         }))
     }
-    pub fn new_out(h: HashMap<String, BusRange>) -> Chip {
+    pub fn new_out(h: HashMap<Arc<str>, BusRange>) -> Chip {
         Chip::Builtin(Box::new(Self {
-            size: h.iter().map(|(_, x)| x.size()).sum(),
             interface: Interface {
-                name: "_Output".to_string(),
+                name: intern("_Output"),
+                order: by_position(&h),
                 com_in: h,
                 ..Default::default()
             },
@@ -79,7 +80,7 @@ struct VirtualConst {
 
 #[allow(dead_code)]
 impl VirtualConst {
-    fn from_number(mut n: usize, channel_size: u16, name: String) -> Self {
+    fn from_number(mut n: usize, channel_size: u16, name: &str) -> Self {
         // TODO: assert that n fits within the channel
         let value = {
             let mut bits = Vec::new();
@@ -91,13 +92,13 @@ impl VirtualConst {
         };
         VirtualConst {
             value,
-            interface: all_out(channel_size, name),
+            interface: all_out(channel_size, intern(name)),
         }
     }
-    fn from_bool(b: bool, channel_size: u16, name: String) -> Self {
+    fn from_bool(b: bool, channel_size: u16, name: &str) -> Self {
         VirtualConst {
             value: vec![b; channel_size as usize],
-            interface: all_out(channel_size, name),
+            interface: all_out(channel_size, intern(name)),
         }
     }
 }
@@ -0,0 +1,103 @@
+use crate::model::parser::{Chip, Form, Symbol};
+use crate::Span;
+use std::fmt::{self, Display, Formatter};
+
+/// Which language the HDL files of a project are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// The HDL of the course, as its own tools accept it
+    #[default]
+    Strict,
+    /// The course HDL with the extensions of this simulator: `//!` directives, which include
+    /// other files and define text macros, see [`preprocess`](super::preprocess::preprocess),
+    /// chips with width parameters such as `CHIP Mux<N>`, used as parts such as `Mux<16>`, parts
+    /// with instance labels such as `low: Mux16(...)`, and numbers connected to inputs
+    Extended,
+}
+
+/// A feature of the HDL of this simulator which the course tools do not accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extension {
+    /// A chip with width parameters, or a part using one
+    Generic,
+    /// A part named with `label: Chip(...)`
+    Label,
+    /// A number connected to an input, such as `a=5`
+    Number,
+}
+
+impl Display for Extension {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Extension::Generic => "width parameters",
+            Extension::Label => "instance labels",
+            Extension::Number => "numeric constants",
+        })
+    }
+}
+
+impl Dialect {
+    /// Every use of an extension in the chip which the dialect does not accept, in order, with
+    /// where it is in the source. Directives are not listed, as only the extended dialect reads
+    /// them; the course tools take them for comments.
+    pub fn rejected<'a>(self, chip: &Chip<'a>) -> Vec<(Extension, Span<'a>)> {
+        if self == Dialect::Extended {
+            return Vec::new();
+        }
+        let mut rejected = Vec::new();
+        if chip.name.contains('<') {
+            rejected.push((Extension::Generic, chip.name));
+        }
+        if let Form::Native(connections) = &chip.logic {
+            for connection in connections {
+                if let Some(label) = connection.label {
+                    rejected.push((Extension::Label, label));
+                }
+                if connection.chip_name.contains('<') {
+                    rejected.push((Extension::Generic, connection.chip_name));
+                }
+                rejected.extend(
+                    connection
+                        .inputs
+                        .iter()
+                        .filter(|x| matches!(x.external, Symbol::Number(_)))
+                        .map(|x| (Extension::Number, x.internal)),
+                );
+            }
+        }
+        rejected
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::parser::create_chip;
+
+    #[test]
+    fn test_rejected() {
+        let source = "\
+CHIP Top {
+    IN a[16];
+    OUT out[16];
+    PARTS:
+    low: Mux<8>(a=a[0..7], b=5, sel=true, out=out[0..7]);
+    Not16(in=a, out=x);
+}";
+        let chip = create_chip(Span::new(source)).unwrap();
+        let rejected = Dialect::Strict
+            .rejected(&chip)
+            .into_iter()
+            .map(|(extension, span)| (extension, *span))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rejected,
+            [
+                (Extension::Label, "low"),
+                (Extension::Generic, "Mux<8>"),
+                (Extension::Number, "b"),
+            ]
+        );
+        assert!(Dialect::Extended.rejected(&chip).is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+//! How the interfaces of two chips differ, such as two revisions of the same file, for reviewers
+//! and graders who want to know whether a change keeps the pins which other chips and test
+//! scripts rely on.
+
+use crate::clock_behavior::ClockBehavior;
+use crate::error::Result;
+use crate::model::chip::error::ModelConstructionError;
+use crate::model::dialect::Dialect;
+use crate::model::parser::{create_chip, Direction, Interface};
+use crate::model::preprocess::preprocess;
+use crate::Span;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinChange {
+    Added {
+        name: String,
+        direction: Direction,
+        width: u16,
+    },
+    Removed {
+        name: String,
+        direction: Direction,
+        width: u16,
+    },
+    Width {
+        name: String,
+        before: u16,
+        after: u16,
+    },
+    Direction {
+        name: String,
+        before: Direction,
+        after: Direction,
+    },
+    Clocked {
+        name: String,
+        before: ClockBehavior,
+        after: ClockBehavior,
+    },
+}
+
+/// A pin as it is declared, such as `in[16]`
+fn declared(name: &str, width: u16) -> String {
+    match width {
+        1 => format!("`{name}`"),
+        _ => format!("`{name}[{width}]`"),
+    }
+}
+
+fn clocked(behavior: ClockBehavior) -> &'static str {
+    match behavior {
+        ClockBehavior::Combinatorial => "combinational",
+        ClockBehavior::Sequential => "clocked",
+    }
+}
+
+impl Display for PinChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PinChange::Added {
+                name,
+                direction,
+                width,
+            } => write!(f, "added {direction} {}", declared(name, *width)),
+            PinChange::Removed {
+                name,
+                direction,
+                width,
+            } => write!(f, "removed {direction} {}", declared(name, *width)),
+            PinChange::Width {
+                name,
+                before,
+                after,
+            } => write!(f, "changed `{name}` from {before} to {after} bits"),
+            PinChange::Direction {
+                name,
+                before,
+                after,
+            } => write!(f, "changed `{name}` from an {before} to an {after}"),
+            PinChange::Clocked {
+                name,
+                before,
+                after,
+            } => write!(
+                f,
+                "changed `{name}` from {} to {}",
+                clocked(*before),
+                clocked(*after)
+            ),
+        }
+    }
+}
+
+/// The changes which turn the pins of `before` into those of `after`: the pins which were
+/// removed, in the order of `before`, then those which changed and those which were added, in
+/// the order of `after`. A pin which changed in several ways is listed once for each. The names of
+/// the chips are not compared.
+pub fn diff_interfaces(before: &Interface, after: &Interface) -> Vec<PinChange> {
+    let mut changes = Vec::new();
+    for pin in before.pins() {
+        if !after.pins().any(|x| x.name == pin.name) {
+            changes.push(PinChange::Removed {
+                name: pin.name.to_string(),
+                direction: pin.direction,
+                width: pin.width,
+            });
+        }
+    }
+    for pin in after.pins() {
+        let name = pin.name.to_string();
+        let Some(old) = before.pins().find(|x| x.name == pin.name) else {
+            changes.push(PinChange::Added {
+                name,
+                direction: pin.direction,
+                width: pin.width,
+            });
+            continue;
+        };
+        if old.direction != pin.direction {
+            changes.push(PinChange::Direction {
+                name: name.clone(),
+                before: old.direction,
+                after: pin.direction,
+            });
+        }
+        if old.width != pin.width {
+            changes.push(PinChange::Width {
+                name: name.clone(),
+                before: old.width,
+                after: pin.width,
+            });
+        }
+        if old.clocked != pin.clocked {
+            changes.push(PinChange::Clocked {
+                name,
+                before: old.clocked,
+                after: pin.clocked,
+            });
+        }
+    }
+    changes
+}
+
+/// The interface of the chip in an HDL file
+pub fn read_interface(path: &Path, dialect: Dialect) -> Result<Interface> {
+    let source = fs::read_to_string(path)?;
+    let source = match dialect {
+        Dialect::Strict => source,
+        Dialect::Extended => preprocess(&source, path)?,
+    };
+    let chip = create_chip(Span::new(&source)).map_err(ModelConstructionError::syntax(&source))?;
+    Ok(chip.interface())
+}
+
+/// Compares the chips in two HDL files, see [`diff_interfaces`]
+pub fn diff_files(before: &Path, after: &Path, dialect: Dialect) -> Result<Vec<PinChange>> {
+    Ok(diff_interfaces(
+        &read_interface(before, dialect)?,
+        &read_interface(after, dialect)?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::parser::create_chip;
+    use crate::Span;
+
+    #[test]
+    fn test_diff() {
+        let interface = |source| create_chip(Span::new(source)).unwrap().interface();
+        let before = interface(
+            "CHIP Reg { IN in[16], load, reset; OUT out[16]; BUILTIN Register; CLOCKED in; }",
+        );
+        let after = interface(
+            "CHIP Reg { IN in[8], load, inc; OUT out[16], zr; BUILTIN Register; CLOCKED in, load; }",
+        );
+        let changes = diff_interfaces(&before, &after)
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                "removed input `reset`",
+                "changed `in` from 16 to 8 bits",
+                "changed `load` from combinational to clocked",
+                "added input `inc`",
+                "added output `zr`",
+            ]
+        );
+        assert!(diff_interfaces(&after, &after).is_empty());
+
+        let moved = interface("CHIP Reg { IN out[16]; OUT in[8]; BUILTIN Register; }");
+        assert_eq!(
+            diff_interfaces(&before, &moved)[2..],
+            [
+                PinChange::Direction {
+                    name: "out".to_string(),
+                    before: Direction::Out,
+                    after: Direction::In,
+                },
+                PinChange::Direction {
+                    name: "in".to_string(),
+                    before: Direction::In,
+                    after: Direction::Out,
+                },
+                PinChange::Width {
+                    name: "in".to_string(),
+                    before: 16,
+                    after: 8,
+                },
+                PinChange::Clocked {
+                    name: "in".to_string(),
+                    before: ClockBehavior::Sequential,
+                    after: ClockBehavior::Combinatorial,
+                },
+            ]
+        );
+        assert_eq!(
+            diff_interfaces(&moved, &before)[0].to_string(),
+            "changed `in` from an output to an input"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Chips with width parameters, an extension of the HDL. A chip declared as `CHIP Mux<N> { ... }`
+//! is a template: each part such as `Mux<16>` is a separate chip, made by replacing the
+//! parameters in its source with the arguments. Arithmetic inside bus declarations, bus ranges
+//! and arguments, as in `a[0..N-1]` or `Mux<N*2>`, is worked out after the replacement.
+
+use crate::model::parser::chip_header;
+use crate::model::preprocess::substitute;
+use crate::Span;
+use std::collections::HashMap;
+
+/// The name and width parameters of the chip in an HDL source, if it has any
+pub(crate) fn generic_params(source: &str) -> Option<(String, Vec<String>)> {
+    let (_, header) = chip_header(Span::new(source)).ok()?;
+    let (name, params) = split_arguments(&header)?;
+    Some((name.to_string(), params))
+}
+
+/// Splits a name such as `Mux<N, 2>` into `Mux` and its arguments
+pub(crate) fn split_arguments(name: &str) -> Option<(&str, Vec<String>)> {
+    let (base, rest) = name.split_once('<')?;
+    let arguments = rest.strip_suffix('>')?;
+    Some((
+        base,
+        arguments.split(',').map(|x| x.trim().to_string()).collect(),
+    ))
+}
+
+/// The source of the chip `name`, such as `Mux<16>`, made from the source of a generic chip.
+/// Returns `None` unless the name has a number for every parameter of the chip.
+pub(crate) fn specialize(source: &str, name: &str) -> Option<String> {
+    let (_, header) = chip_header(Span::new(source)).ok()?;
+    let (_, params) = split_arguments(&header)?;
+    let (_, arguments) = split_arguments(name)?;
+    if params.len() != arguments.len() || arguments.iter().any(|x| x.parse::<u16>().is_err()) {
+        return None;
+    }
+    let values = params.into_iter().zip(arguments).collect::<HashMap<_, _>>();
+    let (start, end) = (
+        header.location_offset(),
+        header.location_offset() + header.len(),
+    );
+    Some(format!(
+        "{}{name}{}",
+        &source[..start],
+        fold(&substitute(&source[end..], &values))
+    ))
+}
+
+/// Works out the arithmetic between brackets
+fn fold(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(['[', '<']) {
+        let (close, separator) = if rest[start..].starts_with('[') {
+            (']', "..")
+        } else {
+            ('>', ",")
+        };
+        output.push_str(&rest[..=start]);
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find(close) else {
+            break;
+        };
+        let pieces = rest[..end]
+            .split(separator)
+            .map(|x| evaluate(x).map_or(x.to_string(), |x| x.to_string()))
+            .collect::<Vec<_>>();
+        output.push_str(&pieces.join(separator));
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// The value of an expression of numbers, `+`, `-`, `*`, `/` and parentheses. Returns `None` if
+/// it is not such an expression, or its value would be negative or a division by zero.
+fn evaluate(expression: &str) -> Option<u64> {
+    let tokens = expression
+        .chars()
+        .filter(|x| !x.is_whitespace())
+        .collect::<Vec<_>>();
+    let mut position = 0;
+    let value = sum(&tokens, &mut position)?;
+    (position == tokens.len()).then_some(value)
+}
+
+fn sum(tokens: &[char], position: &mut usize) -> Option<u64> {
+    let mut value = product(tokens, position)?;
+    while let Some(op @ ('+' | '-')) = tokens.get(*position).copied() {
+        *position += 1;
+        let right = product(tokens, position)?;
+        value = match op {
+            '+' => value.checked_add(right)?,
+            _ => value.checked_sub(right)?,
+        };
+    }
+    Some(value)
+}
+
+fn product(tokens: &[char], position: &mut usize) -> Option<u64> {
+    let mut value = atom(tokens, position)?;
+    while let Some(op @ ('*' | '/')) = tokens.get(*position).copied() {
+        *position += 1;
+        let right = atom(tokens, position)?;
+        value = match op {
+            '*' => value.checked_mul(right)?,
+            _ => value.checked_div(right)?,
+        };
+    }
+    Some(value)
+}
+
+fn atom(tokens: &[char], position: &mut usize) -> Option<u64> {
+    if tokens.get(*position) == Some(&'(') {
+        *position += 1;
+        let value = sum(tokens, position)?;
+        (tokens.get(*position) == Some(&')')).then_some(())?;
+        *position += 1;
+        return Some(value);
+    }
+    let digits = tokens[*position..]
+        .iter()
+        .take_while(|x| x.is_ascii_digit())
+        .collect::<String>();
+    *position += digits.len();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate("16"), Some(16));
+        assert_eq!(evaluate("2 * (8 - 1) + 6 / 3"), Some(16));
+        assert_eq!(evaluate("1 - 2"), None);
+        assert_eq!(evaluate("4 / 0"), None);
+        assert_eq!(evaluate("in"), None);
+        assert_eq!(evaluate("(1"), None);
+    }
+
+    #[test]
+    fn test_specialize() {
+        let source = "\
+// Selects between buses of N bits
+CHIP Mux<N> {
+    IN a[N], b[N], sel;
+    OUT out[N];
+    PARTS:
+    Half<N / 2>(a=a[0..N/2-1], b=b[0..N/2-1], sel=sel, out=out[0..N/2-1]);
+}";
+        assert_eq!(
+            generic_params(source),
+            Some(("Mux".to_string(), vec!["N".to_string()]))
+        );
+        assert_eq!(
+            specialize(source, "Mux<8>").unwrap(),
+            "\
+// Selects between buses of N bits
+CHIP Mux<8> {
+    IN a[8], b[8], sel;
+    OUT out[8];
+    PARTS:
+    Half<4>(a=a[0..3], b=b[0..3], sel=sel, out=out[0..3]);
+}"
+        );
+        assert_eq!(specialize(source, "Mux<8, 2>"), None);
+        assert_eq!(specialize(source, "Mux<M>"), None);
+        assert_eq!(generic_params("CHIP Mux { IN a; }"), None);
+    }
+}
@@ -1,2 +1,16 @@
 pub mod chip;
-mod parser;
+pub mod dialect;
+pub mod diff;
+mod generic;
+pub(crate) mod parser;
+pub mod preprocess;
+
+pub use parser::error::HdlParseError;
+pub use parser::tokens::{tokenize, Token, TokenKind, Tokens};
+pub use parser::{Direction, Interface, Pin};
+
+/// The syntax tree of an HDL chip, and traits for traversing and rewriting it
+pub mod ast {
+    pub use super::parser::visit::*;
+    pub use super::parser::{Argument, Builtin, Channel, Chip, Connection, Form, Symbol, Value};
+}
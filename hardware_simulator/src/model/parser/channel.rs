@@ -1,7 +1,8 @@
 use super::symbols::*;
 use super::*;
+use nom::branch::alt;
 use nom::character::complete::char;
-use nom::combinator::{complete, opt};
+use nom::combinator::{complete, not, opt, peek};
 use nom::multi::many0;
 use nom::sequence::{delimited, tuple};
 use nom::Parser;
@@ -22,9 +23,17 @@ fn channel_declaration(arg: Span) -> PResult<Channel> {
             Channel {
                 name,
                 size: Some(convert_num(size)?),
+                comments: Vec::new(),
+            },
+        )),
+        None => Ok((
+            remainder,
+            Channel {
+                name,
+                size: None,
+                comments: Vec::new(),
             },
         )),
-        None => Ok((remainder, Channel { name, size: None })),
     }
 }
 
@@ -40,8 +49,10 @@ pub fn in_pin_decl(arg: Span) -> PResult<Vec<Channel>> {
     headed_pin_decl("IN").parse(arg)
 }
 
+/// The outputs of a chip, which may have none, like the `Computer` of the course
 pub fn out_pin_decl(arg: Span) -> PResult<Vec<Channel>> {
-    headed_pin_decl("OUT").parse(arg)
+    let none = peek(not(spaced(tag("OUT")))).map(|_| Vec::new());
+    alt((headed_pin_decl("OUT"), none)).parse(arg)
 }
 
 #[cfg(test)]
@@ -59,7 +70,7 @@ mod test {
                     }
                     _ => panic!("{test:?}"),
                 },
-                Err(_) => assert!(matches!(test, Err(_))),
+                Err(_) => assert!(test.is_err()),
             }
         }
 
@@ -93,7 +104,7 @@ mod test {
         }
         {
             let res = channel_declaration(Span::from("in[abc]"));
-            assert!(matches!(res, Err(_)))
+            assert!(res.is_err())
         }
     }
 
@@ -105,7 +116,7 @@ mod test {
             let exp = [("a", Some(1)), ("b", None), ("c", Some(32))];
             res.1
                 .into_iter()
-                .zip(exp.into_iter())
+                .zip(exp)
                 .for_each(|(test, exp)| check_pin_decl(test, exp))
         }
         {
@@ -114,7 +125,7 @@ mod test {
             let exp = [("a", Some(16)), ("b", Some(16))];
             res.1
                 .into_iter()
-                .zip(exp.into_iter())
+                .zip(exp)
                 .for_each(|(test, exp)| check_pin_decl(test, exp))
         }
     }
@@ -1,15 +1,17 @@
-use super::connection::connection;
 use super::channel::{in_pin_decl, out_pin_decl};
-use super::symbols::{name, spaced};
+use super::connection::connection;
+use super::symbols::{chip_name, generic_space0, name, spaced};
+use super::tokens::{tokenize, TokenKind};
 use super::{Builtin, Chip, Connection, Form, PResult, Span};
+use crate::model::parser::error::HdlParseError;
+use nom::branch::alt;
 use nom::character::complete::char;
-use nom::combinator::opt;
+use nom::combinator::{opt, peek};
 use nom::multi::{many1, separated_list0};
 use nom::sequence::{delimited, preceded, terminated, tuple};
-use nom::Parser;
+use nom::{Parser, Slice};
 use nom_supreme::error::{BaseErrorKind, ErrorTree};
 use nom_supreme::tag::complete::tag;
-use crate::model::parser::error::HdlParseError;
 
 fn builtin(arg: Span) -> PResult<Builtin> {
     let (remainder, (name, clocked)) = tuple((
@@ -24,8 +26,10 @@ fn builtin(arg: Span) -> PResult<Builtin> {
     Ok((remainder, Builtin { name, clocked }))
 }
 
+/// The parts of a chip, which may be none at all, as in the files the course starts each chip from
 fn native(arg: Span) -> PResult<Vec<Connection>> {
-    spaced(preceded(tag("PARTS:"), many1(connection)))(arg)
+    let empty = peek(spaced(char('}'))).map(|_| Vec::new());
+    spaced(preceded(tag("PARTS:"), alt((many1(connection), empty))))(arg)
 }
 
 fn implementation(arg: Span) -> PResult<Form> {
@@ -36,6 +40,9 @@ fn implementation(arg: Span) -> PResult<Form> {
         Ok((remainder, Form::Builtin(answer)))
     } else if let Ok((remainder, answer)) = native {
         Ok((remainder, Form::Native(answer)))
+    } else if arg.trim_start().starts_with("PARTS:") {
+        // the mistake is in the parts, which the error of the parts points at
+        native.map(|(remainder, answer)| (remainder, Form::Native(answer)))
     } else {
         Err(nom::Err::Error(ErrorTree::Base {
             location: arg,
@@ -46,28 +53,96 @@ fn implementation(arg: Span) -> PResult<Form> {
 
 pub fn chip(arg: Span) -> PResult<Chip> {
     let (remainder, (name, (in_pins, out_pins, logic))) =
-        delimited(spaced(tag("CHIP")), name, spaced(tag("{")))
+        delimited(spaced(tag("CHIP")), chip_name, spaced(tag("{")))
             .and(terminated(
                 tuple((in_pin_decl, out_pin_decl, implementation)),
                 spaced(tag("}")),
             ))
             .parse(arg)?;
 
-    Ok((
-        remainder,
-        Chip {
-            name,
-            in_pins,
-            out_pins,
-            logic,
-        },
-    ))
+    let mut chip = Chip {
+        name,
+        in_pins,
+        out_pins,
+        logic,
+        comments: Vec::new(),
+    };
+    attach_comments(
+        arg.slice(..remainder.location_offset() - arg.location_offset()),
+        &mut chip,
+    );
+    Ok((remainder, chip))
+}
+
+/// Hands each comment in the source of a chip to the pin or part next to it: a comment after code
+/// on the same line belongs to the node before it, and any other comment to the node after it.
+/// Comments with no such node, like those before the header, belong to the chip.
+fn attach_comments<'a>(source: Span<'a>, chip: &mut Chip<'a>) {
+    let text = *source.fragment();
+    let start = |x: Span| x.location_offset() - source.location_offset();
+    let mut nodes = chip
+        .in_pins
+        .iter()
+        .chain(&chip.out_pins)
+        .map(|x| start(x.name))
+        .collect::<Vec<_>>();
+    if let Form::Native(connections) = &chip.logic {
+        nodes.extend(
+            connections
+                .iter()
+                .map(|x| start(x.label.unwrap_or(x.chip_name))),
+        );
+    }
+
+    let mut attached = Vec::new();
+    for token in tokenize(text).filter(|x| x.kind == TokenKind::Comment) {
+        let line = text[..token.range.start].rfind('\n').map_or(0, |x| x + 1);
+        let node = if token.range.start < start(chip.name) {
+            None
+        } else if text[line..token.range.start].trim().is_empty() {
+            nodes.iter().position(|&x| x > token.range.start)
+        } else {
+            nodes.iter().rposition(|&x| x < token.range.start)
+        };
+        attached.push((node, source.slice(token.range)));
+    }
+
+    let pins = chip.in_pins.len() + chip.out_pins.len();
+    for (node, comment) in attached {
+        let comments = match (node, &mut chip.logic) {
+            (Some(i), _) if i < chip.in_pins.len() => &mut chip.in_pins[i].comments,
+            (Some(i), _) if i < pins => &mut chip.out_pins[i - chip.in_pins.len()].comments,
+            (Some(i), Form::Native(connections)) => &mut connections[i - pins].comments,
+            _ => &mut chip.comments,
+        };
+        comments.push(comment);
+    }
+}
+
+/// The name of the chip in the source of an HDL file, including any width parameters
+pub fn chip_header(arg: Span) -> PResult<Span> {
+    preceded(spaced(tag("CHIP")), chip_name)(arg)
 }
 
 pub fn create_chip(arg: Span) -> Result<Chip, nom::Err<ErrorTree<Span>>> {
     Ok(chip(arg)?.1)
 }
 
+/// Every chip in the source, one after another, which must hold at least one and nothing else
+pub fn create_chips(arg: Span) -> Result<Vec<Chip>, nom::Err<ErrorTree<Span>>> {
+    let mut chips = Vec::new();
+    let mut rest = arg;
+    // each chip is parsed in turn so that a mistake in any of them is where the error points
+    loop {
+        let (remainder, next) = chip(rest)?;
+        chips.push(next);
+        rest = generic_space0(remainder)?.0;
+        if rest.is_empty() {
+            return Ok(chips);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -82,7 +157,7 @@ mod test {
             assert_eq!(*remainder, "}");
             assert_eq!(*name, "DFF");
 
-            assert!(matches!(clocked, Some(_)));
+            assert!(clocked.is_some());
             if let Some(clocked) = clocked {
                 assert_eq!(*(clocked[0]), "in");
             }
@@ -93,7 +168,7 @@ mod test {
             assert_eq!(*remainder, "");
             assert_eq!(*name, "DFF");
 
-            assert!(matches!(clocked, Some(_)));
+            assert!(clocked.is_some());
             if let Some(clocked) = clocked {
                 assert_eq!(*(clocked[0]), "in");
                 assert_eq!(*(clocked[1]), "out");
@@ -105,7 +180,7 @@ mod test {
             assert_eq!(*remainder, "}");
             assert_eq!(*name, "DFF");
 
-            assert!(matches!(clocked, None));
+            assert!(clocked.is_none());
         }
     }
 
@@ -128,7 +203,9 @@ mod test {
             let checks = [
                 // checking Xor(a=a, b=b, out=neq1);
                 |conn: Connection| {
-                    let Connection { chip_name, inputs } = conn;
+                    let Connection {
+                        chip_name, inputs, ..
+                    } = conn;
                     assert_eq!(*chip_name, "Xor");
                     assert_eq!(inputs.len(), 3);
 
@@ -141,12 +218,12 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "a");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "a")
                             }
-                            assert!(matches!(external_bus, None));
+                            assert!(external_bus.is_none());
                         },
                         |arg: Argument| {
                             let Argument {
@@ -156,12 +233,12 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "b");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "b")
                             }
-                            assert!(matches!(external_bus, None))
+                            assert!(external_bus.is_none())
                         },
                         |arg: Argument| {
                             let Argument {
@@ -171,23 +248,25 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "out");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "neq1")
                             }
-                            assert!(matches!(external_bus, None))
+                            assert!(external_bus.is_none())
                         },
                     ];
 
                     checks
                         .into_iter()
-                        .zip(inputs.into_iter())
+                        .zip(inputs)
                         .for_each(|(check, input)| check(input));
                 },
                 // checking Xor(a=b, b=c, out=neq2);
                 |conn: Connection| {
-                    let Connection { chip_name, inputs } = conn;
+                    let Connection {
+                        chip_name, inputs, ..
+                    } = conn;
                     assert_eq!(*chip_name, "Xor");
                     assert_eq!(inputs.len(), 3);
 
@@ -200,12 +279,12 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "a");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "b")
                             }
-                            assert!(matches!(external_bus, None));
+                            assert!(external_bus.is_none());
                         },
                         |arg: Argument| {
                             let Argument {
@@ -215,12 +294,12 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "b");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "c")
                             }
-                            assert!(matches!(external_bus, None))
+                            assert!(external_bus.is_none())
                         },
                         |arg: Argument| {
                             let Argument {
@@ -230,23 +309,25 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "out");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "neq2")
                             }
-                            assert!(matches!(external_bus, None))
+                            assert!(external_bus.is_none())
                         },
                     ];
 
                     checks
                         .into_iter()
-                        .zip(inputs.into_iter())
+                        .zip(inputs)
                         .for_each(|(check, input)| check(input));
                 },
                 // checking Or(a=neq1, b=neq2, out=outOr);
                 |conn: Connection| {
-                    let Connection { chip_name, inputs } = conn;
+                    let Connection {
+                        chip_name, inputs, ..
+                    } = conn;
                     assert_eq!(*chip_name, "Or");
                     assert_eq!(inputs.len(), 3);
 
@@ -259,12 +340,12 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "a");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "neq1")
                             }
-                            assert!(matches!(external_bus, None));
+                            assert!(external_bus.is_none());
                         },
                         |arg: Argument| {
                             let Argument {
@@ -274,12 +355,12 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "b");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "neq2")
                             }
-                            assert!(matches!(external_bus, None))
+                            assert!(external_bus.is_none())
                         },
                         |arg: Argument| {
                             let Argument {
@@ -289,23 +370,25 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "out");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "outOr")
                             }
-                            assert!(matches!(external_bus, None))
+                            assert!(external_bus.is_none())
                         },
                     ];
 
                     checks
                         .into_iter()
-                        .zip(inputs.into_iter())
+                        .zip(inputs)
                         .for_each(|(check, input)| check(input));
                 },
                 // checking Not(in=outOr, out=out);
                 |conn: Connection| {
-                    let Connection { chip_name, inputs } = conn;
+                    let Connection {
+                        chip_name, inputs, ..
+                    } = conn;
                     assert_eq!(*chip_name, "Not");
                     assert_eq!(inputs.len(), 2);
 
@@ -318,12 +401,12 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "in");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "outOr")
                             }
-                            assert!(matches!(external_bus, None));
+                            assert!(external_bus.is_none());
                         },
                         |arg: Argument| {
                             let Argument {
@@ -333,25 +416,25 @@ mod test {
                                 external_bus,
                             } = arg;
                             assert_eq!(*internal, "out");
-                            assert!(matches!(internal_bus, None));
+                            assert!(internal_bus.is_none());
                             assert!(matches!(external, Symbol::Name(_)));
                             if let Symbol::Name(external) = external {
                                 assert_eq!(*external, "out")
                             }
-                            assert!(matches!(external_bus, None))
+                            assert!(external_bus.is_none())
                         },
                     ];
 
                     checks
                         .into_iter()
-                        .zip(inputs.into_iter())
+                        .zip(inputs)
                         .for_each(|(check, input)| check(input));
                 },
             ];
 
             checks
                 .into_iter()
-                .zip(connections.into_iter())
+                .zip(connections)
                 .for_each(|(check, connection)| check(connection));
         }
     }
@@ -360,6 +443,84 @@ mod test {
     fn test_chip_parser_success() {
         let res = chip(Span::new(include_str!("../../../../test_files/And16.hdl")));
         println!("{res:#?}");
-        assert!(matches!(res, Ok(_)))
+        assert!(res.is_ok())
+    }
+
+    #[test]
+    fn test_stub() {
+        let (_, stub) = chip(Span::new(include_str!("../../../../test_files/Xor.hdl"))).unwrap();
+        assert!(matches!(&stub.logic, Form::Native(parts) if parts.is_empty()));
+
+        // a chip without outputs, like the course `Computer`
+        let (_, computer) = chip(Span::new(
+            "CHIP Computer { IN reset; PARTS: // Put your code here:\n}",
+        ))
+        .unwrap();
+        assert!(computer.out_pins.is_empty());
+        assert!(chip(Span::new("CHIP Bad { IN a; OUT out[; PARTS: }")).is_err());
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "// Or of two bits
+/* by De Morgan */
+CHIP Or {
+    IN a, // the first bit
+       b;
+    OUT out;
+
+    PARTS:
+    // negate both
+    Not(in=a, out=na);
+    Not(in=b, out=nb); // and
+    Nand(a=na, b=nb, out=out);
+    // nothing after this
+}";
+        let chip = create_chip(Span::new(source)).unwrap();
+        let text = |comments: &[Span<'static>]| comments.iter().map(|x| **x).collect::<Vec<_>>();
+        assert_eq!(
+            text(&chip.comments),
+            [
+                "// Or of two bits",
+                "/* by De Morgan */",
+                "// nothing after this"
+            ]
+        );
+        assert_eq!(text(&chip.in_pins[0].comments), ["// the first bit"]);
+        assert!(chip.in_pins[1].comments.is_empty());
+        let Form::Native(connections) = chip.logic else {
+            panic!("Or is not builtin");
+        };
+        assert_eq!(text(&connections[0].comments), ["// negate both"]);
+        assert_eq!(text(&connections[1].comments), ["// and"]);
+        assert!(connections[2].comments.is_empty());
+    }
+    #[test]
+    fn test_doc() {
+        let source = "\
+// not the documentation
+/** also not, since another follows */
+/**
+ * Negates its input:
+ *   out = not in
+ */
+CHIP Not {
+    /** nor this, which belongs to a pin */
+    IN in;
+    OUT out;
+    PARTS:
+    Nand(a=in, b=in, out=out);
+}";
+        let chip = create_chip(Span::new(source)).unwrap();
+        assert_eq!(
+            chip.doc().as_deref(),
+            Some("Negates its input:\n  out = not in")
+        );
+        let chip = create_chip(Span::new(
+            "/** One line */ CHIP A { IN a; OUT b; BUILTIN A; }",
+        ));
+        assert_eq!(chip.unwrap().doc().as_deref(), Some("One line"));
+        let chip = create_chip(Span::new("CHIP A { IN a; OUT b; BUILTIN A; }"));
+        assert_eq!(chip.unwrap().doc(), None);
     }
 }
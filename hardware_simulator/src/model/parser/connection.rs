@@ -1,17 +1,19 @@
-use super::symbols::{convert_num, generic_space0, name, skip_comma, spaced, symbol};
+use super::symbols::{
+    chip_name, convert_num, failure, generic_space0, name, skip_comma, spaced, symbol,
+};
 use crate::bus_range::BusRange;
+use crate::model::parser::error::HdlParseError;
 use nom::branch::alt;
 use nom::bytes::complete::is_not;
 use nom::character::complete::digit1;
 use nom::character::streaming::char;
 use nom::combinator::{complete, opt};
 use nom::multi::many0;
-use nom::sequence::{delimited, separated_pair, tuple};
+use nom::sequence::{delimited, separated_pair, terminated, tuple};
 use nom::IResult;
 use nom::Parser;
 use nom_supreme::error::BaseErrorKind;
 use nom_supreme::tag::complete::tag;
-use crate::model::parser::error::HdlParseError;
 
 use super::*;
 
@@ -23,9 +25,14 @@ fn bus_range(arg: Span) -> PResult<BusRange> {
         )))
         .parse(arg)?;
 
-    let (start, end) = (convert_num(start)?, convert_num(end)?);
+    let range = BusRange::new(convert_num(start)?, convert_num(end)?).ok_or_else(|| {
+        nom::Err::Error(ErrorTree::Base {
+            location: start,
+            kind: BaseErrorKind::External(Box::new(HdlParseError::BadBusRange)),
+        })
+    })?;
 
-    Ok((remainder, BusRange { start, end }))
+    Ok((remainder, range))
 }
 
 fn symbol_bus(arg: Span) -> PResult<(Span, Option<BusRange>)> {
@@ -40,13 +47,13 @@ fn single_arg(arg: Span) -> PResult<Argument> {
 
     let (remainder, _) = skip_comma(remainder)?;
 
-    let external = match Symbol::try_from(external) {
-        Ok(sym) => Ok(sym),
-        Err(sp) => Err(nom::Err::Error(ErrorTree::Base {
-            location: sp,
-            kind: BaseErrorKind::External(Box::new(HdlParseError::BadSymbol)),
-        })),
-    }?;
+    // the pin of the part must be a name, while what it is connected to may be any symbol
+    match Symbol::try_from(internal) {
+        Ok(Symbol::Name(_)) => {}
+        Ok(_) => return Err(failure(internal, HdlParseError::BadName)),
+        Err(e) => return Err(failure(internal, e)),
+    }
+    let external = Symbol::try_from(external).map_err(|e| failure(external, e))?;
 
     IResult::Ok((
         remainder,
@@ -63,14 +70,21 @@ fn args(arg: Span) -> PResult<Vec<Argument>> {
     delimited(char('('), many0(single_arg), char(')'))(arg)
 }
 
+fn label(arg: Span) -> PResult<Span> {
+    terminated(name, spaced(char(':')))(arg)
+}
+
 pub fn connection(arg: Span) -> PResult<Connection> {
-    let (remainder, (name, args, ..)) = tuple((name, args, spaced(char(';')))).parse(arg)?;
+    let (remainder, (label, name, args, ..)) =
+        tuple((opt(label), chip_name, args, spaced(char(';')))).parse(arg)?;
 
     Ok((
         remainder,
         Connection {
+            label,
             chip_name: name,
             inputs: args,
+            comments: Vec::new(),
         },
     ))
 }
@@ -89,29 +103,30 @@ mod test {
         test(
             bus_range(Span::from("[0..1]")).unwrap(),
             "",
-            BusRange { start: 0, end: 1 },
+            BusRange::new(0, 1).unwrap(),
         );
         test(
             bus_range(Span::from("[5..10]")).unwrap(),
             "",
-            BusRange { start: 5, end: 10 },
+            BusRange::new(5, 10).unwrap(),
         );
         test(
             bus_range(Span::from("[5..10] and")).unwrap(),
             "and",
-            BusRange { start: 5, end: 10 },
+            BusRange::new(5, 10).unwrap(),
         );
         test(
             bus_range(Span::from("[   5   ..  10       ] and")).unwrap(),
             "and",
-            BusRange { start: 5, end: 10 },
+            BusRange::new(5, 10).unwrap(),
         );
         test(
             bus_range(Span::from("[   5\n   ..  10       ] and")).unwrap(),
             "and",
-            BusRange { start: 5, end: 10 },
+            BusRange::new(5, 10).unwrap(),
         );
-        assert!(matches!(bus_range(Span::from("[ a..b]")), Err(_)));
+        assert!(bus_range(Span::from("[ a..b]")).is_err());
+        assert!(bus_range(Span::from("[10..5]")).is_err());
     }
 
     #[test]
@@ -124,11 +139,11 @@ mod test {
 
         test(
             symbol_bus(Span::from("limo[1..10]")).unwrap(),
-            Some(BusRange { start: 1, end: 10 }),
+            Some(BusRange::new(1, 10).unwrap()),
         );
         test(
             symbol_bus(Span::from("limo   [  1  .. 10  ]")).unwrap(),
-            Some(BusRange { start: 1, end: 10 }),
+            Some(BusRange::new(1, 10).unwrap()),
         );
         test(symbol_bus(Span::from("limo   ")).unwrap(), None);
         test(symbol_bus(Span::from("limo")).unwrap(), None);
@@ -188,12 +203,12 @@ mod test {
         test_2(
             single_arg(Span::from("in[3..4]=true)")).unwrap(),
             ")",
-            Some(BusRange { start: 3, end: 4 }),
+            Some(BusRange::new(3, 4).unwrap()),
         );
         test_2(
             single_arg(Span::from("in[3]=true)")).unwrap(),
             ")",
-            Some(BusRange { start: 3, end: 3 }),
+            Some(BusRange::new(3, 3).unwrap()),
         );
 
         let test_3 = |res: (Span, Argument), excess, in_bus, ex_bus, int, ext| {
@@ -219,16 +234,16 @@ mod test {
         test_3(
             single_arg(Span::from("in[3]=out[4])")).unwrap(),
             ")",
-            Some(BusRange { start: 3, end: 3 }),
-            Some(BusRange { start: 4, end: 4 }),
+            Some(BusRange::new(3, 3).unwrap()),
+            Some(BusRange::new(4, 4).unwrap()),
             "in",
             "out",
         );
         test_3(
             single_arg(Span::from("a[9..10]=b[5..10]")).unwrap(),
             "",
-            Some(BusRange { start: 9, end: 10 }),
-            Some(BusRange { start: 5, end: 10 }),
+            Some(BusRange::new(9, 10).unwrap()),
+            Some(BusRange::new(5, 10).unwrap()),
             "a",
             "b",
         );
@@ -243,7 +258,7 @@ mod test {
                 external,
                 external_bus,
             } = res.1;
-            assert_eq!(internal_bus, Some(BusRange { start: 3, end: 4 }));
+            assert_eq!(internal_bus, Some(BusRange::new(3, 4).unwrap()));
             assert_eq!(external_bus, None);
 
             assert_eq!(*internal, "in");
@@ -255,6 +270,27 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_bad_symbol() {
+        let error = |source| match single_arg(Span::from(source)) {
+            Err(nom::Err::Failure(ErrorTree::Base {
+                location,
+                kind: BaseErrorKind::External(e),
+            })) => (location.location_offset(), e.to_string()),
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(
+            error("in=123456789012345678901234567890"),
+            (3, HdlParseError::NumberOverflow.to_string())
+        );
+        assert_eq!(error("true=out"), (0, HdlParseError::BadName.to_string()));
+        // the error is not lost among the other arguments
+        assert!(matches!(
+            args(Span::from("(a=b, 2=c)")),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+
     #[test]
     fn test_parse_args() {
         let res = args(Span::from("(in=ax, out=bruh)")).unwrap();
@@ -293,7 +329,7 @@ mod test {
         }
 
         let err = args(Span::from("(in=a,"));
-        assert!(matches!(err, Err(_)))
+        assert!(err.is_err())
     }
 
     #[test]
@@ -305,8 +341,14 @@ mod test {
 
         assert_eq!(*res.0, "");
 
-        let Connection { chip_name, inputs } = res.1;
+        let Connection {
+            label,
+            chip_name,
+            inputs,
+            ..
+        } = res.1;
 
+        assert_eq!(label, None);
         assert_eq!(*chip_name, "Nand");
 
         let Argument {
@@ -315,7 +357,7 @@ mod test {
             external,
             external_bus,
         } = &inputs[0];
-        assert_eq!(internal_bus, &Some(BusRange { start: 3, end: 4 }));
+        assert_eq!(internal_bus, &Some(BusRange::new(3, 4).unwrap()));
         assert_eq!(external_bus, &None);
 
         assert_eq!(**internal, "a");
@@ -331,7 +373,7 @@ mod test {
             external,
             external_bus,
         } = &inputs[1];
-        assert_eq!(internal_bus, &Some(BusRange { start: 1, end: 10 }));
+        assert_eq!(internal_bus, &Some(BusRange::new(1, 10).unwrap()));
         assert_eq!(external_bus, &None);
 
         assert_eq!(**internal, "b");
@@ -348,7 +390,7 @@ mod test {
             external_bus,
         } = &inputs[2];
         assert_eq!(internal_bus, &None);
-        assert_eq!(external_bus, &Some(BusRange { start: 6, end: 9 }));
+        assert_eq!(external_bus, &Some(BusRange::new(6, 9).unwrap()));
 
         assert_eq!(**internal, "out");
 
@@ -357,4 +399,30 @@ mod test {
             assert_eq!(*x, "foo");
         }
     }
+
+    #[test]
+    fn test_parse_labelled_connection() {
+        let (
+            remainder,
+            Connection {
+                label, chip_name, ..
+            },
+        ) = connection(Span::from("left : Mux16(a=a, b=b, sel=sel, out=out);")).unwrap();
+        assert_eq!(*remainder, "");
+        assert_eq!(label.map(|x| *x), Some("left"));
+        assert_eq!(*chip_name, "Mux16");
+    }
+
+    #[test]
+    fn test_display_connection() {
+        let text = |source| connection(Span::from(source)).unwrap().1.to_string();
+        assert_eq!(
+            text("  Nand (a\n[3\n..4]    =\n2, b[1]=  false, out=foo[6  .. 9]) ;"),
+            "Nand(a[3..4]=2, b[1]=false, out=foo[6..9])"
+        );
+        assert_eq!(
+            text("left : Mux16(a=a, b=b, sel=sel, out=out);"),
+            "left: Mux16(a=a, b=b, sel=sel, out=out)"
+        );
+    }
 }
@@ -1,3 +1,6 @@
+use crate::Span;
+use nom_supreme::error::{BaseErrorKind, ErrorTree, StackContext};
+use std::ops::Range;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -12,4 +15,122 @@ pub enum HdlParseError {
     NumberError,
     #[error("Could not deduce a given implementation")]
     BadImplementation,
+    #[error("The start of a bus range is after its end")]
+    BadBusRange,
+}
+
+impl HdlParseError {
+    /// A code which stays the same for each kind of error, see [`crate::error`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            HdlParseError::BadSymbol => "E0201",
+            HdlParseError::BadName => "E0202",
+            HdlParseError::NumberOverflow => "E0203",
+            HdlParseError::NumberError => "E0204",
+            HdlParseError::BadImplementation => "E0205",
+            HdlParseError::BadBusRange => "E0206",
+        }
+    }
+}
+
+/// Where HDL stops making sense to the parser, which is the place it got furthest, and what it
+/// expected there
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{message}")]
+pub struct SyntaxError {
+    pub message: String,
+    /// The bytes of the source which could not be parsed
+    pub range: Range<usize>,
+    /// The constructs the parser was in at the time, such as a part, and where they start
+    pub contexts: Vec<(String, Range<usize>)>,
+}
+
+impl SyntaxError {
+    /// Describes an error of the parser of `source`
+    pub fn new(error: &nom::Err<ErrorTree<Span>>, source: &str) -> Self {
+        let (nom::Err::Error(error) | nom::Err::Failure(error)) = error else {
+            return Self {
+                message: "unexpected end of file".to_string(),
+                range: source.len()..source.len(),
+                contexts: Vec::new(),
+            };
+        };
+        let mut furthest = Vec::new();
+        let mut contexts = Vec::new();
+        collect_furthest(error, &mut furthest, &mut contexts);
+        let offset = furthest.first().map_or(0, |(offset, _)| *offset);
+        let mut expected = Vec::new();
+        for message in furthest.into_iter().filter_map(|(_, message)| message) {
+            if !expected.contains(&message) {
+                expected.push(message);
+            }
+        }
+        let range = offset..offset + token_len(&source[offset..]);
+        let message = match (expected.is_empty(), &source[range.clone()]) {
+            (false, _) => expected.join(" or "),
+            (true, "") => "unexpected end of file".to_string(),
+            (true, token) => format!("unexpected `{token}`"),
+        };
+        let contexts = contexts
+            .into_iter()
+            .map(|(location, message)| {
+                let offset = location.location_offset();
+                (message, offset..offset + token_len(&location))
+            })
+            .collect();
+        Self {
+            message,
+            range,
+            contexts,
+        }
+    }
+}
+
+fn collect_furthest<'a>(
+    error: &ErrorTree<Span<'a>>,
+    furthest: &mut Vec<(usize, Option<String>)>,
+    contexts: &mut Vec<(Span<'a>, String)>,
+) {
+    match error {
+        ErrorTree::Base { location, kind } => {
+            let offset = location.location_offset();
+            match furthest.first() {
+                Some((best, _)) if *best > offset => return,
+                Some((best, _)) if *best < offset => furthest.clear(),
+                _ => {}
+            }
+            // the kinds of nom parsers mean nothing to the author of the file
+            let message = match kind {
+                BaseErrorKind::External(e) => Some(e.to_string()),
+                BaseErrorKind::Expected(_) => Some(kind.to_string()),
+                BaseErrorKind::Kind(_) => None,
+            };
+            furthest.push((offset, message));
+        }
+        ErrorTree::Stack {
+            base,
+            contexts: stack,
+        } => {
+            collect_furthest(base, furthest, contexts);
+            contexts.extend(stack.iter().filter_map(|(x, context)| match context {
+                StackContext::Context(context) => Some((*x, context.to_string())),
+                StackContext::Kind(_) => None,
+            }));
+        }
+        ErrorTree::Alt(siblings) => {
+            for sibling in siblings {
+                collect_furthest(sibling, furthest, contexts);
+            }
+        }
+    }
+}
+
+/// The length of the word at the start of the text, or of its first character if it is not a
+/// word
+fn token_len(text: &str) -> usize {
+    match text.find(|x: char| !(x.is_alphanumeric() || x == '_')) {
+        Some(0) => text.chars().next().map_or(0, char::len_utf8),
+        Some(end) => end,
+        None => text.len(),
+    }
 }
@@ -1,37 +1,108 @@
-use super::{Builtin, Chip, Form, Channel};
+use super::{Builtin, Channel, Chip, Form};
 use crate::bus_range::BusRange;
+use crate::clock_behavior::ClockBehavior;
+use crate::intern::intern;
 use crate::Span;
 use std::collections::HashMap;
-use crate::clock_behavior::ClockBehavior;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 
-type PinMap = HashMap<String, BusRange>;
+pub(crate) type PinMap = HashMap<Arc<str>, BusRange>;
 
 #[derive(PartialEq, Debug, Clone, Default)]
+/// The pins of a chip. Names are [interned](crate::intern), so clones are cheap.
 pub struct Interface {
-    pub name: String,
+    pub name: Arc<str>,
     pub com_in: PinMap,
     pub com_out: PinMap,
     pub seq_in: PinMap,
     pub seq_out: PinMap,
+    /// The names of every pin in declaration order, inputs first
+    pub order: Vec<Arc<str>>,
+    /// The documentation of the chip, see [`Chip::doc`]
+    pub doc: Option<Arc<str>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::In => write!(f, "input"),
+            Direction::Out => write!(f, "output"),
+        }
+    }
+}
+
+/// A single pin as seen from outside of a chip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin<'a> {
+    pub name: &'a str,
+    pub range: &'a BusRange,
+    pub width: u16,
+    pub direction: Direction,
+    pub clocked: ClockBehavior,
+}
+
+impl Display for Interface {
+    /// The header of the chip as it would be declared in HDL, such as
+    /// `CHIP DFF { IN in; OUT out; CLOCKED in; }`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let declare = |pins: &mut dyn Iterator<Item = Pin>| {
+            pins.map(|pin| match pin.width {
+                1 => pin.name.to_string(),
+                width => format!("{}[{width}]", pin.name),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+        };
+        write!(f, "CHIP {} {{ ", self.name)?;
+        let inputs = declare(&mut self.inputs());
+        if !inputs.is_empty() {
+            write!(f, "IN {inputs}; ")?;
+        }
+        let outputs = declare(&mut self.outputs());
+        if !outputs.is_empty() {
+            write!(f, "OUT {outputs}; ")?;
+        }
+        let clocked = self
+            .pins()
+            .filter(|pin| pin.clocked == ClockBehavior::Sequential)
+            .map(|pin| pin.name)
+            .collect::<Vec<_>>();
+        if !clocked.is_empty() {
+            write!(f, "CLOCKED {}; ", clocked.join(", "))?;
+        }
+        write!(f, "}}")
+    }
 }
 
 fn to_map(pins: Vec<Channel>, mut next: u16) -> (PinMap, u16) {
     let map = pins
         .into_iter()
-        .map(|Channel { name, size }| {
-            let size = if let Some(n) = size { n } else { 1 };
-            let range = BusRange {
-                start: next,
-                end: next + size - 1,
-            };
+        .map(|Channel { name, size, .. }| {
+            let size = size.unwrap_or(1);
+            let range = BusRange::with_width(next, size).unwrap();
             next += size;
-            ((*name).to_string(), range)
+            (intern(&name), range)
         })
         .collect();
 
     (map, next)
 }
 
+fn declaration_order(chip: &Chip) -> Vec<Arc<str>> {
+    chip.in_pins
+        .iter()
+        .chain(chip.out_pins.iter())
+        .map(|Channel { name, .. }| intern(name))
+        .collect()
+}
+
 fn split_seq_com(pins: &Vec<Channel>, seq_names: &Vec<Span>) -> (PinMap, PinMap) {
     let (in_seq, in_com) = pins.iter().cloned().partition(|pin| {
         seq_names
@@ -55,73 +126,120 @@ impl<'a> Chip<'a> {
             let (seq_out, com_out) = split_seq_com(&self.out_pins, clocked);
 
             Interface {
-                name: self.name.to_string(),
+                name: intern(&self.name),
                 seq_in,
                 com_in,
                 seq_out,
                 com_out,
+                order: declaration_order(self),
+                doc: self.doc().map(Arc::from),
             }
         } else {
             Interface {
-                name: self.name.to_string(),
+                name: intern(&self.name),
                 com_in: to_map(self.in_pins.clone(), 0).0,
                 com_out: to_map(self.out_pins.clone(), 0).0,
                 seq_in: HashMap::with_capacity(0),
                 seq_out: HashMap::with_capacity(0),
+                order: declaration_order(self),
+                doc: self.doc().map(Arc::from),
             }
         }
     }
 }
 
 impl Interface {
-    fn iter_inputs(&self) -> impl Iterator<Item = (&String, &BusRange)> {
+    fn iter_inputs(&self) -> impl Iterator<Item = (&Arc<str>, &BusRange)> {
         self.com_in.iter().chain(self.seq_in.iter())
     }
 
-    fn iter_outputs(&self) -> impl Iterator<Item = (&String, &BusRange)> {
+    fn iter_outputs(&self) -> impl Iterator<Item = (&Arc<str>, &BusRange)> {
         self.com_out.iter().chain(self.seq_out.iter())
     }
 
-    fn iter_all(&self) -> impl Iterator<Item = (&String, &BusRange)> {
+    fn iter_all(&self) -> impl Iterator<Item = (&Arc<str>, &BusRange)> {
         self.iter_inputs().chain(self.iter_outputs())
     }
 
-    fn iter_combinatorial(&self) -> impl Iterator<Item = (&String, &BusRange)> {
+    fn iter_combinatorial(&self) -> impl Iterator<Item = (&Arc<str>, &BusRange)> {
         self.com_in.iter().chain(self.com_out.iter())
     }
 
-    fn iter_sequential(&self) -> impl Iterator<Item = (&String, &BusRange)> {
-        self.seq_in.iter().chain(self.seq_out.iter())
+    fn pin<'a>(&'a self, name: &'a str) -> Option<Pin<'a>> {
+        let (map, direction, clocked) = [
+            (&self.com_in, Direction::In, ClockBehavior::Combinatorial),
+            (&self.seq_in, Direction::In, ClockBehavior::Sequential),
+            (&self.com_out, Direction::Out, ClockBehavior::Combinatorial),
+            (&self.seq_out, Direction::Out, ClockBehavior::Sequential),
+        ]
+        .into_iter()
+        .find(|(map, ..)| map.contains_key(name))?;
+        let range = &map[name];
+
+        Some(Pin {
+            name,
+            range,
+            width: range.width(),
+            direction,
+            clocked,
+        })
+    }
+
+    /// The number of bits in the vector passed to `eval`
+    pub fn input_width(&self) -> usize {
+        self.iter_inputs()
+            .map(|(_, range)| range.end() as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The number of bits in the vector returned by `eval`
+    pub fn output_width(&self) -> usize {
+        self.iter_outputs()
+            .map(|(_, range)| range.end() as usize + 1)
+            .max()
+            .unwrap_or(0)
     }
 
-    pub fn real_range(&self, name: &str, relative: Option<&BusRange>) -> Result<BusRange, ()> {
+    /// Every pin of the chip in declaration order, inputs first
+    pub fn pins(&self) -> impl Iterator<Item = Pin<'_>> {
+        self.order.iter().filter_map(|name| self.pin(name))
+    }
+
+    /// The input pins of the chip in declaration order
+    pub fn inputs(&self) -> impl Iterator<Item = Pin<'_>> {
+        self.pins().filter(|pin| pin.direction == Direction::In)
+    }
+
+    /// The output pins of the chip in declaration order
+    pub fn outputs(&self) -> impl Iterator<Item = Pin<'_>> {
+        self.pins().filter(|pin| pin.direction == Direction::Out)
+    }
+
+    pub(crate) fn real_range(
+        &self,
+        name: &str,
+        relative: Option<&BusRange>,
+    ) -> Result<BusRange, ()> {
         let raw = self
             .iter_all()
-            .find(|(n, _)| n.as_str() == name)
+            .find(|(n, _)| &***n == name)
             .map(|(_, range)| range)
             .ok_or(())?;
-        if let Some(relative) = relative {
-            if raw.size() < relative.size() {
-                return Err(());
-            }
-            // offset the provided relative range
-            Ok(BusRange {
-                start: raw.start + relative.start,
-                end: raw.start + relative.end,
-            })
-        } else {
-            Ok(raw.clone())
+        match relative {
+            Some(relative) => raw.slice(relative).ok_or(()),
+            None => Ok(raw.clone()),
         }
     }
 
     pub fn is_input(&self, name: &str) -> bool {
-        matches!(self.iter_inputs().find(|(s, _)| *s == name), Some(_))
+        self.iter_inputs().find(|(s, _)| &***s == name).is_some()
     }
 
     pub fn clocked(&self, name: &str) -> ClockBehavior {
-        match self.iter_combinatorial().find(|(n, _)| *n == name) {
+        match self.iter_combinatorial().find(|(n, _)| &***n == name) {
             Some(_) => ClockBehavior::Combinatorial,
-            None => ClockBehavior::Sequential
+            None => ClockBehavior::Sequential,
         }
     }
 }
@@ -129,12 +247,12 @@ impl Interface {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::model::parser::chip;
+    use crate::model::parser::chip::chip;
     use std::iter::once;
 
-    const COM_CHIP: &'static str = include_str!("../../../../test_files/And16.hdl");
-    const SEQ_CHIP: &'static str = include_str!("../../../../test_files/DFF.hdl");
-    const EXAMPLE_CHIP: &'static str = "\
+    const COM_CHIP: &str = include_str!("../../../../test_files/And16.hdl");
+    const SEQ_CHIP: &str = include_str!("../../../../test_files/DFF.hdl");
+    const EXAMPLE_CHIP: &str = "\
 CHIP test {
     IN a[2], b[2], c[3];
     OUT d;
@@ -149,18 +267,20 @@ CHIP test {
         assert_eq!(
             com_chip.interface(),
             Interface {
-                name: "And16".to_string(),
+                name: "And16".into(),
                 com_in: [
-                    ("a".to_string(), BusRange { start: 0, end: 15 }),
-                    ("b".to_string(), BusRange { start: 16, end: 31 })
+                    ("a".into(), BusRange::new(0, 15).unwrap()),
+                    ("b".into(), BusRange::new(16, 31).unwrap())
                 ]
                 .into_iter()
                 .collect(),
-                com_out: [("out".to_string(), BusRange { start: 0, end: 15 })]
+                com_out: [("out".into(), BusRange::new(0, 15).unwrap())]
                     .into_iter()
                     .collect(),
                 seq_in: Default::default(),
-                seq_out: Default::default()
+                seq_out: Default::default(),
+                order: vec!["a".into(), "b".into(), "out".into()],
+                doc: Some("16-bit bitwise And:\nfor i = 0..15: out[i] = (a[i] and b[i])".into()),
             }
         );
 
@@ -168,11 +288,17 @@ CHIP test {
         assert_eq!(
             seq_chip.interface(),
             Interface {
-                name: String::from("DFF"),
+                name: "DFF".into(),
                 com_in: Default::default(),
-                com_out: once(("out".to_string(), BusRange { start: 0, end: 0 })).collect(),
-                seq_in: once(("in".to_string(), BusRange { start: 0, end: 0 })).collect(),
-                seq_out: Default::default()
+                com_out: once(("out".into(), BusRange::new(0, 0).unwrap())).collect(),
+                seq_in: once(("in".into(), BusRange::new(0, 0).unwrap())).collect(),
+                seq_out: Default::default(),
+                order: vec!["in".into(), "out".into()],
+                doc: Some(
+                    "Data Flip-flop: out(t) = in(t-1)\nwhere t is the current time unit, or clock \
+                     cycle."
+                        .into()
+                ),
             }
         );
 
@@ -180,32 +306,74 @@ CHIP test {
         assert_eq!(
             example_chip.interface(),
             Interface {
-                name: "test".to_string(),
-                com_in: once(("a".to_string(), BusRange { start: 5, end: 6 })).collect(),
-                com_out: once(("d".to_string(), BusRange { start: 0, end: 0 })).collect(),
+                name: "test".into(),
+                com_in: once(("a".into(), BusRange::new(5, 6).unwrap())).collect(),
+                com_out: once(("d".into(), BusRange::new(0, 0).unwrap())).collect(),
                 seq_in: [
-                    ("b".to_string(), BusRange { start: 0, end: 1 }),
-                    ("c".to_string(), BusRange { start: 2, end: 4 }),
+                    ("b".into(), BusRange::new(0, 1).unwrap()),
+                    ("c".into(), BusRange::new(2, 4).unwrap()),
                 ]
                 .into_iter()
                 .collect(),
-                seq_out: Default::default()
+                seq_out: Default::default(),
+                order: ["a", "b", "c", "d"].map(Arc::from).to_vec(),
+                doc: None,
             }
         )
     }
 
+    #[test]
+    fn test_ordered_pins() {
+        let (_, example_chip) = chip(Span::from(EXAMPLE_CHIP)).unwrap();
+        let interface = example_chip.interface();
+        let pins = interface
+            .pins()
+            .map(|pin| (pin.name, pin.width, pin.direction, pin.clocked))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pins,
+            vec![
+                ("a", 2, Direction::In, ClockBehavior::Combinatorial),
+                ("b", 2, Direction::In, ClockBehavior::Sequential),
+                ("c", 3, Direction::In, ClockBehavior::Sequential),
+                ("d", 1, Direction::Out, ClockBehavior::Combinatorial),
+            ]
+        );
+        assert_eq!(
+            interface.outputs().map(|pin| pin.name).collect::<Vec<_>>(),
+            vec!["d"]
+        );
+    }
+
     #[test]
     fn test_real_range() {
         let (_, com_chip) = chip(Span::from(COM_CHIP)).unwrap();
         assert_eq!(
             com_chip
                 .interface()
-                .real_range("b", Some(&BusRange { start: 0, end: 7 })),
-            Ok(BusRange { start: 16, end: 23 })
+                .real_range("b", Some(&BusRange::new(0, 7).unwrap())),
+            Ok(BusRange::new(16, 23).unwrap())
         );
         assert_eq!(
             com_chip.interface().real_range("b", None),
-            Ok(BusRange { start: 16, end: 31 })
+            Ok(BusRange::new(16, 31).unwrap())
         )
     }
+
+    #[test]
+    fn test_display_interface() {
+        let interface = |source| chip(Span::from(source)).unwrap().1.interface().to_string();
+        assert_eq!(
+            interface(COM_CHIP),
+            "CHIP And16 { IN a[16], b[16]; OUT out[16]; }"
+        );
+        assert_eq!(
+            interface(SEQ_CHIP),
+            "CHIP DFF { IN in; OUT out; CLOCKED in; }"
+        );
+        assert_eq!(
+            interface(EXAMPLE_CHIP),
+            "CHIP test { IN a[2], b[2], c[3]; OUT d; CLOCKED b, c; }"
+        );
+    }
 }
@@ -1,18 +1,22 @@
 use crate::Span;
 use nom_supreme::error::ErrorTree;
 
+mod channel;
 mod chip;
 mod connection;
-pub(crate) mod interface;
-mod channel;
-mod symbols;
 pub mod error;
+pub(crate) mod interface;
+pub(crate) mod symbols;
+pub mod tokens;
+pub mod visit;
 
 use crate::bus_range::BusRange;
-pub use chip::{chip, create_chip};
-pub use interface::Interface;
+pub use chip::{chip_header, create_chip, create_chips};
+pub use interface::{Direction, Interface, Pin};
 pub use symbols::Symbol;
 
+use std::fmt::{Display, Formatter};
+
 type PResult<'a, O> = nom::IResult<Span<'a>, O, ErrorTree<Span<'a>>>;
 
 #[derive(Debug)]
@@ -21,6 +25,55 @@ pub struct Chip<'a> {
     pub in_pins: Vec<Channel<'a>>,
     pub out_pins: Vec<Channel<'a>>,
     pub logic: Form<'a>,
+    /// The comments before the header, and any others which are not next to a pin or a part
+    pub comments: Vec<Span<'a>>,
+}
+
+impl<'a> Chip<'a> {
+    /// The names of the chips used as parts, in order of first use. Builtin chips have none.
+    pub fn parts(&self) -> Vec<&'a str> {
+        let mut parts = Vec::new();
+        if let Form::Native(connections) = &self.logic {
+            for connection in connections {
+                if !parts.contains(&*connection.chip_name) {
+                    parts.push(*connection.chip_name);
+                }
+            }
+        }
+        parts
+    }
+
+    /// The text of the last `/** ... */` comment before the header, without its delimiters and
+    /// the `*` at the start of each line, as documentation of the chip
+    pub fn doc(&self) -> Option<String> {
+        let comment = self
+            .comments
+            .iter()
+            .filter(|x| x.location_offset() < self.name.location_offset())
+            .rfind(|x| x.starts_with("/**"))?;
+        Some(doc_text(comment))
+    }
+}
+
+fn doc_text(comment: &str) -> String {
+    let inner = comment.trim_start_matches("/**").trim_end_matches("*/");
+    let lines = inner
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect::<Vec<_>>();
+    let start = lines
+        .iter()
+        .position(|x| !x.is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|x| !x.is_empty())
+        .map_or(start, |x| x + 1);
+    lines[start..end].join("\n")
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -39,12 +92,18 @@ pub struct Builtin<'a> {
 pub struct Channel<'a> {
     pub name: Span<'a>,
     pub size: Option<u16>,
+    /// The comments on the line before the pin, or after it on the same line
+    pub comments: Vec<Span<'a>>,
 }
 
 #[derive(Eq, PartialEq, Debug)]
 pub struct Connection<'a> {
+    /// The instance label given with the `label: Chip(...)` extension syntax
+    pub label: Option<Span<'a>>,
     pub chip_name: Span<'a>,
     pub inputs: Vec<Argument<'a>>,
+    /// The comments on the lines before the part, or after it on the same line
+    pub comments: Vec<Span<'a>>,
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -60,3 +119,44 @@ pub enum Value {
     True,
     False,
 }
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::True => write!(f, "true"),
+            Value::False => write!(f, "false"),
+        }
+    }
+}
+
+impl Display for Argument<'_> {
+    /// The argument as it is written in HDL, such as `a[3..4]=foo`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.internal)?;
+        if let Some(bus) = &self.internal_bus {
+            write!(f, "{bus}")?;
+        }
+        write!(f, "={}", self.external)?;
+        if let Some(bus) = &self.external_bus {
+            write!(f, "{bus}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Connection<'_> {
+    /// The part as it is written in HDL, without its comments, such as `Not(in=a, out=b)`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(label) = self.label {
+            write!(f, "{label}: ")?;
+        }
+        write!(f, "{}(", self.chip_name)?;
+        for (i, argument) in self.inputs.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{argument}")?;
+        }
+        write!(f, ")")
+    }
+}
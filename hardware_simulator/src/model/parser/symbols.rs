@@ -1,16 +1,17 @@
 use super::{PResult, Value};
+use crate::model::parser::error::HdlParseError;
 use crate::Span;
 use nom::branch::alt;
 use nom::bytes::complete::{is_not, take_till, take_until, take_while1};
 use nom::character::complete::{char, multispace1};
-use nom::combinator::{complete, opt};
+use nom::combinator::{complete, opt, recognize};
 use nom::multi::many0;
 use nom::sequence::{delimited, preceded, tuple};
 use nom::Parser;
 use nom_supreme::error::{BaseErrorKind, ErrorTree};
 use nom_supreme::tag::complete::tag;
+use std::fmt::{Display, Formatter};
 use std::num::IntErrorKind;
-use crate::model::parser::error::HdlParseError;
 
 #[derive(Eq, PartialEq, Debug)]
 pub enum Symbol<'a> {
@@ -20,22 +21,45 @@ pub enum Symbol<'a> {
 }
 
 impl<'a> TryFrom<Span<'a>> for Symbol<'a> {
-    type Error = Span<'a>;
+    type Error = HdlParseError;
 
     fn try_from(value: Span<'a>) -> Result<Self, Self::Error> {
         // a valid symbol must be in only ascii characters, as well as consisting of no whitespace
-        if value.is_ascii() && value.chars().all(|c| !c.is_ascii_whitespace()) {
-            Ok(if let Ok(num) = usize::from_str_radix(*value, 10) {
-                Symbol::Number(num)
-            } else {
-                match *value {
-                    "true" => Symbol::Value(Value::True),
-                    "false" => Symbol::Value(Value::False),
-                    _ => Symbol::Name(value),
+        if !value.is_ascii() || value.chars().any(|c| c.is_ascii_whitespace()) {
+            return Err(HdlParseError::BadSymbol);
+        }
+        if value.chars().all(|c| c.is_ascii_digit()) {
+            return match value.parse::<usize>() {
+                Ok(num) => Ok(Symbol::Number(num)),
+                Err(e) if matches!(e.kind(), IntErrorKind::PosOverflow) => {
+                    Err(HdlParseError::NumberOverflow)
                 }
-            })
-        } else {
-            Err(value)
+                Err(_) => Err(HdlParseError::NumberError),
+            };
+        }
+        Ok(match *value {
+            "true" => Symbol::Value(Value::True),
+            "false" => Symbol::Value(Value::False),
+            _ => Symbol::Name(value),
+        })
+    }
+}
+
+/// An error which no other branch of the parser can recover from, so that it is reported where
+/// it is rather than where the parser gave up
+pub fn failure(location: Span, error: HdlParseError) -> nom::Err<ErrorTree<Span>> {
+    nom::Err::Failure(ErrorTree::Base {
+        location,
+        kind: BaseErrorKind::External(Box::new(error)),
+    })
+}
+
+impl Display for Symbol<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Symbol::Name(name) => write!(f, "{name}"),
+            Symbol::Value(value) => write!(f, "{value}"),
+            Symbol::Number(number) => write!(f, "{number}"),
         }
     }
 }
@@ -62,8 +86,22 @@ pub fn name(arg: Span) -> PResult<Span> {
     }
 }
 
+/// The name of a chip, which may be followed by the width arguments of a generic chip, as in
+/// `Mux<16>`
+pub fn chip_name(arg: Span) -> PResult<Span> {
+    name(arg)?;
+    spaced(recognize(tuple((
+        take_while1(|c: char| c.is_ascii_alphanumeric()),
+        opt(complete(delimited(
+            char('<'),
+            take_while1(|c: char| c.is_ascii_alphanumeric() || matches!(c, ',' | ' ')),
+            char('>'),
+        ))),
+    ))))(arg)
+}
+
 pub fn convert_num(span: Span) -> Result<u16, nom::Err<ErrorTree<Span>>> {
-    match u16::from_str_radix(*span, 10) {
+    match span.parse::<u16>() {
         Ok(n) => Ok(n),
         Err(e) => match e.kind() {
             IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
@@ -103,9 +141,9 @@ pub fn generic_space0(arg: Span) -> PResult<()> {
     opt(generic_space1).map(|_| ()).parse(arg)
 }
 
-pub fn spaced<'a, F: 'a, O>(inner: F) -> impl FnMut(Span<'a>) -> PResult<O>
+pub fn spaced<'a, F, O>(inner: F) -> impl FnMut(Span<'a>) -> PResult<O>
 where
-    F: FnMut(Span<'a>) -> PResult<O>,
+    F: FnMut(Span<'a>) -> PResult<O> + 'a,
 {
     delimited(generic_space0, inner, generic_space0)
 }
@@ -136,13 +174,22 @@ mod test {
             assert_eq!(*(res.0), "");
             assert_eq!(*(res.1), "AbCd");
         }
-        assert!(matches!(symbol(Span::new("")), Err(_)))
+        assert!(symbol(Span::new("")).is_err())
     }
 
     #[test]
     fn test_detect_name() {
-        assert!(matches!(name(Span::new("1234")), Err(_)));
-        assert!(matches!(name(Span::new("false")), Err(_)));
+        assert!(name(Span::new("1234")).is_err());
+        assert!(name(Span::new("false")).is_err());
+    }
+
+    #[test]
+    fn test_chip_name() {
+        let (remainder, chip) = chip_name(Span::new(" Mux<N, 2> (a=a)")).unwrap();
+        assert_eq!((*chip, *remainder), ("Mux<N, 2>", "(a=a)"));
+        let (remainder, chip) = chip_name(Span::new("Mux16(a=a)")).unwrap();
+        assert_eq!((*chip, *remainder), ("Mux16", "(a=a)"));
+        assert!(chip_name(Span::new("true<1>")).is_err());
     }
 
     #[test]
@@ -159,7 +206,14 @@ mod test {
             Symbol::try_from(Span::new("false")),
             Ok(Symbol::Value(Value::False))
         );
-        assert!(matches!(Symbol::try_from(Span::new("u r bad")), Err(_)));
+        assert_eq!(
+            Symbol::try_from(Span::new("u r bad")),
+            Err(HdlParseError::BadSymbol)
+        );
+        assert_eq!(
+            Symbol::try_from(Span::new("123456789012345678901234567890")),
+            Err(HdlParseError::NumberOverflow)
+        );
     }
 
     #[test]
@@ -170,7 +224,7 @@ mod test {
                     Ok((rem, _)) => assert_eq!(*rem, str),
                     Err(_) => panic!("{test:?}"),
                 },
-                Err(_) => assert!(matches!(test, Err(_))),
+                Err(_) => assert!(test.is_err()),
             }
         }
 
@@ -0,0 +1,213 @@
+//! A lexer which classifies the text of an HDL file for syntax highlighting. Unlike the parser, it
+//! never fails: text which is being typed, and so is not valid HDL yet, still gets tokens.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// `CHIP`, `IN`, `OUT`, `PARTS`, `BUILTIN` or `CLOCKED`
+    Keyword,
+    /// The name of the chip being declared, of a part, or of a builtin, along with any width
+    /// arguments as in `Mux<16>`
+    ChipName,
+    /// The label of a part, as in `left: Mux16(...)`
+    Label,
+    Pin,
+    /// A width or a range of bits between brackets, as in `[16]` or `[0..7]`
+    BusRange,
+    /// `true`, `false` or a number
+    Constant,
+    /// A line or block comment, including `//!` directives
+    Comment,
+    Punctuation,
+    /// A character which has no place in HDL
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// The bytes of the source the token covers
+    pub range: Range<usize>,
+}
+
+const KEYWORDS: &[&str] = &["CHIP", "IN", "OUT", "PARTS", "BUILTIN", "CLOCKED"];
+
+/// The tokens of an HDL source, in order. Whitespace is left out.
+pub fn tokenize(source: &str) -> Tokens<'_> {
+    Tokens {
+        source,
+        position: 0,
+        previous: None,
+    }
+}
+
+/// The iterator returned by [`tokenize`]
+pub struct Tokens<'a> {
+    source: &'a str,
+    position: usize,
+    /// The text of the last keyword or word, which decides what a name after it is
+    previous: Option<&'a str>,
+}
+
+impl Tokens<'_> {
+    fn rest(&self) -> &str {
+        &self.source[self.position..]
+    }
+
+    /// The first character after the current position which is not whitespace
+    fn next_significant(&self, from: usize) -> Option<char> {
+        self.source[from..].chars().find(|x| !x.is_whitespace())
+    }
+
+    fn word_kind(&self, word: &str, end: usize) -> TokenKind {
+        if KEYWORDS.contains(&word) {
+            return TokenKind::Keyword;
+        }
+        if matches!(word, "true" | "false") || word.chars().all(|x| x.is_ascii_digit()) {
+            return TokenKind::Constant;
+        }
+        if matches!(self.previous, Some("CHIP" | "BUILTIN")) {
+            return TokenKind::ChipName;
+        }
+        match self.next_significant(end) {
+            Some('(' | '<') => TokenKind::ChipName,
+            Some(':') => TokenKind::Label,
+            _ => TokenKind::Pin,
+        }
+    }
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let skipped = self.rest().len() - self.rest().trim_start().len();
+        self.position += skipped;
+        let rest = self.rest();
+        let first = rest.chars().next()?;
+        let start = self.position;
+
+        let (kind, len) = if rest.starts_with("//") {
+            (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            let len = body.find("*/").map_or(rest.len(), |x| x + 4);
+            (TokenKind::Comment, len)
+        } else if first == '[' {
+            let len = rest
+                .find([']', '\n'])
+                .map_or(rest.len(), |x| x + (rest[x..].starts_with(']') as usize));
+            (TokenKind::BusRange, len)
+        } else if first.is_ascii_alphanumeric() || first == '_' {
+            let mut len = rest
+                .find(|x: char| !(x.is_ascii_alphanumeric() || x == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            let kind = self.word_kind(word, start + len);
+            // the width arguments of a generic chip are part of its name
+            if kind == TokenKind::ChipName && rest[len..].starts_with('<') {
+                len = rest[len..].find(['>', '\n', '(']).map_or(rest.len(), |x| {
+                    len + x + (rest[len + x..].starts_with('>') as usize)
+                });
+            }
+            self.previous = Some(&self.source[start..start + word.len()]);
+            (kind, len)
+        } else if "{}();,=:.".contains(first) {
+            (TokenKind::Punctuation, 1)
+        } else {
+            (TokenKind::Invalid, first.len_utf8())
+        };
+        self.position += len;
+        Some(Token {
+            kind,
+            range: start..start + len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn classify(source: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(source)
+            .map(|x| (x.kind, &source[x.range]))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        use TokenKind::*;
+        let source = "\
+// Selects a bus
+CHIP Mux16 {
+    IN a[16], sel;
+    OUT out[16];
+    PARTS:
+    low: Mux<8>(a=a[0..7], sel=true, out=out); /* the rest */
+}";
+        assert_eq!(
+            classify(source),
+            [
+                (Comment, "// Selects a bus"),
+                (Keyword, "CHIP"),
+                (ChipName, "Mux16"),
+                (Punctuation, "{"),
+                (Keyword, "IN"),
+                (Pin, "a"),
+                (BusRange, "[16]"),
+                (Punctuation, ","),
+                (Pin, "sel"),
+                (Punctuation, ";"),
+                (Keyword, "OUT"),
+                (Pin, "out"),
+                (BusRange, "[16]"),
+                (Punctuation, ";"),
+                (Keyword, "PARTS"),
+                (Punctuation, ":"),
+                (Label, "low"),
+                (Punctuation, ":"),
+                (ChipName, "Mux<8>"),
+                (Punctuation, "("),
+                (Pin, "a"),
+                (Punctuation, "="),
+                (Pin, "a"),
+                (BusRange, "[0..7]"),
+                (Punctuation, ","),
+                (Pin, "sel"),
+                (Punctuation, "="),
+                (Constant, "true"),
+                (Punctuation, ","),
+                (Pin, "out"),
+                (Punctuation, "="),
+                (Pin, "out"),
+                (Punctuation, ")"),
+                (Punctuation, ";"),
+                (Comment, "/* the rest */"),
+                (Punctuation, "}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unfinished() {
+        use TokenKind::*;
+        assert_eq!(
+            classify("CHIP Not {\n    IN in[1\n    BUILTIN Not; /* not yet"),
+            [
+                (Keyword, "CHIP"),
+                (ChipName, "Not"),
+                (Punctuation, "{"),
+                (Keyword, "IN"),
+                (Pin, "in"),
+                (BusRange, "[1"),
+                (Keyword, "BUILTIN"),
+                (ChipName, "Not"),
+                (Punctuation, ";"),
+                (Comment, "/* not yet"),
+            ]
+        );
+        assert_eq!(classify("a # b"), [(Pin, "a"), (Invalid, "#"), (Pin, "b")]);
+        assert_eq!(classify(""), []);
+    }
+}
@@ -0,0 +1,192 @@
+//! Traversal of the syntax tree of a chip. Each method of [`Visit`] and [`VisitMut`] is called on
+//! a node of its type and by default walks its children, through the `walk_*` functions, so that an
+//! implementation only needs to override the methods for the nodes it is interested in. An
+//! overriding method can call the matching `walk_*` function to keep visiting below its node.
+
+use super::{Argument, Builtin, Channel, Chip, Connection, Direction, Form, Symbol};
+
+pub trait Visit<'a> {
+    fn visit_chip(&mut self, chip: &Chip<'a>) {
+        walk_chip(self, chip);
+    }
+
+    fn visit_channel(&mut self, _channel: &Channel<'a>, _direction: Direction) {}
+
+    fn visit_builtin(&mut self, _builtin: &Builtin<'a>) {}
+
+    fn visit_connection(&mut self, connection: &Connection<'a>) {
+        walk_connection(self, connection);
+    }
+
+    fn visit_argument(&mut self, argument: &Argument<'a>) {
+        walk_argument(self, argument);
+    }
+
+    fn visit_symbol(&mut self, _symbol: &Symbol<'a>) {}
+}
+
+pub fn walk_chip<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, chip: &Chip<'a>) {
+    for channel in &chip.in_pins {
+        visitor.visit_channel(channel, Direction::In);
+    }
+    for channel in &chip.out_pins {
+        visitor.visit_channel(channel, Direction::Out);
+    }
+    match &chip.logic {
+        Form::Builtin(builtin) => visitor.visit_builtin(builtin),
+        Form::Native(connections) => {
+            for connection in connections {
+                visitor.visit_connection(connection);
+            }
+        }
+    }
+}
+
+pub fn walk_connection<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, connection: &Connection<'a>) {
+    for argument in &connection.inputs {
+        visitor.visit_argument(argument);
+    }
+}
+
+pub fn walk_argument<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, argument: &Argument<'a>) {
+    visitor.visit_symbol(&argument.external);
+}
+
+/// Like [`Visit`], but with mutable access to the nodes, so that chips can be rewritten in place
+pub trait VisitMut<'a> {
+    fn visit_chip_mut(&mut self, chip: &mut Chip<'a>) {
+        walk_chip_mut(self, chip);
+    }
+
+    fn visit_channel_mut(&mut self, _channel: &mut Channel<'a>, _direction: Direction) {}
+
+    fn visit_builtin_mut(&mut self, _builtin: &mut Builtin<'a>) {}
+
+    fn visit_connection_mut(&mut self, connection: &mut Connection<'a>) {
+        walk_connection_mut(self, connection);
+    }
+
+    fn visit_argument_mut(&mut self, argument: &mut Argument<'a>) {
+        walk_argument_mut(self, argument);
+    }
+
+    fn visit_symbol_mut(&mut self, _symbol: &mut Symbol<'a>) {}
+}
+
+pub fn walk_chip_mut<'a, V: VisitMut<'a> + ?Sized>(visitor: &mut V, chip: &mut Chip<'a>) {
+    for channel in &mut chip.in_pins {
+        visitor.visit_channel_mut(channel, Direction::In);
+    }
+    for channel in &mut chip.out_pins {
+        visitor.visit_channel_mut(channel, Direction::Out);
+    }
+    match &mut chip.logic {
+        Form::Builtin(builtin) => visitor.visit_builtin_mut(builtin),
+        Form::Native(connections) => {
+            for connection in connections {
+                visitor.visit_connection_mut(connection);
+            }
+        }
+    }
+}
+
+pub fn walk_connection_mut<'a, V: VisitMut<'a> + ?Sized>(
+    visitor: &mut V,
+    connection: &mut Connection<'a>,
+) {
+    for argument in &mut connection.inputs {
+        visitor.visit_argument_mut(argument);
+    }
+}
+
+pub fn walk_argument_mut<'a, V: VisitMut<'a> + ?Sized>(
+    visitor: &mut V,
+    argument: &mut Argument<'a>,
+) {
+    visitor.visit_symbol_mut(&mut argument.external);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::parser::create_chip;
+    use crate::Span;
+
+    const SOURCE: &str = "CHIP Or8Way {
+    IN in[8];
+    OUT out;
+    PARTS:
+    Or(a=in[0], b=in[1], out=x);
+    Or(a=x, b=false, out=out);
+}";
+
+    #[test]
+    fn test_visit() {
+        #[derive(Default)]
+        struct Names(Vec<String>);
+
+        impl<'a> Visit<'a> for Names {
+            fn visit_channel(&mut self, channel: &Channel<'a>, direction: Direction) {
+                let prefix = match direction {
+                    Direction::In => "in",
+                    Direction::Out => "out",
+                };
+                self.0.push(format!("{prefix} {}", channel.name));
+            }
+
+            fn visit_connection(&mut self, connection: &Connection<'a>) {
+                self.0.push(format!("part {}", connection.chip_name));
+                walk_connection(self, connection);
+            }
+
+            fn visit_symbol(&mut self, symbol: &Symbol<'a>) {
+                if let Symbol::Name(name) = symbol {
+                    self.0.push(name.to_string());
+                }
+            }
+        }
+
+        let chip = create_chip(Span::new(SOURCE)).unwrap();
+        let mut names = Names::default();
+        names.visit_chip(&chip);
+        assert_eq!(
+            names.0,
+            ["in in", "out out", "part Or", "in", "in", "x", "part Or", "x", "out"]
+        );
+    }
+
+    #[test]
+    fn test_visit_mut() {
+        struct Rename;
+
+        impl<'a> VisitMut<'a> for Rename {
+            fn visit_connection_mut(&mut self, connection: &mut Connection<'a>) {
+                if *connection.chip_name == "Or" {
+                    connection.chip_name = Span::new("Nor");
+                }
+                walk_connection_mut(self, connection);
+            }
+
+            fn visit_symbol_mut(&mut self, symbol: &mut Symbol<'a>) {
+                if matches!(symbol, Symbol::Name(name) if **name == "x") {
+                    *symbol = Symbol::Name(Span::new("y"));
+                }
+            }
+        }
+
+        let mut chip = create_chip(Span::new(SOURCE)).unwrap();
+        Rename.visit_chip_mut(&mut chip);
+        let Form::Native(connections) = &chip.logic else {
+            panic!("Or8Way is not builtin");
+        };
+        assert!(connections.iter().all(|x| *x.chip_name == "Nor"));
+        assert_eq!(
+            connections[0].inputs[2].external,
+            Symbol::Name(Span::new("y"))
+        );
+        assert_eq!(
+            connections[1].inputs[0].external,
+            Symbol::Name(Span::new("y"))
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! A text preprocessor for HDL files, so that chips of a large project can share boilerplate such
+//! as pin lists. Directives are line comments starting with `//!`, which the course tools ignore:
+//!
+//! ```text
+//! //! include "common.hdl"
+//! //! define WIDTH 16
+//! ```
+//!
+//! An include is replaced by the preprocessed text of the file, found relative to the file which
+//! includes it. A define replaces every later occurrence of the name as a whole word, including
+//! in included files. Positions in parse errors refer to the preprocessed text.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PreprocessError {
+    #[error("Line {line} of {path:?} is not a valid directive: `//!{directive}`")]
+    BadDirective {
+        path: PathBuf,
+        line: usize,
+        directive: String,
+    },
+    #[error("Could not include {path:?}: {error}")]
+    Include { path: PathBuf, error: io::Error },
+    #[error("{path:?} includes itself")]
+    IncludeCycle { path: PathBuf },
+}
+
+impl PreprocessError {
+    /// A code which stays the same for each kind of error, see [`crate::error`]
+    pub fn code(&self) -> &'static str {
+        "E0103"
+    }
+}
+
+/// Expands the directives of the source of the file at `path`
+pub fn preprocess(source: &str, path: &Path) -> Result<String, PreprocessError> {
+    let mut output = String::new();
+    Preprocessor::default().expand(source, path, &mut output)?;
+    Ok(output)
+}
+
+#[derive(Default)]
+struct Preprocessor {
+    macros: HashMap<String, String>,
+    /// The files being expanded, outermost first
+    including: Vec<PathBuf>,
+}
+
+impl Preprocessor {
+    fn expand(
+        &mut self,
+        source: &str,
+        path: &Path,
+        output: &mut String,
+    ) -> Result<(), PreprocessError> {
+        self.including.push(path.to_path_buf());
+        for (i, line) in source.lines().enumerate() {
+            match line.trim_start().strip_prefix("//!") {
+                Some(directive) => self.directive(directive, path, i + 1, output)?,
+                None => {
+                    output.push_str(&substitute(line, &self.macros));
+                    output.push('\n');
+                }
+            }
+        }
+        self.including.pop();
+        Ok(())
+    }
+
+    fn directive(
+        &mut self,
+        directive: &str,
+        path: &Path,
+        line: usize,
+        output: &mut String,
+    ) -> Result<(), PreprocessError> {
+        let bad = || PreprocessError::BadDirective {
+            path: path.to_path_buf(),
+            line,
+            directive: directive.to_string(),
+        };
+        let (keyword, rest) = directive
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(bad)?;
+        match keyword {
+            "include" => {
+                let name = rest
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|x| x.strip_suffix('"'))
+                    .ok_or_else(bad)?;
+                let target = path.with_file_name(name);
+                if self.including.contains(&target) {
+                    return Err(PreprocessError::IncludeCycle { path: target });
+                }
+                let source =
+                    fs::read_to_string(&target).map_err(|error| PreprocessError::Include {
+                        path: target.clone(),
+                        error,
+                    })?;
+                self.expand(&source, &target, output)
+            }
+            "define" => {
+                let rest = rest.trim_start();
+                let (name, body) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if !is_identifier(name) {
+                    return Err(bad());
+                }
+                let body = substitute(body.trim(), &self.macros);
+                self.macros.insert(name.to_string(), body);
+                // keeps the lines of the file where they were, for errors
+                output.push('\n');
+                Ok(())
+            }
+            _ => Err(bad()),
+        }
+    }
+}
+
+/// Replaces every word of the text which is the name of a macro
+pub(crate) fn substitute(text: &str, macros: &HashMap<String, String>) -> String {
+    if macros.is_empty() {
+        return text.to_string();
+    }
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_word_char) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|x| !is_word_char(x)).unwrap_or(rest.len());
+        let word = &rest[..end];
+        output.push_str(macros.get(word).map_or(word, |x| x.as_str()));
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|x: char| x.is_ascii_alphabetic() || x == '_')
+        && name.chars().all(is_word_char)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn test_define() {
+        let source = "\
+//! define WIDTH 16
+//! define BUS [WIDTH]
+IN a BUS, WIDTH2;
+OUT out[WIDTH];";
+        assert_eq!(
+            preprocess(source, Path::new("Chip.hdl")).unwrap(),
+            "\n\nIN a [16], WIDTH2;\nOUT out[16];\n"
+        );
+    }
+
+    #[test]
+    fn test_bad_directive() {
+        for source in [
+            "//! define",
+            "//! define 1x 2",
+            "//! import \"a.hdl\"",
+            "//! include a.hdl",
+        ] {
+            assert!(
+                matches!(
+                    preprocess(source, Path::new("Chip.hdl")),
+                    Err(PreprocessError::BadDirective { line: 1, .. })
+                ),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = TempDir::new("preprocess");
+        fs::write(dir.join("pins.hdl"), "//! define N 2\nIN a[N];").unwrap();
+        fs::write(dir.join("loop.hdl"), "//! include \"loop.hdl\"").unwrap();
+
+        let chip = dir.join("Chip.hdl");
+        let expanded = preprocess("//! include \"pins.hdl\"\nOUT out[N];", &chip);
+        let cycle = preprocess("//! include \"loop.hdl\"", &chip);
+        let missing = preprocess("//! include \"none.hdl\"", &chip);
+
+        assert_eq!(expanded.unwrap(), "\nIN a[2];\nOUT out[2];\n");
+        assert!(matches!(cycle, Err(PreprocessError::IncludeCycle { .. })));
+        assert!(matches!(missing, Err(PreprocessError::Include { .. })));
+    }
+}
@@ -0,0 +1,316 @@
+//! Generates the files a project of the course starts from: a stub HDL file for each chip, with
+//! the interface of the course and no parts, and a test script which drives its pins, so that a
+//! class can start from this simulator alone. Skeleton tests can also be generated for any chip.
+
+use crate::error::Result;
+use crate::manifest::Manifest;
+use crate::model::chip::build_ctx::ChipBuilder;
+use crate::model::chip::canonical::{canonical, CanonicalChip};
+use crate::model::dialect::Dialect;
+use crate::model::diff::read_interface;
+use crate::model::{Direction, Interface};
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+/// The chips the students write in each project of the course
+pub const PROJECTS: &[(u8, &[&str])] = &[
+    (
+        1,
+        &[
+            "Not",
+            "And",
+            "Or",
+            "Xor",
+            "Mux",
+            "DMux",
+            "Not16",
+            "And16",
+            "Or16",
+            "Mux16",
+            "Or8Way",
+            "Mux4Way16",
+            "Mux8Way16",
+            "DMux4Way",
+            "DMux8Way",
+        ],
+    ),
+    (2, &["HalfAdder", "FullAdder", "Add16", "Inc16", "ALU"]),
+    (
+        3,
+        &[
+            "Bit", "Register", "RAM8", "RAM64", "RAM512", "RAM4K", "RAM16K", "PC",
+        ],
+    ),
+    (5, &["Memory", "CPU", "Computer"]),
+];
+
+/// The chips of the projects which keep state, and are tested with the clock
+const CLOCKED: &[&str] = &[
+    "Bit", "Register", "RAM8", "RAM64", "RAM512", "RAM4K", "RAM16K", "PC", "Memory", "CPU",
+    "Computer",
+];
+
+/// The chips of a project, or `None` if the course has no chips in it
+pub fn project_chips(project: u8) -> Option<&'static [&'static str]> {
+    PROJECTS
+        .iter()
+        .find(|(number, _)| *number == project)
+        .map(|(_, chips)| *chips)
+}
+
+fn declaration(pins: &[(&str, u16)]) -> String {
+    pins.iter()
+        .map(|(name, width)| match width {
+            1 => name.to_string(),
+            _ => format!("{name}[{width}]"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The file a student starts a chip from, as in the course: its interface, and `PARTS:` with
+/// nothing under it
+pub fn stub_hdl(chip: &CanonicalChip) -> String {
+    let mut hdl = format!("CHIP {} {{\n", chip.name);
+    if !chip.inputs.is_empty() {
+        writeln!(hdl, "    IN {};", declaration(chip.inputs)).unwrap();
+    }
+    if !chip.outputs.is_empty() {
+        writeln!(hdl, "    OUT {};", declaration(chip.outputs)).unwrap();
+    }
+    hdl.push_str("\n    PARTS:\n    // Put your code here:\n}\n");
+    hdl
+}
+
+/// A test script which loads a chip and writes every pin to its output file, with a single step
+/// which sets every input to 0. Clocked chips are stepped with `tick` and `tock`, and also output
+/// the time.
+pub fn stub_script(name: &str, pins: &[(&str, u16, Direction)], clocked: bool) -> String {
+    let mut script = format!("load {name}.hdl,\noutput-file {name}.out,\ncompare-to {name}.cmp,\n");
+    let columns = clocked
+        .then(|| "time%S1.4.1".to_string())
+        .into_iter()
+        .chain(pins.iter().map(|(pin, width, _)| match width {
+            1 => format!("{pin}%B3.1.3"),
+            _ => format!("{pin}%B1.{width}.1"),
+        }))
+        .collect::<Vec<_>>();
+    writeln!(script, "output-list {};\n", columns.join(" ")).unwrap();
+    let sets = pins
+        .iter()
+        .filter(|(_, _, direction)| *direction == Direction::In)
+        .map(|(pin, ..)| format!("set {pin} 0, "))
+        .collect::<String>();
+    if clocked {
+        writeln!(script, "{sets}tick, output; tock, output;").unwrap();
+    } else {
+        writeln!(script, "{sets}eval, output;").unwrap();
+    }
+    script.push_str("// add a step for each case the chip should handle\n");
+    script
+}
+
+fn canonical_script(chip: &CanonicalChip) -> String {
+    let pins = chip
+        .inputs
+        .iter()
+        .map(|(name, width)| (*name, *width, Direction::In))
+        .chain(
+            chip.outputs
+                .iter()
+                .map(|(name, width)| (*name, *width, Direction::Out)),
+        )
+        .collect::<Vec<_>>();
+    stub_script(chip.name, &pins, CLOCKED.contains(&chip.name))
+}
+
+/// A skeleton test script for a chip, see [`stub_script`]. The chip is driven with the clock if
+/// any of its pins is clocked.
+pub fn interface_script(interface: &Interface) -> String {
+    let pins = interface
+        .pins()
+        .map(|pin| (pin.name, pin.width, pin.direction))
+        .collect::<Vec<_>>();
+    let clocked = !interface.seq_in.is_empty() || !interface.seq_out.is_empty();
+    stub_script(&interface.name, &pins, clocked)
+}
+
+/// Writes a file unless it already exists
+fn create(path: &Path, text: &str) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+}
+
+/// Writes a skeleton test script for the chip in an HDL file next to it, along with an empty
+/// comparison file to fill in with the rows the chip should output. Returns the files which were
+/// written. Which pins are clocked is found by building the chip, or taken from its declaration
+/// if it cannot be built. Existing files are never overwritten.
+pub fn scaffold_tests(hdl: &Path, dialect: Dialect) -> Result<Vec<PathBuf>> {
+    let manifest = Manifest {
+        dialect,
+        ..Manifest::new(hdl.parent().unwrap_or(Path::new(".")))
+    };
+    scaffold_tests_with(hdl, &manifest)
+}
+
+/// Writes the test script of [`scaffold_tests`], building the chip in the dialect and with the
+/// builtins of a manifest
+pub fn scaffold_tests_with(hdl: &Path, manifest: &Manifest) -> Result<Vec<PathBuf>> {
+    let dialect = manifest.dialect;
+    let mut builder = ChipBuilder::new();
+    manifest.configure_builder(&mut builder);
+    let name = hdl.file_stem().unwrap_or_default().to_string_lossy();
+    let built = builder
+        .add_hdl(hdl)
+        .ok()
+        .and_then(|_| builder.hdl_chip(&name));
+    let interface = match built {
+        Some(chip) => chip.interface(),
+        None => read_interface(hdl, dialect)?,
+    };
+    let (script, cmp) = (
+        hdl.with_file_name(format!("{}.tst", interface.name)),
+        hdl.with_file_name(format!("{}.cmp", interface.name)),
+    );
+    create(&script, &interface_script(&interface))?;
+    create(&cmp, "")?;
+    Ok(vec![script, cmp])
+}
+
+/// Writes the stub HDL file and test script of every chip of a project into `dir`, which is
+/// created if needed, and returns the files which were written. Files which already exist are
+/// never overwritten: the first one stops the generation with an error.
+pub fn new_project(project: u8, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let chips = project_chips(project).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Project {project} has no chips"),
+        )
+    })?;
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for chip in chips.iter().filter_map(|x| canonical(x)) {
+        for (extension, text) in [("hdl", stub_hdl(chip)), ("tst", canonical_script(chip))] {
+            let path = dir.join(format!("{}.{extension}", chip.name));
+            create(&path, &text)?;
+            written.push(path);
+        }
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::catalog::project_interfaces;
+    use crate::temp_dir::TempDir;
+    use crate::test_script::parse_script;
+
+    #[test]
+    fn test_stubs() {
+        assert_eq!(
+            stub_hdl(canonical("RAM8").unwrap()),
+            "\
+CHIP RAM8 {
+    IN in[16], load, address[3];
+    OUT out[16];
+
+    PARTS:
+    // Put your code here:
+}
+"
+        );
+        assert_eq!(
+            canonical_script(canonical("Mux").unwrap()),
+            "\
+load Mux.hdl,
+output-file Mux.out,
+compare-to Mux.cmp,
+output-list a%B3.1.3 b%B3.1.3 sel%B3.1.3 out%B3.1.3;
+
+set a 0, set b 0, set sel 0, eval, output;
+// add a step for each case the chip should handle
+"
+        );
+        let script = canonical_script(canonical("PC").unwrap());
+        assert!(script.contains("output-list time%S1.4.1 in%B1.16.1 "));
+        assert!(script.contains("set reset 0, tick, output; tock, output;\n"));
+
+        // every chip of the course has an interface and a script which can be read back
+        for (_, chips) in PROJECTS {
+            for chip in chips.iter() {
+                let chip = canonical(chip).unwrap();
+                parse_script(&canonical_script(chip)).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_project() {
+        let dir = TempDir::new("new");
+        let written = new_project(2, &dir).unwrap();
+        assert_eq!(written.len(), 10);
+        assert!(dir.join("ALU.hdl").is_file());
+        assert!(dir.join("ALU.tst").is_file());
+        // nothing is overwritten
+        let again = new_project(2, &dir);
+        assert_eq!(again.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert!(new_project(4, &dir).is_err());
+
+        // the stubs can be read back by the tools which go through a project
+        for (project, chips) in PROJECTS {
+            let dir = TempDir::new(&format!("new-{project}"));
+            for path in new_project(*project, &dir).unwrap() {
+                if path.extension().is_some_and(|x| x == "hdl") {
+                    let interface = read_interface(&path, Dialect::Strict).unwrap();
+                    assert_eq!(
+                        *interface.name,
+                        *path.file_stem().unwrap().to_string_lossy()
+                    );
+                }
+            }
+            let interfaces = project_interfaces(&dir, Dialect::Strict).unwrap();
+            assert_eq!(interfaces.len(), chips.len());
+        }
+    }
+
+    #[test]
+    fn test_scaffold_tests() {
+        let dir = TempDir::new("scaffold");
+        let files = std::env::current_dir().unwrap().join("../test_files");
+        for name in ["Not", "And", "Or", "Mux", "Bit"] {
+            fs::copy(
+                files.join(format!("{name}.hdl")),
+                dir.join(format!("{name}.hdl")),
+            )
+            .unwrap();
+        }
+        let written = scaffold_tests(&dir.join("Bit.hdl"), Dialect::Strict).unwrap();
+        let script = fs::read_to_string(dir.join("Bit.tst")).unwrap();
+        let cmp = fs::read_to_string(dir.join("Bit.cmp")).unwrap();
+        let again = scaffold_tests(&dir.join("Bit.hdl"), Dialect::Strict);
+
+        assert_eq!(written, [dir.join("Bit.tst"), dir.join("Bit.cmp")]);
+        // the clocked pins of a chip built from parts are only known once it is built
+        assert_eq!(
+            script,
+            "\
+load Bit.hdl,
+output-file Bit.out,
+compare-to Bit.cmp,
+output-list time%S1.4.1 in%B3.1.3 load%B3.1.3 out%B3.1.3;
+
+set in 0, set load 0, tick, output; tock, output;
+// add a step for each case the chip should handle
+"
+        );
+        assert!(cmp.is_empty());
+        assert!(again.is_err());
+    }
+}
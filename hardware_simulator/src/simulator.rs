@@ -0,0 +1,365 @@
+use crate::clock_behavior::{Clock, Throttle};
+use crate::coverage::ToggleCoverage;
+use crate::image::{read_image, ImageError};
+use crate::initial_state::{InitialState, StateRng};
+use crate::model::chip::Chip;
+use crate::model::{Direction, Interface};
+use std::ops::{ControlFlow, Range};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SimulationError {
+    #[error("Chip `{chip}` has no pin `{pin}`")]
+    UnknownPin { chip: String, pin: String },
+    #[error("Pin `{pin}` is {expected} bits wide, but {found} bits were given")]
+    WidthMismatch {
+        pin: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Pin `{0}` is an output and cannot be set")]
+    NotAnInput(String),
+    #[error("A clock frequency of {0} Hz is not a positive number of cycles per second")]
+    BadClockFrequency(String),
+}
+
+impl SimulationError {
+    /// A code which stays the same for each kind of error, see [`crate::error`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            SimulationError::UnknownPin { .. } => "E0301",
+            SimulationError::WidthMismatch { .. } => "E0302",
+            SimulationError::NotAnInput(_) => "E0303",
+            SimulationError::BadClockFrequency(_) => "E0304",
+        }
+    }
+}
+
+pub(crate) fn throttle(hertz: f64) -> Result<Throttle, SimulationError> {
+    Throttle::new(hertz).ok_or_else(|| SimulationError::BadClockFrequency(hertz.to_string()))
+}
+
+/// Drives a chip from the outside, keeping track of its input pins and the clock
+pub struct Simulator {
+    chip: Chip,
+    interface: Interface,
+    inputs: Vec<bool>,
+    outputs: Vec<bool>,
+    clock: Clock,
+    initial_state: InitialState,
+    toggles: Option<ToggleCoverage>,
+    /// The pins passed to the callback of [`run_cycles`](Self::run_cycles)
+    probes: Vec<(Range<usize>, bool)>,
+    throttle: Option<Throttle>,
+    evals: u64,
+    cycles: u64,
+}
+
+/// Counts of the work a [`Simulator`] has done, to measure what a design costs to simulate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Evaluations of builtin chips such as `Nand`, at any depth
+    pub primitive_evals: u64,
+    /// Passes over the parts of a native chip while evaluating it, at any depth. Parts are
+    /// evaluated in order, so a pass is enough for chips without loops through clocked pins.
+    pub traversals: u64,
+    /// Evaluations of the whole chip, by `eval`, `tick` and `tock`
+    pub evals: u64,
+    /// Full clock cycles, counted at each `tock`
+    pub cycles: u64,
+}
+
+/// What [`Simulator::swap_program`] resets besides the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgramReset {
+    /// The chip carries on from where it was, with the new program
+    #[default]
+    Nothing,
+    /// Everything but the contents of memories such as the RAM, as [`Simulator::reset`]
+    KeepMemory,
+    /// Everything, clearing memories too
+    Everything,
+}
+
+impl Simulator {
+    pub fn new(chip: Chip) -> Self {
+        Self::with_initial_state(chip, InitialState::Zero)
+    }
+
+    /// Creates a simulator whose chip starts out in the given state, and returns to it whenever
+    /// it is reset
+    pub fn with_initial_state(chip: Chip, initial_state: InitialState) -> Self {
+        let interface = chip.interface();
+        let mut simulator = Self {
+            inputs: vec![false; interface.input_width()],
+            outputs: vec![false; interface.output_width()],
+            interface,
+            chip,
+            clock: Clock::default(),
+            initial_state,
+            toggles: None,
+            probes: Vec::new(),
+            throttle: None,
+            evals: 0,
+            cycles: 0,
+        };
+        simulator.initialize(true);
+        simulator.clear_metrics();
+        simulator
+    }
+
+    pub fn chip(&self) -> &Chip {
+        &self.chip
+    }
+
+    pub fn interface(&self) -> &Interface {
+        &self.interface
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn inputs(&self) -> &[bool] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[bool] {
+        &self.outputs
+    }
+
+    /// Sets an input pin. The new value is only seen by the chip once it is evaluated.
+    pub fn set(&mut self, pin: &str, value: &[bool]) -> Result<(), SimulationError> {
+        let (range, is_input) = self.find(pin)?;
+        if !is_input {
+            return Err(SimulationError::NotAnInput(pin.to_string()));
+        }
+        if range.len() != value.len() {
+            return Err(SimulationError::WidthMismatch {
+                pin: pin.to_string(),
+                expected: range.len(),
+                found: value.len(),
+            });
+        }
+        self.inputs[range].copy_from_slice(value);
+        Ok(())
+    }
+
+    /// The current value of an input or output pin, least significant bit first
+    pub fn get(&self, pin: &str) -> Result<&[bool], SimulationError> {
+        let (range, is_input) = self.find(pin)?;
+        Ok(if is_input {
+            &self.inputs[range]
+        } else {
+            &self.outputs[range]
+        })
+    }
+
+    fn find(&self, pin: &str) -> Result<(Range<usize>, bool), SimulationError> {
+        self.interface
+            .pins()
+            .find(|x| x.name == pin)
+            .map(|x| (x.range.indices(), x.direction == Direction::In))
+            .ok_or_else(|| SimulationError::UnknownPin {
+                chip: self.interface.name.to_string(),
+                pin: pin.to_string(),
+            })
+    }
+
+    /// Propagates the current inputs through the chip without touching the clock
+    pub fn eval(&mut self) -> &[bool] {
+        self.evals += 1;
+        self.outputs = self.chip.eval(&self.inputs);
+        if let Some(toggles) = self.toggles.as_mut() {
+            toggles.record(&self.chip.pin_values());
+        }
+        &self.outputs
+    }
+
+    /// The work done since the simulator was created or [`clear_metrics`](Self::clear_metrics)
+    /// was last called. Resetting the chip does not clear it.
+    pub fn metrics(&self) -> Metrics {
+        let (primitive_evals, traversals) = match &self.chip {
+            Chip::Native(chip) => {
+                let counters = chip.counters();
+                (counters.primitive_evals, counters.traversals)
+            }
+            Chip::Builtin(_) => (self.evals, 0),
+        };
+        Metrics {
+            primitive_evals,
+            traversals,
+            evals: self.evals,
+            cycles: self.cycles,
+        }
+    }
+
+    pub fn clear_metrics(&mut self) {
+        if let Chip::Native(chip) = &mut self.chip {
+            chip.clear_counters();
+        }
+        self.evals = 0;
+        self.cycles = 0;
+    }
+
+    /// Starts recording which bits of the pins inside the chip toggle, beginning with their
+    /// current values
+    pub fn track_toggles(&mut self) {
+        let mut toggles = ToggleCoverage::default();
+        toggles.record(&self.chip.pin_values());
+        self.toggles = Some(toggles);
+    }
+
+    /// The toggle coverage since [`track_toggles`](Self::track_toggles) was called
+    pub fn toggles(&self) -> Option<&ToggleCoverage> {
+        self.toggles.as_ref()
+    }
+
+    /// Raises the clock: the chip is evaluated and its clocked inputs are latched
+    pub fn tick(&mut self) -> &[bool] {
+        self.eval();
+        self.chip.tick();
+        self.clock.tick();
+        &self.outputs
+    }
+
+    /// Lowers the clock: latched values are committed to the chip's state, the new state is
+    /// propagated, and time advances. If the clock has a frequency, this waits until the cycle is
+    /// due.
+    pub fn tock(&mut self) -> &[bool] {
+        if let Some(throttle) = self.throttle.as_mut() {
+            throttle.wait();
+        }
+        self.chip.clock();
+        self.eval();
+        self.clock.tock();
+        self.cycles += 1;
+        &self.outputs
+    }
+
+    /// Runs at most this many cycles per second, or as fast as possible if `None`. The frequency
+    /// has to be positive and finite.
+    pub fn set_clock_frequency(&mut self, hertz: Option<f64>) -> Result<(), SimulationError> {
+        self.throttle = hertz.map(throttle).transpose()?;
+        Ok(())
+    }
+
+    pub fn clock_frequency(&self) -> Option<f64> {
+        self.throttle.map(|x| x.hertz())
+    }
+
+    pub fn initial_state(&self) -> InitialState {
+        self.initial_state
+    }
+
+    fn initialize(&mut self, memory: bool) {
+        if let InitialState::Random { seed } = self.initial_state {
+            self.chip.randomize(&mut StateRng::new(seed), memory);
+        }
+        self.eval();
+    }
+
+    /// Returns the chip, its inputs and the clock to their initial state, so that it can be driven
+    /// again without being rebuilt. The contents of memories are kept unless `clear_memory` is
+    /// set.
+    pub fn reset(&mut self, clear_memory: bool) {
+        self.chip.reset(clear_memory);
+        self.inputs.fill(false);
+        self.clock = Clock::default();
+        self.initialize(clear_memory);
+    }
+
+    /// Loads words into the memory of every part named `chip`, such as `ROM32K`, and propagates
+    /// the change. Returns how many memories were loaded.
+    pub fn load_memory(&mut self, chip: &str, words: &[u16]) -> usize {
+        let loaded = self.chip.load_memory(chip, words);
+        self.eval();
+        loaded
+    }
+
+    /// The contents of the memory of the first part named `chip`, such as `RAM16K`
+    pub fn memory(&self, chip: &str) -> Option<&[u16]> {
+        self.chip.memory(chip)
+    }
+
+    /// Writes words into the memory of every part named `chip` from `address` on, keeping the
+    /// rest, and propagates the change. Returns how many memories were written.
+    pub fn write_memory(&mut self, chip: &str, address: usize, words: &[u16]) -> usize {
+        let written = self.chip.write_memory(chip, address, words);
+        self.eval();
+        written
+    }
+
+    /// A word of the memory of the first part named `chip`, or `None` if there is no such word
+    pub fn peek(&self, chip: &str, address: usize) -> Option<u16> {
+        self.memory(chip)?.get(address).copied()
+    }
+
+    /// Sets a word of the memory of every part named `chip`, as
+    /// [`write_memory`](Self::write_memory)
+    pub fn poke(&mut self, chip: &str, address: usize, word: u16) -> usize {
+        self.write_memory(chip, address, &[word])
+    }
+
+    /// Loads a memory image from a file, in the format given by its extension, as
+    /// [`load_memory`](Self::load_memory)
+    pub fn load_image(&mut self, chip: &str, path: impl AsRef<Path>) -> Result<usize, ImageError> {
+        let words = read_image(path)?;
+        Ok(self.load_memory(chip, &words))
+    }
+
+    /// Replaces the program in the `ROM32K` of the chip, resetting as much of the rest as asked,
+    /// so that an edited program can be tried without rebuilding the chip. Returns how many
+    /// memories were loaded.
+    pub fn swap_program(&mut self, words: &[u16], reset: ProgramReset) -> usize {
+        match reset {
+            ProgramReset::Nothing => {}
+            ProgramReset::KeepMemory => self.reset(false),
+            ProgramReset::Everything => self.reset(true),
+        }
+        self.load_memory("ROM32K", words)
+    }
+
+    /// A full clock cycle, a tick followed by a tock
+    pub fn cycle(&mut self) -> &[bool] {
+        self.tick();
+        self.tock()
+    }
+
+    /// Selects the pins whose values are passed to the callback of
+    /// [`run_cycles`](Self::run_cycles), in the given order
+    pub fn set_probes(&mut self, pins: &[&str]) -> Result<(), SimulationError> {
+        self.probes = pins
+            .iter()
+            .map(|pin| self.find(pin))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Runs up to `n` clock cycles without any test script. After each cycle, `f` is called with
+    /// the number of the cycle, counting from 0, and the values of the probes. The run stops
+    /// early, with the value it was given, as soon as `f` breaks.
+    pub fn run_cycles<B>(
+        &mut self,
+        n: u64,
+        mut f: impl FnMut(u64, &[&[bool]]) -> ControlFlow<B>,
+    ) -> ControlFlow<B> {
+        for cycle in 0..n {
+            self.cycle();
+            let values = self
+                .probes
+                .iter()
+                .map(|(range, is_input)| {
+                    if *is_input {
+                        &self.inputs[range.clone()]
+                    } else {
+                        &self.outputs[range.clone()]
+                    }
+                })
+                .collect::<Vec<_>>();
+            f(cycle, &values)?;
+        }
+        ControlFlow::Continue(())
+    }
+}
@@ -0,0 +1,91 @@
+//! How much of a chip a test script checks, either by how many stuck-at faults in the chip make it
+//! fail, or by which pins of the chip it toggles.
+
+use super::error::ScriptError;
+use super::parser::parse_script;
+use super::runner::{Limits, TestRunner};
+use super::Script;
+use crate::coverage::ToggleCoverage;
+use crate::model::chip::fault::StuckAt;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default)]
+pub struct FaultReport {
+    /// Faults which made the script fail
+    pub detected: Vec<StuckAt>,
+    /// Faults which the script passed with, and so are not tested by it
+    pub undetected: Vec<StuckAt>,
+}
+
+impl FaultReport {
+    /// The share of faults which were detected, between 0 and 1
+    pub fn coverage(&self) -> f64 {
+        let total = self.detected.len() + self.undetected.len();
+        if total == 0 {
+            1.0
+        } else {
+            self.detected.len() as f64 / total as f64
+        }
+    }
+}
+
+fn read_script(path: &Path) -> Result<Script, ScriptError> {
+    let source = fs::read_to_string(path).map_err(|e| ScriptError {
+        line: 0,
+        column: 0,
+        kind: e.into(),
+    })?;
+    parse_script(&source)
+}
+
+/// Runs a script and returns which pins toggled in the last chip it loaded
+pub fn toggle_coverage(
+    path: impl AsRef<Path>,
+    limits: Limits,
+) -> Result<ToggleCoverage, ScriptError> {
+    let path = path.as_ref();
+    let script = read_script(path)?;
+    let mut runner = TestRunner::new(path.parent().unwrap_or(Path::new(".")))
+        .with_limits(limits)
+        .with_toggle_tracking();
+    runner.run(&script)?;
+    Ok(runner
+        .simulator()
+        .and_then(|x| x.toggles())
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Runs a script once as it is, and then again for every stuck-at fault of the last chip it
+/// loads. Only the first run writes the output file. The script has to pass without faults.
+/// `limits` apply to every run, so that a fault which makes a chip loop forever counts as
+/// detected.
+pub fn fault_coverage(path: impl AsRef<Path>, limits: Limits) -> Result<FaultReport, ScriptError> {
+    let path = path.as_ref();
+    let script = read_script(path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    let mut runner = TestRunner::new(dir).with_limits(limits);
+    runner.run(&script)?;
+    let sites = runner
+        .simulator()
+        .map(|x| x.chip().fault_sites())
+        .unwrap_or_default();
+
+    let mut report = FaultReport::default();
+    for fault in sites.iter().flat_map(|x| x.faults()) {
+        let passed = TestRunner::new(dir)
+            .with_limits(limits)
+            .with_faults(vec![fault.clone()])
+            .without_output_file()
+            .run(&script)
+            .is_ok();
+        if passed {
+            report.undetected.push(fault);
+        } else {
+            report.detected.push(fault);
+        }
+    }
+    Ok(report)
+}
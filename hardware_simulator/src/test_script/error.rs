@@ -0,0 +1,92 @@
+use crate::image::ImageError;
+use crate::model::chip::error::ModelConstructionError;
+use crate::simulator::SimulationError;
+use thiserror::Error;
+
+/// An error which stopped a script, located at the command which caused it
+#[derive(Error, Debug)]
+#[error("{line}:{column}: {kind}")]
+pub struct ScriptError {
+    pub line: u32,
+    pub column: usize,
+    pub kind: ScriptErrorKind,
+}
+
+#[derive(Error, Debug)]
+pub enum ScriptErrorKind {
+    #[error("Could not parse the script")]
+    ParseError,
+    #[error("Could not read the script: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Could not load the chip: {0}")]
+    LoadError(#[from] ModelConstructionError),
+    #[error("No chip has been loaded")]
+    NoChip,
+    #[error("The script ran for more than {0} cycles")]
+    CycleLimit(usize),
+    #[error("The script ran for more than {0:?}")]
+    TimeLimit(std::time::Duration),
+    #[error("Line {0} of the program is not a 16-bit binary word")]
+    BadProgram(usize),
+    #[error("The chip has no `{0}` part whose memory can be loaded")]
+    NoMemory(String),
+    #[error("The chip has no memory word `{0}`")]
+    NoMemoryWord(String),
+    #[error("{value} does not fit in the {width} bits of `{pin}`")]
+    ValueTooWide {
+        pin: String,
+        value: i64,
+        width: usize,
+    },
+    #[error("Could not read the program: {0}")]
+    BadImage(ImageError),
+    #[error(transparent)]
+    SimulationError(#[from] SimulationError),
+    #[error("Comparison failure at line {line}: expected `{expected}`, found `{found}`")]
+    ComparisonFailure {
+        line: usize,
+        expected: String,
+        found: String,
+    },
+    #[error("The outputs differ from the builtin `{chip}`:\n{dump}")]
+    ReferenceMismatch { chip: String, dump: String },
+    #[error("Pin `{pin}` is not {expected}, but {found}")]
+    ExpectationFailed {
+        pin: String,
+        expected: i64,
+        found: i64,
+    },
+}
+
+impl ScriptErrorKind {
+    /// A code which stays the same for each kind of error, see [`crate::error`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScriptErrorKind::ParseError => "E0401",
+            ScriptErrorKind::IoError(_) => "E0100",
+            ScriptErrorKind::LoadError(e) => e.code(),
+            ScriptErrorKind::NoChip => "E0402",
+            ScriptErrorKind::CycleLimit(_) => "E0403",
+            ScriptErrorKind::TimeLimit(_) => "E0404",
+            ScriptErrorKind::BadProgram(_) => "E0405",
+            ScriptErrorKind::NoMemory(_) => "E0406",
+            ScriptErrorKind::BadImage(e) => e.code(),
+            ScriptErrorKind::SimulationError(e) => e.code(),
+            ScriptErrorKind::ComparisonFailure { .. } => "E0407",
+            ScriptErrorKind::ReferenceMismatch { .. } => "E0408",
+            ScriptErrorKind::ExpectationFailed { .. } => "E0409",
+            ScriptErrorKind::NoMemoryWord(_) => "E0410",
+            ScriptErrorKind::ValueTooWide { .. } => "E0411",
+        }
+    }
+}
+
+impl From<ImageError> for ScriptErrorKind {
+    fn from(e: ImageError) -> Self {
+        match e {
+            ImageError::Io(e) => ScriptErrorKind::IoError(e),
+            ImageError::BadLine(line) => ScriptErrorKind::BadProgram(line),
+            e => ScriptErrorKind::BadImage(e),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+/// How a column of the output table is printed, as in `out%B1.16.1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `%B`, binary digits with the most significant bit first
+    Binary,
+    /// `%D`, a decimal number. 16-bit pins are read in two's complement, like Hack words.
+    Decimal,
+    /// `%X`, hexadecimal digits
+    Hex,
+    /// `%S`, the value as text, which is only meaningful for `time`
+    String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputColumn {
+    pub name: String,
+    pub format: Format,
+    /// Spaces before the value
+    pub left: usize,
+    /// Characters taken up by the value
+    pub width: usize,
+    /// Spaces after the value
+    pub right: usize,
+}
+
+/// Bits of `value` in two's complement, least significant bit first
+pub(crate) fn to_bits(value: i64, width: usize) -> Vec<bool> {
+    (0..width).map(|i| value >> i.min(63) & 1 == 1).collect()
+}
+
+/// Whether `value` can be written in `width` bits, either unsigned or in two's complement
+pub(crate) fn fits(value: i64, width: usize) -> bool {
+    let width = width.min(64) as u32;
+    let value = value as i128;
+    value < 1 << width && value >= -(1 << width.saturating_sub(1))
+}
+
+/// The value of some bits, least significant bit first
+pub(crate) fn from_bits(bits: &[bool]) -> i64 {
+    let value = bits
+        .iter()
+        .rev()
+        .fold(0, |value, bit| value << 1 | *bit as i64);
+    if bits.len() == 16 {
+        value as u16 as i16 as i64
+    } else {
+        value
+    }
+}
+
+impl OutputColumn {
+    fn total_width(&self) -> usize {
+        self.left + self.width + self.right
+    }
+
+    /// The name of the column, centered and cut to fit in the column
+    pub fn header(&self) -> String {
+        let width = self.total_width();
+        let name = &self.name[..self.name.len().min(width)];
+        let left = (width - name.len()) / 2;
+        format!(
+            "{}{name}{}",
+            " ".repeat(left),
+            " ".repeat(width - left - name.len())
+        )
+    }
+
+    /// Formats the bits of a pin, least significant bit first
+    pub fn cell(&self, bits: &[bool]) -> String {
+        let unsigned = bits
+            .iter()
+            .take(64)
+            .rev()
+            .fold(0u64, |value, bit| value << 1 | *bit as u64);
+        let value = match self.format {
+            Format::Binary => format!("{unsigned:0width$b}", width = self.width),
+            Format::Hex => format!("{unsigned:0width$X}", width = self.width),
+            Format::Decimal | Format::String => {
+                format!("{:>width$}", from_bits(bits), width = self.width)
+            }
+        };
+        self.pad(&value)
+    }
+
+    /// Formats a value which does not come from a pin, such as the time
+    pub fn text(&self, text: &str) -> String {
+        self.pad(&format!("{text:<width$}", width = self.width))
+    }
+
+    fn pad(&self, value: &str) -> String {
+        // values which do not fit are cut to their least significant digits
+        let value = &value[value.len().saturating_sub(self.width)..];
+        format!("{}{value}{}", " ".repeat(self.left), " ".repeat(self.right))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn column(format: Format, left: usize, width: usize, right: usize) -> OutputColumn {
+        OutputColumn {
+            name: "sel".to_string(),
+            format,
+            left,
+            width,
+            right,
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        assert_eq!(column(Format::Binary, 2, 2, 2).header(), " sel  ");
+        assert_eq!(column(Format::Binary, 0, 2, 0).header(), "se");
+    }
+
+    #[test]
+    fn test_cell() {
+        let bits = to_bits(-2, 16);
+        assert_eq!(
+            column(Format::Binary, 1, 16, 1).cell(&bits),
+            " 1111111111111110 "
+        );
+        assert_eq!(column(Format::Decimal, 1, 6, 1).cell(&bits), "     -2 ");
+        assert_eq!(column(Format::Hex, 0, 4, 0).cell(&bits), "FFFE");
+        assert_eq!(
+            column(Format::Binary, 2, 2, 2).cell(&[true, false]),
+            "  01  "
+        );
+        assert_eq!(column(Format::String, 1, 4, 1).text("0+"), " 0+   ");
+    }
+}
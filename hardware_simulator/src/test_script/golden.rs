@@ -0,0 +1,110 @@
+//! Checks the tables written by test scripts against `.out` files saved from the official tools.
+//! Unlike `compare-to`, every differing line is reported, which makes a change in how chips are
+//! evaluated, such as the order of bits or the phases of the clock, easy to spot.
+
+use super::error::ScriptError;
+use super::parser::parse_script;
+use super::runner::TestRunner;
+use crate::grade::find_scripts;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A line of the output table which differs from the stored one. A missing line is `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineDiff {
+    /// The line number, starting at 1
+    pub line: usize,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+}
+
+impl Display for LineDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let show = |x: &Option<String>| {
+            x.as_ref()
+                .map_or("nothing".to_string(), |x| format!("`{x}`"))
+        };
+        write!(
+            f,
+            "line {}: expected {}, found {}",
+            self.line,
+            show(&self.expected),
+            show(&self.found)
+        )
+    }
+}
+
+/// Every line which differs between two tables
+pub fn diff_lines(expected: &str, found: &str) -> Vec<LineDiff> {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let found = found.lines().collect::<Vec<_>>();
+    (0..expected.len().max(found.len()))
+        .filter_map(|i| {
+            let (expected, found) = (expected.get(i), found.get(i));
+            (expected != found).then(|| LineDiff {
+                line: i + 1,
+                expected: expected.map(|x| x.to_string()),
+                found: found.map(|x| x.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Runs a script to the end, ignoring its `compare-to` command, and diffs its table with the
+/// stored one
+pub fn run_golden(script: &Path, golden: &Path) -> Result<Vec<LineDiff>, ScriptError> {
+    let io_error = |e: io::Error| ScriptError {
+        line: 0,
+        column: 0,
+        kind: e.into(),
+    };
+    let source = fs::read_to_string(script).map_err(io_error)?;
+    let expected = fs::read_to_string(golden).map_err(io_error)?;
+    let mut runner =
+        TestRunner::new(script.parent().unwrap_or(Path::new("."))).without_comparison();
+    runner.run(&parse_script(&source)?)?;
+    Ok(diff_lines(&expected, runner.output()))
+}
+
+/// Pairs every script in `scripts` with the file of the same name in `golden`, skipping scripts
+/// with no stored output
+pub fn find_golden(scripts: &Path, golden: &Path) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    Ok(find_scripts(scripts)?
+        .into_iter()
+        .filter_map(|script| {
+            let stored = golden.join(script.with_extension("out").file_name()?);
+            stored.is_file().then_some((script, stored))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(diff_lines("a\nb\n", "a\nb"), []);
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nx"),
+            [
+                LineDiff {
+                    line: 2,
+                    expected: Some("b".to_string()),
+                    found: Some("x".to_string()),
+                },
+                LineDiff {
+                    line: 3,
+                    expected: Some("c".to_string()),
+                    found: None,
+                },
+            ]
+        );
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nx").last().unwrap().to_string(),
+            "line 3: expected `c`, found nothing"
+        );
+    }
+}
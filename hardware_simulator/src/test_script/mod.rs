@@ -0,0 +1,82 @@
+//! Test scripts in the `.tst` format used by the course tools. A script loads a chip, sets its
+//! inputs, drives the clock and writes the values of chosen pins to a table, one row per
+//! `output` command.
+
+mod coverage;
+mod error;
+mod format;
+mod golden;
+mod parser;
+mod runner;
+
+pub use coverage::{fault_coverage, toggle_coverage, FaultReport};
+pub use error::{ScriptError, ScriptErrorKind};
+pub use format::{Format, OutputColumn};
+pub use golden::{diff_lines, find_golden, run_golden, LineDiff};
+pub use parser::parse_script;
+pub(crate) use runner::run_script_file;
+pub use runner::{run_script, run_script_with, Limits, RunControl, StepResult, TestRunner};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    pub commands: Vec<Command>,
+}
+
+/// A single command, along with where it starts in the script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub line: u32,
+    pub column: usize,
+    pub kind: CommandKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    /// Loads a chip from an `.hdl` file, or a program into the `ROM32K` of the current chip from a
+    /// memory image such as a `.hack` file, see [`crate::image`]
+    Load(String),
+    /// Loads a memory image such as a `.hack` file into the memory of every part of the given
+    /// chip, as in `ROM32K load Program.hack`. With an address, as in `RAM16K load Stack.bin 256`,
+    /// the image is written from that address on and the rest of the memory is kept.
+    LoadMemory {
+        chip: String,
+        path: String,
+        address: Option<usize>,
+    },
+    /// Writes the memory of the first part of the given chip to an image, in the format given by
+    /// the extension of the file, as in `RAM16K dump State.hack`. With a range, as in
+    /// `RAM16K dump Stack.hack 256 272`, only the words from the first address up to the second
+    /// are written. This is not part of the official format.
+    DumpMemory {
+        chip: String,
+        path: String,
+        range: Option<(usize, usize)>,
+    },
+    /// Writes the output table to a file relative to the script
+    OutputFile(String),
+    /// Compares every line of the output table with a file relative to the script
+    CompareTo(String),
+    OutputList(Vec<OutputColumn>),
+    /// Sets an input pin, or a word of the memory of a part, as in `set RAM16K[5] 100`
+    Set {
+        pin: String,
+        value: i64,
+    },
+    Eval,
+    Tick,
+    Tock,
+    Output,
+    Echo(String),
+    ClearEcho,
+    /// Runs the body `count` times, or forever if no count is given
+    Repeat {
+        count: Option<usize>,
+        body: Vec<Command>,
+    },
+    /// Fails the script unless the pin has the given value. This is not part of the official
+    /// format, and allows writing tests which do not need a separate comparison file.
+    Expect {
+        pin: String,
+        value: i64,
+    },
+}
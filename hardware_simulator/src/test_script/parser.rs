@@ -0,0 +1,349 @@
+use super::error::{ScriptError, ScriptErrorKind};
+use super::format::{Format, OutputColumn};
+use super::{Command, CommandKind, Script};
+use crate::model::parser::symbols::{generic_space0, spaced};
+use crate::Span;
+use nom::branch::alt;
+use nom::bytes::complete::{take_while, take_while1};
+use nom::character::complete::{char, digit1, one_of};
+use nom::combinator::{map, map_res, opt, recognize, value};
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+use nom::Parser;
+use nom_supreme::error::ErrorTree;
+use nom_supreme::tag::complete::tag;
+
+type PResult<'a, O> = nom::IResult<Span<'a>, O, ErrorTree<Span<'a>>>;
+
+fn word(arg: Span) -> PResult<String> {
+    spaced(take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'))
+        .map(|x: Span| x.to_string())
+        .parse(arg)
+}
+
+/// The name of a pin, or of a word of a memory such as `RAM16K[5]`
+fn target_name(arg: Span) -> PResult<Span> {
+    recognize(pair(
+        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+        opt(delimited(char('['), digit1, char(']'))),
+    ))(arg)
+}
+
+fn target(arg: Span) -> PResult<String> {
+    spaced(target_name).map(|x: Span| x.to_string()).parse(arg)
+}
+
+fn path(arg: Span) -> PResult<String> {
+    spaced(take_while1(|c: char| {
+        c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '/')
+    }))
+    .map(|x: Span| x.to_string())
+    .parse(arg)
+}
+
+fn string(arg: Span) -> PResult<String> {
+    spaced(delimited(char('"'), take_while(|c| c != '"'), char('"')))
+        .map(|x: Span| x.to_string())
+        .parse(arg)
+}
+
+fn number(arg: Span) -> PResult<usize> {
+    spaced(map_res(digit1, |x: Span| x.parse::<usize>()))(arg)
+}
+
+fn radix(prefix: &'static str, radix: u32) -> impl FnMut(Span) -> PResult<i64> {
+    move |arg| {
+        preceded(
+            tag(prefix),
+            map_res(take_while1(|c: char| c.is_digit(radix)), move |x: Span| {
+                i64::from_str_radix(&x, radix)
+            }),
+        )(arg)
+    }
+}
+
+fn decimal(arg: Span) -> PResult<i64> {
+    map_res(recognize(pair(opt(char('-')), digit1)), |x: Span| {
+        x.parse::<i64>()
+    })(arg)
+}
+
+/// A literal such as `%B0101`, `%X1F`, `%D-3` or `-3`
+fn literal(arg: Span) -> PResult<i64> {
+    spaced(alt((
+        radix("%B", 2),
+        radix("%X", 16),
+        preceded(tag("%D"), decimal),
+        decimal,
+    )))(arg)
+}
+
+fn output_column(arg: Span) -> PResult<OutputColumn> {
+    let format = alt((
+        value(Format::Binary, char('B')),
+        value(Format::Decimal, char('D')),
+        value(Format::Hex, char('X')),
+        value(Format::String, char('S')),
+    ));
+    let size = || map_res(digit1, |x: Span| x.parse::<usize>());
+    spaced(tuple((
+        target_name,
+        preceded(char('%'), format),
+        size(),
+        preceded(char('.'), size()),
+        preceded(char('.'), size()),
+    )))
+    .map(|(name, format, left, width, right)| OutputColumn {
+        name: name.to_string(),
+        format,
+        left,
+        width,
+        right,
+    })
+    .parse(arg)
+}
+
+fn terminator(arg: Span) -> PResult<char> {
+    spaced(one_of(",;!"))(arg)
+}
+
+fn simple(name: &'static str, kind: CommandKind) -> impl FnMut(Span) -> PResult<CommandKind> {
+    move |arg| value(kind.clone(), terminated(tag(name), terminator))(arg)
+}
+
+fn command_kind(arg: Span) -> PResult<CommandKind> {
+    alt((
+        map(delimited(tag("load"), path, terminator), CommandKind::Load),
+        map(
+            delimited(tag("output-file"), path, terminator),
+            CommandKind::OutputFile,
+        ),
+        map(
+            delimited(tag("compare-to"), path, terminator),
+            CommandKind::CompareTo,
+        ),
+        map(
+            delimited(tag("echo"), string, terminator),
+            CommandKind::Echo,
+        ),
+        simple("clear-echo", CommandKind::ClearEcho),
+        map(
+            delimited(tag("output-list"), many1(output_column), terminator),
+            CommandKind::OutputList,
+        ),
+        map(
+            delimited(
+                tag("set"),
+                separated_pair(target, generic_space0, literal),
+                terminator,
+            ),
+            |(pin, value)| CommandKind::Set { pin, value },
+        ),
+        map(
+            delimited(
+                tag("expect"),
+                separated_pair(target, generic_space0, literal),
+                terminator,
+            ),
+            |(pin, value)| CommandKind::Expect { pin, value },
+        ),
+        map(
+            preceded(
+                tag("repeat"),
+                pair(
+                    opt(number),
+                    delimited(spaced(char('{')), many0(command), spaced(char('}'))),
+                ),
+            ),
+            |(count, body)| CommandKind::Repeat { count, body },
+        ),
+        simple("eval", CommandKind::Eval),
+        simple("tick", CommandKind::Tick),
+        simple("tock", CommandKind::Tock),
+        simple("output", CommandKind::Output),
+        // tried last, since the chip name could be any other command
+        map(
+            terminated(
+                tuple((word, preceded(tag("load"), path), opt(number))),
+                terminator,
+            ),
+            |(chip, path, address)| CommandKind::LoadMemory {
+                chip,
+                path,
+                address,
+            },
+        ),
+        map(
+            terminated(
+                tuple((word, preceded(tag("dump"), path), opt(pair(number, number)))),
+                terminator,
+            ),
+            |(chip, path, range)| CommandKind::DumpMemory { chip, path, range },
+        ),
+    ))(arg)
+}
+
+fn command(arg: Span) -> PResult<Command> {
+    let (arg, _) = generic_space0(arg)?;
+    let (line, column) = (arg.location_line(), arg.get_utf8_column());
+    command_kind
+        .map(|kind| Command { line, column, kind })
+        .parse(arg)
+}
+
+/// The position at which the parse failed
+fn error_location<'a>(error: &ErrorTree<Span<'a>>) -> Span<'a> {
+    match error {
+        ErrorTree::Base { location, .. } => *location,
+        ErrorTree::Stack { base, .. } => error_location(base),
+        ErrorTree::Alt(alternatives) => alternatives
+            .iter()
+            .map(error_location)
+            .max_by_key(|location| location.location_offset())
+            .unwrap(),
+    }
+}
+
+pub fn parse_script(source: &str) -> Result<Script, ScriptError> {
+    let mut remainder = Span::new(source);
+    let mut commands = Vec::new();
+    loop {
+        // skipping whitespace and comments never fails
+        remainder = generic_space0(remainder).map_or(remainder, |(x, _)| x);
+        if remainder.is_empty() {
+            return Ok(Script { commands });
+        }
+        match command(remainder) {
+            Ok((rest, command)) => {
+                commands.push(command);
+                remainder = rest;
+            }
+            Err(e) => {
+                let location = match e {
+                    nom::Err::Error(e) | nom::Err::Failure(e) => error_location(&e),
+                    nom::Err::Incomplete(_) => remainder,
+                };
+                return Err(ScriptError {
+                    line: location.location_line(),
+                    column: location.get_utf8_column(),
+                    kind: ScriptErrorKind::ParseError,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let source = "\
+// a comment
+load Not.hdl,
+output-list in%B3.1.3 out%X1.4.1;
+
+echo \"Testing Not\";
+set in %B1, eval, output;
+repeat 2 {
+    tick, tock;
+}
+expect out -1;";
+        let script = parse_script(source).unwrap();
+        let kinds = script
+            .commands
+            .iter()
+            .map(|x| x.kind.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                CommandKind::Load("Not.hdl".to_string()),
+                CommandKind::OutputList(vec![
+                    OutputColumn {
+                        name: "in".to_string(),
+                        format: Format::Binary,
+                        left: 3,
+                        width: 1,
+                        right: 3
+                    },
+                    OutputColumn {
+                        name: "out".to_string(),
+                        format: Format::Hex,
+                        left: 1,
+                        width: 4,
+                        right: 1
+                    },
+                ]),
+                CommandKind::Echo("Testing Not".to_string()),
+                CommandKind::Set {
+                    pin: "in".to_string(),
+                    value: 1
+                },
+                CommandKind::Eval,
+                CommandKind::Output,
+                CommandKind::Repeat {
+                    count: Some(2),
+                    body: vec![
+                        Command {
+                            line: 8,
+                            column: 5,
+                            kind: CommandKind::Tick
+                        },
+                        Command {
+                            line: 8,
+                            column: 11,
+                            kind: CommandKind::Tock
+                        },
+                    ]
+                },
+                CommandKind::Expect {
+                    pin: "out".to_string(),
+                    value: -1
+                },
+            ]
+        );
+        assert_eq!((script.commands[3].line, script.commands[3].column), (6, 1));
+    }
+
+    #[test]
+    fn test_parse_load_memory() {
+        let script = parse_script("load Computer.hdl,\nROM32K load Max.hack, set load 1;").unwrap();
+        assert_eq!(
+            script.commands[1].kind,
+            CommandKind::LoadMemory {
+                chip: "ROM32K".to_string(),
+                path: "Max.hack".to_string(),
+                address: None,
+            }
+        );
+        assert!(matches!(script.commands[2].kind, CommandKind::Set { .. }));
+
+        let script =
+            parse_script("RAM16K load Stack.bin 256, RAM16K dump Stack.hack 256 272;").unwrap();
+        assert_eq!(
+            script.commands[0].kind,
+            CommandKind::LoadMemory {
+                chip: "RAM16K".to_string(),
+                path: "Stack.bin".to_string(),
+                address: Some(256),
+            }
+        );
+        assert_eq!(
+            script.commands[1].kind,
+            CommandKind::DumpMemory {
+                chip: "RAM16K".to_string(),
+                path: "Stack.hack".to_string(),
+                range: Some((256, 272)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let error = parse_script("load Not.hdl,\nset in %B2;").unwrap_err();
+        assert!(matches!(error.kind, ScriptErrorKind::ParseError));
+        assert_eq!((error.line, error.column), (2, 10));
+    }
+}
@@ -0,0 +1,662 @@
+use super::error::{ScriptError, ScriptErrorKind};
+use super::format::{fits, from_bits, to_bits, OutputColumn};
+use super::parser::parse_script;
+use super::{Command, CommandKind, Script};
+use crate::image::{read_image, write_image, ImageFormat};
+use crate::model::chip::build_ctx::{BuiltinPolicy, ChipBuilder};
+use crate::model::chip::fault::StuckAt;
+use crate::model::dialect::Dialect;
+use crate::simulator::{throttle, SimulationError, Simulator};
+use crate::trace::Trace;
+use crate::vectors::show_bits;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Runs test scripts, loading chips relative to a directory
+pub struct TestRunner {
+    dir: PathBuf,
+    builder: ChipBuilder,
+    simulator: Option<Simulator>,
+    output_list: Vec<OutputColumn>,
+    output: String,
+    output_file: Option<File>,
+    /// The lines of the comparison file, and how many of them have been checked
+    compare_to: Option<(Vec<String>, usize)>,
+    /// Whether `compare-to` commands are followed
+    compare: bool,
+    /// Whether `output-file` commands are followed
+    write_output: bool,
+    echo: Option<String>,
+    frames: Vec<Frame>,
+    limits: Limits,
+    /// Faults injected into every chip which is loaded
+    faults: Vec<StuckAt>,
+    /// Whether toggles are tracked in every chip which is loaded
+    track_toggles: bool,
+    /// Whether chips which share a name with a builtin are run next to it
+    differential: bool,
+    /// The builtin being run next to the current chip
+    reference: Option<Simulator>,
+    /// The pins of the current chip after every `eval`, `tick` and `tock`, if they are recorded
+    trace: Option<Trace>,
+    /// Full clock cycles run since the script was started
+    cycles: usize,
+    started: Instant,
+    /// Stops the script once it holds for the chip, checked after every command
+    halt: Option<Box<HaltCondition>>,
+    control: RunControl,
+    /// The frequency of the clock of every chip which is loaded
+    clock_frequency: Option<f64>,
+    /// Where chips are looked for when they are not next to the script
+    chip_dirs: Vec<PathBuf>,
+}
+
+/// Bounds on how long a script may run, so that a chip which never settles or a script which
+/// loops forever cannot hang the caller. Limits are checked between commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    pub cycles: Option<usize>,
+    pub time: Option<Duration>,
+}
+
+type HaltCondition = dyn FnMut(&Simulator) -> bool + Send;
+
+/// Pauses and resumes a [`TestRunner`] from elsewhere, such as the stop button of a UI on another
+/// thread. A paused runner finishes the command it is running, and then runs nothing until it is
+/// resumed.
+#[derive(Debug, Clone, Default)]
+pub struct RunControl {
+    paused: Arc<AtomicBool>,
+}
+
+impl RunControl {
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
+
+/// A list of commands being run, either the script itself or the body of a loop
+struct Frame {
+    body: Vec<Command>,
+    next: usize,
+    /// How many times the body is still to be run, including the current time. `None` for loops
+    /// which run forever.
+    remaining: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepResult {
+    /// The command was run
+    Ran(Command),
+    /// The script has no commands left
+    Finished,
+    /// The runner is paused, so nothing was run
+    Paused,
+    /// The command was run, and the halt condition held afterwards, so the rest of the script is
+    /// skipped
+    Halted(Command),
+}
+
+impl TestRunner {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            builder: ChipBuilder::new(),
+            simulator: None,
+            output_list: Vec::new(),
+            output: String::new(),
+            output_file: None,
+            compare_to: None,
+            compare: true,
+            write_output: true,
+            echo: None,
+            frames: Vec::new(),
+            limits: Limits::default(),
+            faults: Vec::new(),
+            track_toggles: false,
+            trace: None,
+            differential: false,
+            reference: None,
+            cycles: 0,
+            started: Instant::now(),
+            halt: None,
+            control: RunControl::default(),
+            clock_frequency: None,
+            chip_dirs: Vec::new(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Stops the script as soon as the condition holds for the chip after a command, as when a
+    /// program has reached its final loop. The rest of the script is skipped without an error.
+    pub fn with_halt_condition(
+        mut self,
+        halt: impl FnMut(&Simulator) -> bool + Send + 'static,
+    ) -> Self {
+        self.halt = Some(Box::new(halt));
+        self
+    }
+
+    /// Runs the clock of every chip the script loads at this many cycles per second at most, see
+    /// [`Simulator::set_clock_frequency`]. Fails at once if the frequency is not positive and
+    /// finite.
+    pub fn with_clock_frequency(mut self, hertz: f64) -> Result<Self, SimulationError> {
+        throttle(hertz)?;
+        self.clock_frequency = Some(hertz);
+        Ok(self)
+    }
+
+    /// Loads chips written in the given dialect of HDL
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.builder.set_dialect(dialect);
+        self
+    }
+
+    /// Chooses between builtins and chips loaded from HDL of the same name, see
+    /// [`ChipBuilder::set_builtin_policy`]
+    pub fn with_builtin_policy(mut self, policy: BuiltinPolicy) -> Self {
+        self.builder.set_builtin_policy(policy);
+        self
+    }
+
+    /// Uses the builtins for these parts of the chips the script loads, whatever the policy, see
+    /// [`ChipBuilder::use_builtins`]
+    pub fn with_builtin_parts<'a>(mut self, parts: impl IntoIterator<Item = &'a str>) -> Self {
+        self.builder.use_builtins(parts);
+        self
+    }
+
+    /// Looks for the chips the script loads in these directories, in order, when they are not in
+    /// the directory of the script. The parts of a chip are loaded from its own directory.
+    pub fn with_chip_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.chip_dirs = dirs;
+        self
+    }
+
+    /// Injects faults into every chip the script loads. Faults whose pin cannot be found are
+    /// ignored.
+    pub fn with_faults(mut self, faults: Vec<StuckAt>) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// Tracks which pins toggle in every chip the script loads, see [`Simulator::toggles`]
+    pub fn with_toggle_tracking(mut self) -> Self {
+        self.track_toggles = true;
+        self
+    }
+
+    /// Records the pins of the chip after every `eval`, `tick` and `tock`, see
+    /// [`trace`](Self::trace). Loading a chip starts a new trace.
+    pub fn with_trace(mut self) -> Self {
+        self.trace = Some(Trace::default());
+        self
+    }
+
+    /// Runs every chip loaded from HDL which shares its name with a builtin, such as a `RAM8`,
+    /// next to the builtin: both are given the same inputs and clock, and the script stops with
+    /// [`ScriptErrorKind::ReferenceMismatch`] as soon as their outputs differ.
+    pub fn with_differential(mut self) -> Self {
+        self.differential = true;
+        self
+    }
+
+    /// Ignores `compare-to` commands, so that a script runs to the end even if its table differs
+    /// from the comparison file
+    pub fn without_comparison(mut self) -> Self {
+        self.compare = false;
+        self
+    }
+
+    /// Ignores `output-file` and memory `dump` commands, so that files next to the script are left
+    /// alone
+    pub fn without_output_file(mut self) -> Self {
+        self.write_output = false;
+        self
+    }
+
+    /// The table written by the `output-list` and `output` commands so far
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// The message of the last `echo` command, unless it has been cleared
+    pub fn echo(&self) -> Option<&str> {
+        self.echo.as_deref()
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    pub fn simulator(&self) -> Option<&Simulator> {
+        self.simulator.as_ref()
+    }
+
+    /// A control which pauses and resumes this runner
+    pub fn control(&self) -> RunControl {
+        self.control.clone()
+    }
+
+    /// Runs a whole script. The runner keeps its state afterwards, so that further scripts continue
+    /// with the same chip. If the runner is paused, the script stops early, and
+    /// [`proceed`](Self::proceed) continues it once it is resumed.
+    pub fn run(&mut self, script: &Script) -> Result<(), ScriptError> {
+        self.start(script);
+        self.proceed().map(|_| ())
+    }
+
+    /// Runs the rest of the script started last, until it finishes, halts or is paused
+    pub fn proceed(&mut self) -> Result<StepResult, ScriptError> {
+        loop {
+            match self.step()? {
+                StepResult::Ran(_) => {}
+                result => return Ok(result),
+            }
+        }
+    }
+
+    /// Prepares a script to be run one command at a time through [`step`](Self::step), replacing
+    /// any script which has not finished yet
+    pub fn start(&mut self, script: &Script) {
+        self.frames = vec![Frame {
+            body: script.commands.clone(),
+            next: 0,
+            remaining: Some(1),
+        }];
+        self.cycles = 0;
+        self.started = Instant::now();
+        self.settle();
+    }
+
+    /// The command which the next call to [`step`](Self::step) runs
+    pub fn current(&self) -> Option<&Command> {
+        self.frames.last().map(|frame| &frame.body[frame.next])
+    }
+
+    /// Runs the next command of the script. After an error, the failing command is skipped, so
+    /// stepping again continues with the rest of the script.
+    pub fn step(&mut self) -> Result<StepResult, ScriptError> {
+        let command = match self.current() {
+            Some(command) => command.clone(),
+            None => return Ok(StepResult::Finished),
+        };
+        if self.control.is_paused() {
+            return Ok(StepResult::Paused);
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            frame.next += 1;
+        }
+        self.settle();
+
+        self.check_limits()
+            .and_then(|_| self.run_command(&command))
+            .map_err(|kind| ScriptError {
+                line: command.line,
+                column: command.column,
+                kind,
+            })?;
+        if let (Some(halt), Some(simulator)) = (self.halt.as_mut(), self.simulator.as_ref()) {
+            if halt(simulator) {
+                self.frames.clear();
+                return Ok(StepResult::Halted(command));
+            }
+        }
+        Ok(StepResult::Ran(command))
+    }
+
+    fn check_limits(&self) -> Result<(), ScriptErrorKind> {
+        match self.limits {
+            Limits {
+                cycles: Some(cycles),
+                ..
+            } if self.cycles >= cycles => Err(ScriptErrorKind::CycleLimit(cycles)),
+            Limits {
+                time: Some(time), ..
+            } if self.started.elapsed() >= time => Err(ScriptErrorKind::TimeLimit(time)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Moves through the ends and starts of loops, until the innermost frame points at a command
+    /// which can be run
+    fn settle(&mut self) {
+        while let Some(frame) = self.frames.last_mut() {
+            if frame.next == frame.body.len() {
+                match frame.remaining {
+                    Some(1) => {
+                        self.frames.pop();
+                    }
+                    _ => {
+                        frame.remaining = frame.remaining.map(|x| x - 1);
+                        frame.next = 0;
+                    }
+                }
+                continue;
+            }
+            let CommandKind::Repeat { count, body } = &frame.body[frame.next].kind else {
+                return;
+            };
+            frame.next += 1;
+            // an empty body would loop forever without running anything
+            if *count != Some(0) && !body.is_empty() {
+                let frame = Frame {
+                    body: body.clone(),
+                    next: 0,
+                    remaining: *count,
+                };
+                self.frames.push(frame);
+            }
+        }
+    }
+
+    fn simulator_mut(&mut self) -> Result<&mut Simulator, ScriptErrorKind> {
+        self.simulator.as_mut().ok_or(ScriptErrorKind::NoChip)
+    }
+
+    fn pin_width(&self, pin: &str) -> Result<usize, ScriptErrorKind> {
+        let simulator = self.simulator.as_ref().ok_or(ScriptErrorKind::NoChip)?;
+        Ok(simulator.get(pin)?.len())
+    }
+
+    fn run_command(&mut self, command: &Command) -> Result<(), ScriptErrorKind> {
+        match &command.kind {
+            CommandKind::Load(file) if ImageFormat::from_extension(Path::new(file)).is_some() => {
+                self.load_memory("ROM32K", file, None)?;
+            }
+            CommandKind::LoadMemory {
+                chip,
+                path,
+                address,
+            } => self.load_memory(chip, path, *address)?,
+            CommandKind::DumpMemory { .. } if !self.write_output => {}
+            CommandKind::DumpMemory { chip, path, range } => {
+                let simulator = self.simulator.as_ref().ok_or(ScriptErrorKind::NoChip)?;
+                let memory = simulator
+                    .memory(chip)
+                    .ok_or_else(|| ScriptErrorKind::NoMemory(chip.clone()))?;
+                let (start, end) = range.unwrap_or((0, memory.len()));
+                let end = end.min(memory.len());
+                write_image(self.dir.join(path), &memory[start.min(end)..end])?;
+            }
+            CommandKind::Load(file) => {
+                let path = self.chip_path(file);
+                self.builder.add_hdl(&path)?;
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                self.reference = None;
+                let mut chip = match self.builder.hdl_chip(&name) {
+                    Some(chip) if self.differential => {
+                        self.reference = self.builder.builtin_chip(&name).map(Simulator::new);
+                        chip
+                    }
+                    _ => self.builder.resolve_chip(&name)?,
+                };
+                for fault in self.faults.iter() {
+                    chip.inject_fault(fault);
+                }
+                let mut simulator = Simulator::new(chip);
+                simulator.set_clock_frequency(self.clock_frequency)?;
+                if self.track_toggles {
+                    simulator.track_toggles();
+                }
+                self.simulator = Some(simulator);
+                if let Some(trace) = self.trace.as_mut() {
+                    *trace = Trace::default();
+                }
+            }
+            CommandKind::OutputFile(_) if !self.write_output => {}
+            CommandKind::OutputFile(file) => {
+                self.output_file = Some(File::create(self.dir.join(file))?);
+            }
+            CommandKind::CompareTo(_) if !self.compare => {}
+            CommandKind::CompareTo(file) => {
+                let lines = fs::read_to_string(self.dir.join(file))?
+                    .lines()
+                    .map(String::from)
+                    .collect();
+                self.compare_to = Some((lines, 0));
+            }
+            CommandKind::OutputList(columns) => {
+                self.output_list = columns.clone();
+                let header = self.row(|column| Ok(column.header()))?;
+                self.emit(header)?;
+            }
+            CommandKind::Set { pin, value } => match memory_word(pin) {
+                Some((chip, address)) => {
+                    let simulator = self.simulator_mut()?;
+                    if simulator.peek(chip, address).is_none() {
+                        return Err(ScriptErrorKind::NoMemoryWord(pin.clone()));
+                    }
+                    check_fits(pin, *value, 16)?;
+                    simulator.poke(chip, address, *value as u16);
+                    if let Some(reference) = self.reference.as_mut() {
+                        reference.poke(chip, address, *value as u16);
+                    }
+                }
+                None => {
+                    let width = self.pin_width(pin)?;
+                    check_fits(pin, *value, width)?;
+                    let bits = to_bits(*value, width);
+                    self.simulator_mut()?.set(pin, &bits)?;
+                    if let Some(reference) = self.reference.as_mut() {
+                        reference.set(pin, &bits)?;
+                    }
+                }
+            },
+            CommandKind::Eval => {
+                self.drive(Simulator::eval)?;
+            }
+            CommandKind::Tick => {
+                self.drive(Simulator::tick)?;
+            }
+            CommandKind::Tock => {
+                self.drive(Simulator::tock)?;
+                self.cycles += 1;
+            }
+            CommandKind::Output => {
+                let simulator = self.simulator.as_ref().ok_or(ScriptErrorKind::NoChip)?;
+                let row = self.row(|column| {
+                    Ok(if column.name == "time" {
+                        column.text(&simulator.clock().to_string())
+                    } else {
+                        column.cell(&read(simulator, &column.name)?)
+                    })
+                })?;
+                self.emit(row)?;
+            }
+            CommandKind::Echo(message) => self.echo = Some(message.clone()),
+            CommandKind::ClearEcho => self.echo = None,
+            CommandKind::Repeat { .. } => unreachable!("loops are entered by settle"),
+            CommandKind::Expect { pin, value } => {
+                let simulator = self.simulator.as_ref().ok_or(ScriptErrorKind::NoChip)?;
+                let bits = read(simulator, pin)?;
+                if bits != to_bits(*value, bits.len()) {
+                    return Err(ScriptErrorKind::ExpectationFailed {
+                        pin: pin.clone(),
+                        expected: *value,
+                        found: from_bits(&bits),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The file of a chip, next to the script or else in the first of the chip directories which
+    /// has it
+    fn chip_path(&self, file: &str) -> PathBuf {
+        let path = self.dir.join(file);
+        if path.is_file() {
+            return path;
+        }
+        self.chip_dirs
+            .iter()
+            .map(|x| x.join(file))
+            .find(|x| x.is_file())
+            .unwrap_or(path)
+    }
+
+    fn load_memory(
+        &mut self,
+        chip: &str,
+        file: &str,
+        address: Option<usize>,
+    ) -> Result<(), ScriptErrorKind> {
+        let words = read_image(self.dir.join(file))?;
+        let load = |simulator: &mut Simulator| match address {
+            Some(address) => simulator.write_memory(chip, address, &words),
+            None => simulator.load_memory(chip, &words),
+        };
+        if let Some(reference) = self.reference.as_mut() {
+            load(reference);
+        }
+        if load(self.simulator_mut()?) == 0 {
+            return Err(ScriptErrorKind::NoMemory(chip.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Drives the chip, and the builtin next to it if there is one, checking that their outputs
+    /// agree
+    fn drive(&mut self, step: fn(&mut Simulator) -> &[bool]) -> Result<(), ScriptErrorKind> {
+        step(self.simulator_mut()?);
+        self.record();
+        let Some(reference) = self.reference.as_mut() else {
+            return Ok(());
+        };
+        step(reference);
+        let simulator = self.simulator.as_ref().ok_or(ScriptErrorKind::NoChip)?;
+        if simulator.outputs() == reference.outputs() {
+            return Ok(());
+        }
+        let mut dump = String::new();
+        for pin in reference.interface().pins() {
+            let (found, expected) = (simulator.get(pin.name)?, reference.get(pin.name)?);
+            let marker = if found == expected { ' ' } else { '*' };
+            dump.push_str(&format!(
+                "{marker} {} = {} (builtin: {})\n",
+                pin.name,
+                show_bits(found),
+                show_bits(expected)
+            ));
+        }
+        Err(ScriptErrorKind::ReferenceMismatch {
+            chip: reference.interface().name.to_string(),
+            dump,
+        })
+    }
+
+    fn record(&mut self) {
+        if let (Some(trace), Some(simulator)) = (self.trace.as_mut(), self.simulator.as_ref()) {
+            trace.record(simulator);
+        }
+    }
+
+    /// Adds a line to the output table, writing it to the output file and checking it against the
+    /// comparison file
+    fn emit(&mut self, line: String) -> Result<(), ScriptErrorKind> {
+        if let Some(file) = self.output_file.as_mut() {
+            writeln!(file, "{line}")?;
+        }
+        if let Some((lines, checked)) = self.compare_to.as_mut() {
+            let expected = lines.get(*checked).map_or("", String::as_str);
+            if expected != line {
+                return Err(ScriptErrorKind::ComparisonFailure {
+                    line: *checked + 1,
+                    expected: expected.to_string(),
+                    found: line,
+                });
+            }
+            *checked += 1;
+        }
+        self.output.push_str(&line);
+        self.output.push('\n');
+        Ok(())
+    }
+
+    fn row(
+        &self,
+        mut cell: impl FnMut(&OutputColumn) -> Result<String, ScriptErrorKind>,
+    ) -> Result<String, ScriptErrorKind> {
+        let mut row = String::from("|");
+        for column in self.output_list.iter() {
+            row.push_str(&cell(column)?);
+            row.push('|');
+        }
+        Ok(row)
+    }
+}
+
+/// Splits a word of a memory, such as `RAM16K[5]`, into the name of the chip and the address
+fn memory_word(name: &str) -> Option<(&str, usize)> {
+    let (chip, address) = name.strip_suffix(']')?.split_once('[')?;
+    Some((chip, address.parse().ok()?))
+}
+
+/// Fails unless the value set to a pin, or a word of a memory, fits in its bits
+fn check_fits(pin: &str, value: i64, width: usize) -> Result<(), ScriptErrorKind> {
+    if fits(value, width) {
+        Ok(())
+    } else {
+        Err(ScriptErrorKind::ValueTooWide {
+            pin: pin.to_string(),
+            value,
+            width,
+        })
+    }
+}
+
+/// The value of a pin, or of a word of a memory
+fn read(simulator: &Simulator, name: &str) -> Result<Vec<bool>, ScriptErrorKind> {
+    match memory_word(name) {
+        Some((chip, address)) => simulator
+            .peek(chip, address)
+            .map(|x| to_bits(x as i64, 16))
+            .ok_or_else(|| ScriptErrorKind::NoMemoryWord(name.to_string())),
+        None => Ok(simulator.get(name)?.to_vec()),
+    }
+}
+
+/// Runs the script at `path` with a runner for its directory, set up by `configure`, and returns
+/// the output table
+pub(crate) fn run_script_file(
+    path: &Path,
+    configure: impl FnOnce(TestRunner) -> TestRunner,
+) -> Result<String, ScriptError> {
+    let source = fs::read_to_string(path).map_err(|e| ScriptError {
+        line: 0,
+        column: 0,
+        kind: e.into(),
+    })?;
+    let script = parse_script(&source)?;
+    let mut runner = configure(TestRunner::new(path.parent().unwrap_or(Path::new("."))));
+    runner.run(&script)?;
+    Ok(runner.output)
+}
+
+/// Runs the script at `path`, loading chips from the same directory, and returns the output
+/// table
+pub fn run_script(path: impl AsRef<Path>) -> Result<String, ScriptError> {
+    run_script_with(path, Limits::default())
+}
+
+/// Like [`run_script`], but stops the script once it exceeds the limits
+pub fn run_script_with(path: impl AsRef<Path>, limits: Limits) -> Result<String, ScriptError> {
+    run_script_file(path.as_ref(), |runner| runner.with_limits(limits))
+}
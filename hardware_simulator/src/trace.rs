@@ -0,0 +1,218 @@
+//! Traces of the pins of a chip over a simulation, and a comparison of two traces which points at
+//! the first step where they diverge, such as between a student's chip and a reference.
+
+use crate::simulator::Simulator;
+use crate::vectors::show_bits;
+use std::fmt::{self, Display, Formatter};
+
+/// The values of the pins of a chip, recorded step by step
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Trace {
+    /// The names of the recorded pins, inputs first
+    pub signals: Vec<String>,
+    pub steps: Vec<TraceStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The clock at the time of the step, as in the `time` column of test scripts
+    pub time: String,
+    /// A value for every signal, least significant bit first
+    pub values: Vec<Vec<bool>>,
+}
+
+impl Trace {
+    /// Adds the current value of every pin of the simulator's chip. The pins are taken from the
+    /// first step recorded.
+    pub fn record(&mut self, simulator: &Simulator) {
+        if self.steps.is_empty() {
+            self.signals = simulator
+                .interface()
+                .pins()
+                .map(|x| x.name.to_string())
+                .collect();
+        }
+        let values = self
+            .signals
+            .iter()
+            .map(|name| simulator.get(name).map_or(Vec::new(), |x| x.to_vec()))
+            .collect();
+        self.steps.push(TraceStep {
+            time: simulator.clock().to_string(),
+            values,
+        });
+    }
+
+    fn value(&self, step: usize, signal: &str) -> Option<&[bool]> {
+        let index = self.signals.iter().position(|x| x == signal)?;
+        Some(self.steps.get(step)?.values[index].as_slice())
+    }
+}
+
+/// A signal whose value differs between two traces. A side is `None` if its trace has already
+/// ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignalDiff {
+    pub signal: String,
+    pub left: Option<Vec<bool>>,
+    pub right: Option<Vec<bool>>,
+}
+
+/// Where two traces first differ, with the steps around it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub step: usize,
+    pub time: String,
+    pub signals: Vec<SignalDiff>,
+    /// The steps around the divergence, from both traces
+    pub context: Vec<ContextRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextRow {
+    pub step: usize,
+    pub time: String,
+    /// The values of the signals of both traces, in binary, or empty where a trace has ended
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+}
+
+/// Compares the signals which both traces have, step by step. Returns `None` if the traces are
+/// the same, and otherwise the first step where they differ, with up to `context` steps before
+/// and after it.
+pub fn diff_traces(left: &Trace, right: &Trace, context: usize) -> Option<Divergence> {
+    let signals = left
+        .signals
+        .iter()
+        .filter(|x| right.signals.contains(x))
+        .collect::<Vec<_>>();
+    let differences = |step: usize| {
+        signals
+            .iter()
+            .filter_map(|signal| {
+                let (a, b) = (left.value(step, signal), right.value(step, signal));
+                (a != b).then(|| SignalDiff {
+                    signal: signal.to_string(),
+                    left: a.map(<[bool]>::to_vec),
+                    right: b.map(<[bool]>::to_vec),
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+    let steps = left.steps.len().max(right.steps.len());
+    let (step, diffs) = (0..steps)
+        .map(|step| (step, differences(step)))
+        .find(|(_, diffs)| !diffs.is_empty())?;
+
+    let time = |step: usize| {
+        left.steps
+            .get(step)
+            .or(right.steps.get(step))
+            .map_or(String::new(), |x| x.time.clone())
+    };
+    let row = |trace: &Trace, step| {
+        signals
+            .iter()
+            .map(|signal| trace.value(step, signal).map_or(String::new(), show_bits))
+            .collect()
+    };
+    Some(Divergence {
+        step,
+        time: time(step),
+        signals: diffs,
+        context: (step.saturating_sub(context)..(step + context + 1).min(steps))
+            .map(|step| ContextRow {
+                step,
+                time: time(step),
+                left: row(left, step),
+                right: row(right, step),
+            })
+            .collect(),
+    })
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let show = |x: &Option<Vec<bool>>| x.as_deref().map_or("(ended)".to_string(), show_bits);
+        writeln!(
+            f,
+            "The traces diverge at step {} (time {}):",
+            self.step, self.time
+        )?;
+        for diff in self.signals.iter() {
+            writeln!(
+                f,
+                "    {}: {} != {}",
+                diff.signal,
+                show(&diff.left),
+                show(&diff.right)
+            )?;
+        }
+        for row in self.context.iter() {
+            let marker = if row.step == self.step { '>' } else { ' ' };
+            writeln!(
+                f,
+                "{marker} {:>4} {:<5} | {} | {}",
+                row.step,
+                row.time,
+                row.left.join(" "),
+                row.right.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trace(outputs: &[bool]) -> Trace {
+        Trace {
+            signals: vec!["in".to_string(), "out".to_string()],
+            steps: outputs
+                .iter()
+                .enumerate()
+                .map(|(i, out)| TraceStep {
+                    time: i.to_string(),
+                    values: vec![vec![i % 2 == 1], vec![*out]],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_traces() {
+        let left = trace(&[false, true, false, true]);
+        assert_eq!(diff_traces(&left, &left, 1), None);
+
+        let divergence = diff_traces(&left, &trace(&[false, true, true, true]), 1).unwrap();
+        assert_eq!((divergence.step, divergence.time.as_str()), (2, "2"));
+        assert_eq!(
+            divergence.signals,
+            [SignalDiff {
+                signal: "out".to_string(),
+                left: Some(vec![false]),
+                right: Some(vec![true]),
+            }]
+        );
+        assert_eq!(
+            divergence.to_string(),
+            "\
+The traces diverge at step 2 (time 2):
+    out: 0 != 1
+     1 1     | 1 1 | 1 1
+>    2 2     | 0 0 | 0 1
+     3 3     | 1 1 | 1 1
+"
+        );
+    }
+
+    #[test]
+    fn test_diff_lengths() {
+        let divergence = diff_traces(&trace(&[true, true]), &trace(&[true]), 0).unwrap();
+        assert_eq!(divergence.step, 1);
+        assert_eq!(divergence.signals[0].right, None);
+        assert!(divergence.to_string().contains("in: 1 != (ended)"));
+    }
+}
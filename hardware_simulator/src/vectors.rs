@@ -0,0 +1,327 @@
+//! Input vectors for a chip's interface, and helpers which compare a chip against a reference
+//! implementation, such as a builtin, on many generated inputs or drive it with pseudo-random
+//! patterns.
+
+use crate::initial_state::StateRng;
+use crate::model::chip::Chip;
+use crate::model::{Direction, Interface};
+use crate::simulator::{SimulationError, Simulator};
+use std::fmt::{self, Display, Formatter};
+use thiserror::Error;
+
+/// A value for every input pin of a chip, in declaration order. Bits are least significant first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputVector {
+    pub pins: Vec<(String, Vec<bool>)>,
+}
+
+impl InputVector {
+    /// Every input of the interface set to zero
+    pub fn zero(interface: &Interface) -> Self {
+        Self::from_fn(interface, || false)
+    }
+
+    /// Every input of the interface set to pseudo-random bits drawn from `rng`
+    pub fn arbitrary(interface: &Interface, rng: &mut StateRng) -> Self {
+        Self::from_fn(interface, || rng.next_bool())
+    }
+
+    fn from_fn(interface: &Interface, mut bit: impl FnMut() -> bool) -> Self {
+        Self {
+            pins: interface
+                .inputs()
+                .map(|pin| {
+                    (
+                        pin.name.to_string(),
+                        (0..pin.width).map(|_| bit()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Sets the inputs of a simulator, without evaluating it
+    pub fn apply(&self, simulator: &mut Simulator) -> Result<(), SimulationError> {
+        self.pins
+            .iter()
+            .try_for_each(|(name, bits)| simulator.set(name, bits))
+    }
+
+    /// Simpler vectors to try when this one shows a failure: each pin cleared as a whole, and then
+    /// each set bit cleared on its own. Repeatedly shrinking ends at a vector whose every
+    /// remaining bit is needed for the failure.
+    pub fn shrink(&self) -> Vec<InputVector> {
+        let mut simpler = Vec::new();
+        for (i, (_, bits)) in self.pins.iter().enumerate() {
+            if bits.iter().filter(|x| **x).count() > 1 {
+                let mut vector = self.clone();
+                vector.pins[i].1.fill(false);
+                simpler.push(vector);
+            }
+        }
+        for (i, (_, bits)) in self.pins.iter().enumerate() {
+            for (j, _) in bits.iter().enumerate().filter(|(_, x)| **x) {
+                let mut vector = self.clone();
+                vector.pins[i].1[j] = false;
+                simpler.push(vector);
+            }
+        }
+        simpler
+    }
+}
+
+/// Prints bits most significant first, as in test scripts
+pub(crate) fn show_bits(bits: &[bool]) -> String {
+    bits.iter()
+        .rev()
+        .map(|x| if *x { '1' } else { '0' })
+        .collect()
+}
+
+impl Display for InputVector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let pins = self
+            .pins
+            .iter()
+            .map(|(name, bits)| format!("{name}={}", show_bits(bits)))
+            .collect::<Vec<_>>();
+        write!(f, "{}", pins.join(" "))
+    }
+}
+
+/// A 64-bit Galois linear feedback shift register, the usual source of test patterns in hardware.
+/// The same seed always gives the same sequence, and the sequence only repeats after 2^64 - 1
+/// bits.
+#[derive(Debug, Clone)]
+pub struct Lfsr {
+    state: u64,
+}
+
+impl Lfsr {
+    /// Taps for x^64 + x^63 + x^61 + x^60 + 1, which gives a sequence of maximal length
+    const TAPS: u64 = 0xd800_0000_0000_0000;
+
+    pub fn new(seed: u64) -> Self {
+        // an all-zero register would never change
+        Self {
+            state: if seed == 0 { 1 } else { seed },
+        }
+    }
+
+    pub fn next_bit(&mut self) -> bool {
+        let bit = self.state & 1 == 1;
+        self.state >>= 1;
+        if bit {
+            self.state ^= Self::TAPS;
+        }
+        bit
+    }
+
+    /// The next pattern for every input of the interface
+    pub fn next_vector(&mut self, interface: &Interface) -> InputVector {
+        InputVector::from_fn(interface, || self.next_bit())
+    }
+}
+
+/// How each pattern is applied to a chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drive {
+    /// The chip is only evaluated
+    Eval,
+    /// The chip is run for a full clock cycle, so its state carries over to the next pattern
+    Cycle,
+}
+
+/// A pattern which was applied, and the outputs of the chip afterwards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub input: InputVector,
+    pub outputs: Vec<(String, Vec<bool>)>,
+}
+
+impl Display for Sample {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|(name, bits)| format!("{name}={}", show_bits(bits)))
+            .collect::<Vec<_>>();
+        write!(f, "{} -> {}", self.input, outputs.join(" "))
+    }
+}
+
+/// Drives a simulator with `count` patterns from an [`Lfsr`] and records its outputs after each
+/// one. Useful as a quick check of wide chips, whose inputs are too many to try every one.
+pub fn run_patterns(
+    simulator: &mut Simulator,
+    count: usize,
+    seed: u64,
+    drive: Drive,
+) -> Vec<Sample> {
+    let mut lfsr = Lfsr::new(seed);
+    let interface = simulator.interface().clone();
+    (0..count)
+        .map(|_| {
+            let input = lfsr.next_vector(&interface);
+            // the vector is made from the simulator's own interface
+            input.apply(simulator).unwrap();
+            match drive {
+                Drive::Eval => simulator.eval(),
+                Drive::Cycle => simulator.cycle(),
+            };
+            let outputs = interface
+                .outputs()
+                .map(|pin| {
+                    (
+                        pin.name.to_string(),
+                        simulator.get(pin.name).unwrap().to_vec(),
+                    )
+                })
+                .collect();
+            Sample { input, outputs }
+        })
+        .collect()
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PropertyError {
+    #[error("The chip has no {direction} pin `{pin}` of width {width} like the reference")]
+    InterfaceMismatch {
+        pin: String,
+        direction: Direction,
+        width: u16,
+    },
+    #[error(
+        "With {input}, `{pin}` is {} instead of {}",
+        show_bits(found),
+        show_bits(expected)
+    )]
+    Mismatch {
+        input: InputVector,
+        pin: String,
+        expected: Vec<bool>,
+        found: Vec<bool>,
+    },
+}
+
+/// Evaluates `chip` and `reference` on `cases` pseudo-random input vectors, starting from their
+/// initial state each time. On the first difference, the input is shrunk to a minimal vector
+/// which still shows it.
+///
+/// Only combinational behavior is compared, as every vector is evaluated without a clock.
+pub fn check_against(
+    chip: Chip,
+    reference: Chip,
+    cases: usize,
+    seed: u64,
+) -> Result<(), PropertyError> {
+    let mut chip = Simulator::new(chip);
+    let mut reference = Simulator::new(reference);
+    for pin in reference.interface().pins() {
+        if !chip
+            .interface()
+            .pins()
+            .any(|x| x.name == pin.name && x.width == pin.width && x.direction == pin.direction)
+        {
+            return Err(PropertyError::InterfaceMismatch {
+                pin: pin.name.to_string(),
+                direction: pin.direction,
+                width: pin.width,
+            });
+        }
+    }
+
+    let mut rng = StateRng::new(seed);
+    for _ in 0..cases {
+        let input = InputVector::arbitrary(reference.interface(), &mut rng);
+        if let Some(mut error) = compare(&mut chip, &mut reference, &input) {
+            while let Some(simpler) = error_input(&error)
+                .shrink()
+                .into_iter()
+                .find_map(|x| compare(&mut chip, &mut reference, &x))
+            {
+                error = simpler;
+            }
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+fn error_input(error: &PropertyError) -> &InputVector {
+    match error {
+        PropertyError::Mismatch { input, .. } => input,
+        PropertyError::InterfaceMismatch { .. } => unreachable!("interfaces are checked first"),
+    }
+}
+
+/// The first output which differs between the chips for an input
+fn compare(
+    chip: &mut Simulator,
+    reference: &mut Simulator,
+    input: &InputVector,
+) -> Option<PropertyError> {
+    for simulator in [&mut *chip, &mut *reference] {
+        simulator.reset(true);
+        // both interfaces have the pins of the vector, so this cannot fail
+        input.apply(simulator).unwrap();
+        simulator.eval();
+    }
+    reference.interface().outputs().find_map(|pin| {
+        let expected = reference.get(pin.name).unwrap();
+        let found = chip.get(pin.name).unwrap();
+        (expected != found).then(|| PropertyError::Mismatch {
+            input: input.clone(),
+            pin: pin.name.to_string(),
+            expected: expected.to_vec(),
+            found: found.to_vec(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vector(pins: &[(&str, &[bool])]) -> InputVector {
+        InputVector {
+            pins: pins
+                .iter()
+                .map(|(name, bits)| (name.to_string(), bits.to_vec()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_shrink() {
+        let input = vector(&[("a", &[true, true]), ("b", &[true])]);
+        assert_eq!(
+            input.shrink(),
+            [
+                vector(&[("a", &[false, false]), ("b", &[true])]),
+                vector(&[("a", &[false, true]), ("b", &[true])]),
+                vector(&[("a", &[true, false]), ("b", &[true])]),
+                vector(&[("a", &[true, true]), ("b", &[false])]),
+            ]
+        );
+        assert_eq!(vector(&[("a", &[false])]).shrink(), []);
+    }
+
+    #[test]
+    fn test_lfsr() {
+        let bits = |seed| {
+            let mut lfsr = Lfsr::new(seed);
+            (0..64).map(|_| lfsr.next_bit()).collect::<Vec<_>>()
+        };
+        assert_eq!(bits(7), bits(7));
+        assert_ne!(bits(7), bits(8));
+        // zero is not a valid state, and is replaced
+        assert!(bits(0).contains(&true));
+    }
+
+    #[test]
+    fn test_display() {
+        let input = vector(&[("a", &[false, true]), ("sel", &[true])]);
+        assert_eq!(input.to_string(), "a=10 sel=1");
+    }
+}
@@ -0,0 +1,214 @@
+mod common;
+
+use common::{bit, bit_with, builder_with, test_files};
+use hardware_simulator::clock_behavior::ClockBehavior;
+use hardware_simulator::initial_state::InitialState;
+use hardware_simulator::simulator::{Metrics, SimulationError, Simulator};
+use hardware_simulator::test_script::TestRunner;
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+#[test]
+fn tick_tock_bit() {
+    let mut sim = bit();
+    sim.set("in", &[true]).unwrap();
+    sim.set("load", &[true]).unwrap();
+
+    // the new value is latched on the tick, but only shows up after the tock
+    assert_eq!(sim.tick(), &[false]);
+    assert_eq!(sim.clock().to_string(), "0+");
+    assert_eq!(sim.tock(), &[true]);
+    assert_eq!(sim.clock().to_string(), "1");
+
+    // without load, the value is kept
+    sim.set("in", &[false]).unwrap();
+    sim.set("load", &[false]).unwrap();
+    assert_eq!(sim.cycle(), &[true]);
+
+    sim.set("load", &[true]).unwrap();
+    assert_eq!(sim.eval(), &[true]);
+    assert_eq!(sim.cycle(), &[false]);
+    assert_eq!(sim.clock().time(), 3);
+}
+
+#[test]
+fn run_cycles_bit() {
+    let mut sim = bit();
+    sim.set_probes(&["load", "out"]).unwrap();
+    let mut seen = Vec::new();
+    let run = sim.run_cycles(5, |cycle, probes| {
+        seen.push((cycle, probes[0].to_vec(), probes[1].to_vec()));
+        ControlFlow::<()>::Continue(())
+    });
+    assert_eq!(run, ControlFlow::Continue(()));
+    assert_eq!(seen.len(), 5);
+    assert_eq!(seen[4], (4, vec![false], vec![false]));
+    assert_eq!(sim.clock().time(), 5);
+
+    // stops as soon as the bit is set
+    sim.set("in", &[true]).unwrap();
+    sim.set("load", &[true]).unwrap();
+    let run = sim.run_cycles(100, |cycle, probes| {
+        if probes[1] == [true] {
+            ControlFlow::Break(cycle)
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert_eq!(run, ControlFlow::Break(0));
+    assert_eq!(sim.clock().time(), 6);
+
+    assert!(matches!(
+        sim.set_probes(&["nothing"]),
+        Err(SimulationError::UnknownPin { .. })
+    ));
+}
+
+#[test]
+fn drive_bit() {
+    let mut sim = bit();
+    let mut chip = sim.chip().clone();
+    // in, load
+    let inputs = [[true, false], [true, true], [false, false], [false, true]];
+    let outputs = chip.drive(inputs).collect::<Vec<_>>();
+    assert_eq!(outputs, [[false], [true], [true], [false]]);
+
+    // the same as cycling a simulator
+    let cycled = inputs
+        .iter()
+        .map(|x| {
+            sim.set("in", &x[..1]).unwrap();
+            sim.set("load", &x[1..]).unwrap();
+            sim.cycle().to_vec()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(outputs, cycled);
+
+    // the inputs are only taken as the outputs are asked for
+    let mut drive = chip.drive(inputs.iter().take(2));
+    assert_eq!(drive.size_hint(), (2, Some(2)));
+    assert_eq!(drive.next(), Some(vec![false]));
+}
+
+#[test]
+fn clock_frequency() {
+    let mut sim = bit();
+    sim.set_clock_frequency(Some(500.0)).unwrap();
+    assert_eq!(sim.clock_frequency(), Some(500.0));
+    let started = Instant::now();
+    let _ = sim.run_cycles(21, |_, _| ControlFlow::<()>::Continue(()));
+    // the first cycle is due at once
+    assert!(started.elapsed() >= Duration::from_millis(40));
+
+    sim.set_clock_frequency(None).unwrap();
+    let started = Instant::now();
+    let _ = sim.run_cycles(1000, |_, _| ControlFlow::<()>::Continue(()));
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    // a frequency which cannot be kept is refused where it is given
+    for hertz in [0.0, -5.0, f64::NAN, f64::INFINITY, 1e-300] {
+        assert!(matches!(
+            sim.set_clock_frequency(Some(hertz)),
+            Err(SimulationError::BadClockFrequency(_))
+        ));
+        assert!(TestRunner::new(test_files())
+            .with_clock_frequency(hertz)
+            .is_err());
+    }
+    assert_eq!(sim.clock_frequency(), None);
+    assert!(TestRunner::new(test_files())
+        .with_clock_frequency(500.0)
+        .is_ok());
+}
+
+#[test]
+fn reset_bit() {
+    let mut sim = bit();
+    sim.set("in", &[true]).unwrap();
+    sim.set("load", &[true]).unwrap();
+    assert_eq!(sim.cycle(), &[true]);
+
+    sim.reset(true);
+    assert_eq!(sim.outputs(), &[false]);
+    assert_eq!(sim.inputs(), &[false, false]);
+    assert_eq!(sim.clock().time(), 0);
+    sim.set("load", &[true]).unwrap();
+    assert_eq!(sim.cycle(), &[false]);
+}
+
+#[test]
+fn random_initial_state() {
+    let initial = (0..16)
+        .map(|seed| bit_with(InitialState::Random { seed }).outputs()[0])
+        .collect::<Vec<_>>();
+    assert!(initial.contains(&true));
+    assert!(initial.contains(&false));
+
+    let seed = initial.iter().position(|x| *x).unwrap() as u64;
+    let mut sim = bit_with(InitialState::Random { seed });
+    sim.set("load", &[true]).unwrap();
+    assert_eq!(sim.cycle(), &[false]);
+    // resetting brings back the same pattern
+    sim.reset(true);
+    assert_eq!(sim.outputs(), &[true]);
+}
+
+#[test]
+fn set_unknown_pin() {
+    let mut sim = bit();
+    assert!(sim.set("bruh", &[true]).is_err());
+    assert!(sim.set("out", &[true]).is_err());
+    assert!(sim.set("in", &[true, false]).is_err());
+}
+
+#[test]
+fn bit_inputs_are_clocked() {
+    let sim = bit();
+    let clocked = sim
+        .interface()
+        .inputs()
+        .map(|pin| (pin.name, pin.clocked))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        clocked,
+        vec![
+            ("in", ClockBehavior::Sequential),
+            ("load", ClockBehavior::Sequential)
+        ]
+    );
+}
+
+#[test]
+fn metrics() {
+    let mut builder = builder_with(&["Not"]);
+    let mut not = Simulator::new(builder.resolve_chip("Not").unwrap());
+    assert_eq!(not.metrics(), Metrics::default());
+    not.eval();
+    // one pass evaluates the Nand, and a second finds that nothing changed
+    assert_eq!(
+        not.metrics(),
+        Metrics {
+            primitive_evals: 1,
+            traversals: 2,
+            evals: 1,
+            cycles: 0,
+        }
+    );
+
+    let mut sim = bit();
+    sim.set("load", &[true]).unwrap();
+    for _ in 0..3 {
+        sim.cycle();
+    }
+    let metrics = sim.metrics();
+    assert_eq!((metrics.evals, metrics.cycles), (6, 3));
+    assert!(metrics.primitive_evals > 0 && metrics.traversals >= 2 * metrics.evals);
+    sim.reset(false);
+    assert_eq!(sim.metrics().cycles, 3);
+    sim.clear_metrics();
+    assert_eq!(sim.metrics(), Metrics::default());
+
+    let mut nand = Simulator::new(builder.resolve_chip("Nand").unwrap());
+    nand.eval();
+    assert_eq!(nand.metrics().primitive_evals, 1);
+}
@@ -0,0 +1,38 @@
+//! Fixtures shared by the integration tests
+// every test uses only some of them
+#![allow(dead_code, unused_imports)]
+
+mod temp_dir;
+
+pub use temp_dir::TempDir;
+
+use hardware_simulator::initial_state::InitialState;
+use hardware_simulator::model::chip::build_ctx::ChipBuilder;
+use hardware_simulator::simulator::Simulator;
+use std::path::PathBuf;
+
+/// The HDL files, scripts and comparison files of the course which the tests run
+pub fn test_files() -> PathBuf {
+    std::env::current_dir().unwrap().join("../test_files")
+}
+
+/// A builder which has loaded the given chips of the test files
+pub fn builder_with(names: &[&str]) -> ChipBuilder {
+    let mut builder = ChipBuilder::new();
+    for name in names {
+        builder
+            .add_hdl(test_files().join(format!("{name}.hdl")))
+            .unwrap();
+    }
+    builder
+}
+
+/// The course `Bit`, starting at 0
+pub fn bit() -> Simulator {
+    bit_with(InitialState::Zero)
+}
+
+pub fn bit_with(initial_state: InitialState) -> Simulator {
+    let mut builder = builder_with(&["Not", "And", "Or", "Mux", "Bit"]);
+    Simulator::with_initial_state(builder.resolve_chip("Bit").unwrap(), initial_state)
+}
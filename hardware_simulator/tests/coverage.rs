@@ -0,0 +1,119 @@
+mod common;
+
+use common::{builder_with, test_files, TempDir};
+use hardware_simulator::model::chip::fault::StuckAt;
+use hardware_simulator::simulator::Simulator;
+use hardware_simulator::test_script::{
+    fault_coverage, parse_script, toggle_coverage, Limits, TestRunner,
+};
+use std::fs;
+
+fn not() -> Simulator {
+    let mut builder = builder_with(&["Not"]);
+    Simulator::new(builder.resolve_chip("Not").unwrap())
+}
+
+fn stuck(pin: &str, value: bool) -> StuckAt {
+    StuckAt {
+        pin: pin.to_string(),
+        bit: 0,
+        value,
+    }
+}
+
+#[test]
+fn fault_sites() {
+    let sites = not()
+        .chip()
+        .fault_sites()
+        .into_iter()
+        .map(|x| x.pin)
+        .collect::<Vec<_>>();
+    assert_eq!(sites, ["in", "out", "Nand0.a", "Nand0.b", "Nand0.out"]);
+}
+
+#[test]
+fn inject_and_clear() {
+    let mut builder = builder_with(&["Not"]);
+    let mut chip = builder.resolve_chip("Not").unwrap();
+
+    assert!(chip.inject_fault(&stuck("Nand0.a", false)));
+    assert!(!chip.inject_fault(&stuck("Nand1.a", false)));
+    assert!(!chip.inject_fault(&StuckAt {
+        bit: 1,
+        ..stuck("in", false)
+    }));
+    assert_eq!(chip.eval(&[true]), [true]);
+    chip.clear_faults();
+    assert_eq!(chip.eval(&[true]), [false]);
+
+    // a fault on an output of the chip holds it whatever the input
+    assert!(chip.inject_fault(&stuck("out", true)));
+    assert_eq!(chip.eval(&[true]), [true]);
+    assert_eq!(chip.eval(&[false]), [true]);
+}
+
+#[test]
+fn coverage_of_script() {
+    let dir = TempDir::new("test-faults");
+    for file in ["Not.hdl", "Not.tst", "Not.cmp"] {
+        fs::copy(test_files().join(file), dir.join(file)).unwrap();
+    }
+    let report = fault_coverage(dir.join("Not.tst"), Limits::default()).unwrap();
+
+    // holding one input of the Nand at 1 does not change the output of a Not
+    assert_eq!(
+        report.undetected,
+        [stuck("Nand0.a", true), stuck("Nand0.b", true)]
+    );
+    assert_eq!(report.detected.len(), 8);
+    assert_eq!(report.coverage(), 0.8);
+}
+
+#[test]
+fn toggles_of_script() {
+    let dir = TempDir::new("test-toggles");
+    for file in ["Not.hdl", "Not.tst", "Not.cmp"] {
+        fs::copy(test_files().join(file), dir.join(file)).unwrap();
+    }
+    let toggles = toggle_coverage(dir.join("Not.tst"), Limits::default()).unwrap();
+    assert_eq!(toggles.untoggled(), []);
+    assert_eq!(toggles.coverage(), 1.0);
+}
+
+#[test]
+fn untoggled_pins() {
+    let script = parse_script("load Mux.hdl, set sel 0, set a 1, eval, set a 0, eval;").unwrap();
+    let mut runner = TestRunner::new(test_files()).with_toggle_tracking();
+    runner.run(&script).unwrap();
+    let toggles = runner.simulator().unwrap().toggles().unwrap();
+    let untoggled = toggles.untoggled();
+    assert!(untoggled.contains(&("b", 0)));
+    assert!(untoggled.contains(&("sel", 0)));
+    assert!(!untoggled.contains(&("a", 0)));
+    assert!(!untoggled.contains(&("out", 0)));
+    assert!(toggles
+        .summary()
+        .starts_with("b: 0 (always 0)\nsel: 0 (always 0)\n"));
+}
+
+#[test]
+fn fault_settles() {
+    let mut builder = builder_with(&["Not", "And", "Or", "Mux", "Bit"]);
+    let healthy = builder.resolve_chip("Bit").unwrap();
+    let mut faulty = healthy.clone();
+    // the Mux drives 1 onto an input held at 0
+    assert!(faulty.inject_fault(&stuck("DFF0.in", false)));
+
+    let traversals = |chip| {
+        let mut sim = Simulator::new(chip);
+        sim.set("in", &[true]).unwrap();
+        sim.set("load", &[true]).unwrap();
+        for _ in 0..4 {
+            sim.eval();
+        }
+        sim.metrics().traversals
+    };
+    // a held input settles like any other, so it never needs more passes
+    assert!(traversals(faulty) <= traversals(healthy));
+}
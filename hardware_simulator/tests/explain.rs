@@ -0,0 +1,63 @@
+mod common;
+
+use common::test_files;
+use hardware_simulator::test_script::{parse_script, TestRunner};
+
+#[test]
+fn explain_output() {
+    let script = parse_script("load Mux.hdl, set a 1, set b 0, set sel 0, eval;").unwrap();
+    let mut runner = TestRunner::new(test_files());
+    runner.run(&script).unwrap();
+    let chip = runner.simulator().unwrap().chip();
+
+    let influences = chip.explain("out", 0).unwrap();
+    let lines = influences
+        .iter()
+        .map(|x| format!("{}{x}", "  ".repeat(x.depth)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines[..4],
+        [
+            "out[0] = 1",
+            "  Or0.out[0] = 1",
+            "    Or0.a[0] = 1",
+            "    Or0.b[0] = 0"
+        ]
+    );
+    let inputs = influences
+        .iter()
+        .filter(|x| !x.pin.contains('.') && x.pin != "out")
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(inputs, ["a[0] = 1", "b[0] = 0", "sel[0] = 0"]);
+
+    // a pin inside a part is explained within that part
+    let inside = chip.explain("Not0.Nand0.out", 0).unwrap();
+    assert_eq!(inside[0].to_string(), "Not0.Nand0.out[0] = 1");
+    assert!(inside.iter().any(|x| x.pin == "Not0.in"));
+    assert!(chip.explain("out", 1).is_none());
+    assert!(chip.explain("Xor0.out", 0).is_none());
+}
+
+#[test]
+fn explain_single_bit() {
+    let script = parse_script("load Mux16.hdl, set a %XFFFF, set sel 0, eval;").unwrap();
+    let mut runner = TestRunner::new(test_files());
+    runner.run(&script).unwrap();
+    let influences = runner
+        .simulator()
+        .unwrap()
+        .chip()
+        .explain("out", 3)
+        .unwrap();
+    let inputs = influences
+        .iter()
+        .filter(|x| !x.pin.contains('.') && x.pin != "out")
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    // only the matching bits of the buses are involved
+    assert_eq!(inputs.len(), 3);
+    for input in ["a[3] = 1", "b[3] = 0", "sel[0] = 0"] {
+        assert!(inputs.contains(&input.to_string()), "{inputs:?}");
+    }
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::bit;
+use hardware_simulator::handle::SimulatorHandle;
+use hardware_simulator::model::chip::build_ctx::ChipBuilder;
+use hardware_simulator::simulator::{ProgramReset, SimulationError, Simulator};
+
+#[test]
+fn commands() {
+    let handle = SimulatorHandle::spawn(bit());
+    handle.set("in", &[true]).unwrap();
+    handle.set("load", &[true]).unwrap();
+    assert_eq!(handle.step(), [true]);
+    assert_eq!(handle.probe("out"), Ok(vec![true]));
+    assert!(matches!(
+        handle.set("out", &[false]),
+        Err(SimulationError::NotAnInput(_))
+    ));
+
+    handle.run(Some(10));
+    // commands are handled between the cycles of a run
+    assert_eq!(handle.probe("in"), Ok(vec![true]));
+    let paused = handle.pause();
+    assert!((1..=11).contains(&paused));
+    assert_eq!(handle.pause(), paused);
+
+    handle.run(None);
+    handle.set("in", &[false]).unwrap();
+    assert!(handle.pause() >= paused);
+    assert_eq!(handle.step(), [false]);
+
+    let simulator = handle.join();
+    assert_eq!(simulator.get("in"), Ok(&[false][..]));
+}
+
+#[test]
+fn swap_program() {
+    let mut builder = ChipBuilder::new();
+    builder
+        .update_source(
+            "Machine.hdl",
+            "\
+CHIP Machine {
+    IN pc[15], in[16], load, address[3];
+    OUT instruction[16], out[16];
+    PARTS:
+    ROM32K(address=pc, out=instruction);
+    RAM8(in=in, load=load, address=address, out=out);
+}",
+        )
+        .unwrap();
+    let mut simulator = Simulator::new(builder.resolve_chip("Machine").unwrap());
+    let word = |x: u16| (0..16).map(|i| x >> i & 1 == 1).collect::<Vec<_>>();
+    simulator.load_memory("ROM32K", &[1, 2]);
+    simulator.set("in", &word(42)).unwrap();
+    simulator.set("load", &[true]).unwrap();
+    simulator.cycle();
+
+    let handle = SimulatorHandle::spawn(simulator);
+    handle.set("load", &[false]).unwrap();
+    handle.run(None);
+    assert_eq!(handle.swap_program(&[3, 4], ProgramReset::Nothing), 1);
+    handle.pause();
+    // the RAM and the inputs are kept
+    assert_eq!(handle.probe("instruction"), Ok(word(3)));
+    assert_eq!(handle.probe("out"), Ok(word(42)));
+
+    handle.swap_program(&[5], ProgramReset::KeepMemory);
+    assert_eq!(handle.probe("instruction"), Ok(word(5)));
+    assert_eq!(handle.probe("in"), Ok(word(0)));
+    assert_eq!(handle.probe("out"), Ok(word(42)));
+
+    handle.swap_program(&[6], ProgramReset::Everything);
+    assert_eq!(handle.probe("instruction"), Ok(word(6)));
+    assert_eq!(handle.probe("out"), Ok(word(0)));
+}
@@ -1,15 +1,16 @@
+mod common;
+
+use common::test_files;
 use hardware_simulator::model::chip::build_ctx::ChipBuilder;
 
 #[test]
 fn load_step_not() {
-
-    let hdl_dir = std::env::current_dir().unwrap().join("../test_files");
-    let not_file = hdl_dir.join("Not.hdl");
+    let not_file = test_files().join("Not.hdl");
 
     let mut builder = ChipBuilder::new();
-    builder.add_hdl(not_file);
+    builder.add_hdl(not_file).unwrap();
     let mut chip = builder.resolve_chip("Not").unwrap();
 
-    chip.eval(&[false]);
-
-}
\ No newline at end of file
+    assert_eq!(chip.eval(&[false]), vec![true]);
+    assert_eq!(chip.eval(&[true]), vec![false]);
+}
@@ -0,0 +1,49 @@
+use hardware_simulator::model::ast::Form;
+use hardware_simulator::parse_hdl;
+
+#[test]
+fn parse_several_chips() {
+    let source = "\
+/** Not of its input */
+CHIP Not {
+    IN in;
+    OUT out;
+    PARTS:
+    Nand(a=in, b=in, out=out);
+}
+
+// a flip flop
+CHIP DFF {
+    IN in;
+    OUT out;
+    BUILTIN DFF;
+    CLOCKED in;
+}
+";
+    let chips = parse_hdl(source).unwrap();
+    let names = chips.iter().map(|x| *x.name).collect::<Vec<_>>();
+    assert_eq!(names, ["Not", "DFF"]);
+    assert_eq!(chips[0].doc().as_deref(), Some("Not of its input"));
+    assert_eq!(chips[0].parts(), ["Nand"]);
+    assert!(matches!(chips[1].logic, Form::Builtin(_)));
+
+    let interface = chips[1].interface();
+    assert_eq!(
+        interface.to_string(),
+        "CHIP DFF { IN in; OUT out; CLOCKED in; }"
+    );
+}
+
+#[test]
+fn parse_errors() {
+    let source = "CHIP Not { IN in; OUT out; PARTS: Nand(a=in, b=in, out=out); }\nCHIP Bad {";
+    let diagnostics = parse_hdl(source).unwrap_err();
+    let [diagnostic] = diagnostics.0.as_slice() else {
+        panic!("{diagnostics:?}");
+    };
+    assert_eq!(diagnostic.code, "E0102");
+    assert_eq!(diagnostic.range, Some(source.len()..source.len()));
+    assert_eq!(diagnostics.to_string(), "error[E0102]: expected \"IN\"");
+
+    assert_eq!(parse_hdl("").unwrap_err().0[0].code, "E0102");
+}
@@ -0,0 +1,564 @@
+mod common;
+
+use common::{test_files, TempDir};
+use hardware_simulator::grade::{grade_batch, grade_with};
+use hardware_simulator::image::{read_image, write_image, ImageError};
+use hardware_simulator::model::dialect::Dialect;
+use hardware_simulator::test_script::{
+    find_golden, parse_script, run_golden, run_script, Limits, ScriptErrorKind, StepResult,
+    TestRunner,
+};
+use std::fs;
+
+#[test]
+fn expect_passes() {
+    let script = parse_script(
+        "\
+load Not.hdl,
+echo \"Checking Not\",
+set in 0, eval, expect out 1;
+set in 1, eval, expect out 0;",
+    )
+    .unwrap();
+    let mut runner = TestRunner::new(test_files());
+    runner.run(&script).unwrap();
+    assert_eq!(runner.echo(), Some("Checking Not"));
+
+    runner.run(&parse_script("clear-echo;").unwrap()).unwrap();
+    assert_eq!(runner.echo(), None);
+}
+
+#[test]
+fn expect_fails_at_command() {
+    let script = parse_script(
+        "\
+load Mux16.hdl,
+set a %XFFFF, set sel 0, eval,
+    expect out 0;",
+    )
+    .unwrap();
+    let error = TestRunner::new(test_files()).run(&script).unwrap_err();
+    assert_eq!((error.line, error.column), (3, 5));
+    assert!(matches!(
+        error.kind,
+        ScriptErrorKind::ExpectationFailed {
+            expected: 0,
+            found: -1,
+            ..
+        }
+    ));
+    assert_eq!(error.to_string(), "3:5: Pin `out` is not 0, but -1");
+}
+
+/// A copy of the test files, so that output files are not written into the repository
+fn scratch_copy(name: &str) -> TempDir {
+    let dir = TempDir::new(&format!("test-{name}"));
+    for entry in fs::read_dir(test_files()).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            continue;
+        }
+        fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+    }
+    dir
+}
+
+// chips which are still left as exercises in the test files
+const UNIMPLEMENTED: &[&str] = &["Not16", "Or8Way", "Xor"];
+
+#[test]
+fn course_scripts_pass() {
+    let dir = scratch_copy("course");
+    let mut failures = Vec::new();
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_stem().unwrap().to_string_lossy().to_string();
+        if path.extension().is_none_or(|x| x != "tst") || UNIMPLEMENTED.contains(&name.as_str()) {
+            continue;
+        }
+        match run_script(&path) {
+            Ok(output) => {
+                // the output file holds the same table as the one returned
+                let written = fs::read_to_string(path.with_extension("out")).unwrap();
+                assert_eq!(written, output, "{path:?}");
+            }
+            Err(e) => failures.push(format!("{path:?}: {e}")),
+        }
+    }
+    assert!(failures.is_empty(), "{failures:#?}");
+}
+
+#[test]
+fn comparison_failure() {
+    let dir = scratch_copy("compare");
+    let cmp = fs::read_to_string(dir.join("Not.cmp")).unwrap();
+    fs::write(
+        dir.join("Not.cmp"),
+        cmp.replace("|   1   |   0   |", "|   1   |   1   |"),
+    )
+    .unwrap();
+    let error = run_script(dir.join("Not.tst")).unwrap_err();
+    assert_eq!((error.line, error.column), (17, 1));
+    assert!(matches!(
+        error.kind,
+        ScriptErrorKind::ComparisonFailure { line: 3, .. }
+    ));
+}
+
+#[test]
+fn load_program_into_rom() {
+    let dir = scratch_copy("rom");
+    fs::write(
+        dir.join("Fetch.hdl"),
+        "\
+CHIP Fetch {
+    IN pc[15];
+    OUT instruction[16];
+    PARTS:
+    ROM32K(address=pc, out=instruction);
+}",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("Prog.hack"),
+        "0000000000000111\n1110110000010000\n",
+    )
+    .unwrap();
+    fs::write(dir.join("Bad.hack"), "0000000000000111\n12\n").unwrap();
+
+    let mut runner = TestRunner::new(&*dir);
+    let script = "\
+load Fetch.hdl,
+ROM32K load Prog.hack,
+set pc 1, eval, expect instruction %B1110110000010000;
+load Prog.hack,
+set pc 0, eval, expect instruction 7;";
+    runner.run(&parse_script(script).unwrap()).unwrap();
+
+    let error = runner
+        .run(&parse_script("load Bad.hack;").unwrap())
+        .unwrap_err();
+    assert!(matches!(error.kind, ScriptErrorKind::BadProgram(2)));
+    let error = runner
+        .run(&parse_script("load Not.hdl, load Prog.hack;").unwrap())
+        .unwrap_err();
+    assert!(matches!(error.kind, ScriptErrorKind::NoMemory(_)));
+
+    // binary images load the same program
+    write_image(dir.join("Prog.bin"), &[7, 0b1110110000010000]).unwrap();
+    fs::write(dir.join("Odd.bin"), [0, 7, 1]).unwrap();
+    let script = "\
+load Fetch.hdl,
+ROM32K load Prog.bin,
+set pc 1, eval, expect instruction %B1110110000010000;";
+    runner.run(&parse_script(script).unwrap()).unwrap();
+    let error = runner
+        .run(&parse_script("load Odd.bin;").unwrap())
+        .unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ScriptErrorKind::BadImage(ImageError::Truncated)
+    ));
+}
+
+#[test]
+fn dump_and_load_memory() {
+    let dir = scratch_copy("ram");
+    fs::write(
+        dir.join("Store.hdl"),
+        "\
+CHIP Store {
+    IN in[16], load, address[3];
+    OUT out[16];
+    PARTS:
+    RAM8(in=in, load=load, address=address, out=out);
+}",
+    )
+    .unwrap();
+    let script = "\
+load Store.hdl,
+set in 5, set load 1, set address 3, tick, tock,
+RAM8 dump All.hack,
+RAM8 dump Part.bin 2 4,
+RAM8 load Part.bin 6,
+set load 0, set address 7, eval, expect out 5,
+set address 3, eval, expect out 5;";
+    TestRunner::new(&*dir)
+        .run(&parse_script(script).unwrap())
+        .unwrap();
+    let all = read_image(dir.join("All.hack")).unwrap();
+    assert_eq!(all, [0, 0, 0, 5, 0, 0, 0, 0]);
+    assert_eq!(read_image(dir.join("Part.bin")).unwrap(), [0, 5]);
+
+    // dumps are files next to the script, which are left alone without output files
+    let script = parse_script("load Store.hdl, RAM8 dump Skipped.hack;").unwrap();
+    TestRunner::new(&*dir)
+        .without_output_file()
+        .run(&script)
+        .unwrap();
+    assert!(!dir.join("Skipped.hack").exists());
+    let error = TestRunner::new(&*dir)
+        .run(&parse_script("load Store.hdl, ROM32K dump Rom.hack;").unwrap())
+        .unwrap_err();
+    assert!(matches!(error.kind, ScriptErrorKind::NoMemory(_)));
+}
+
+#[test]
+fn memory_words() {
+    let dir = scratch_copy("poke");
+    fs::write(
+        dir.join("Store.hdl"),
+        "\
+CHIP Store {
+    IN in[16], load, address[3];
+    OUT out[16];
+    PARTS:
+    RAM8(in=in, load=load, address=address, out=out);
+}",
+    )
+    .unwrap();
+    let script = "\
+load Store.hdl,
+output-list RAM8[2]%D1.3.1 out%D1.3.1;
+set RAM8[2] -1, set address 2, eval, output;
+expect out -1, expect RAM8[2] %XFFFF;";
+    let mut runner = TestRunner::new(&*dir);
+    runner.run(&parse_script(script).unwrap()).unwrap();
+    assert_eq!(runner.output().lines().last(), Some("|  -1 |  -1 |"));
+    assert_eq!(runner.simulator().unwrap().peek("RAM8", 2), Some(0xffff));
+
+    let error = runner
+        .run(&parse_script("load Store.hdl, set RAM8[8] 1;").unwrap())
+        .unwrap_err();
+    assert!(matches!(error.kind, ScriptErrorKind::NoMemoryWord(_)));
+    let error = runner
+        .run(&parse_script("load Store.hdl,\nset RAM8[0] 70000;").unwrap())
+        .unwrap_err();
+    assert_eq!((error.line, error.column), (2, 1));
+    assert_eq!(
+        error.to_string(),
+        "2:1: 70000 does not fit in the 16 bits of `RAM8[0]`"
+    );
+}
+
+#[test]
+fn values_out_of_range() {
+    let run = |script: &str| TestRunner::new(test_files()).run(&parse_script(script).unwrap());
+    // set as unsigned numbers or in two's complement
+    run("load Mux.hdl, set sel 1, set a -1, eval, expect out 0;").unwrap();
+    run("load Mux16.hdl, set a %XFFFF, set b -32768, set sel 1, eval, expect out -32768;").unwrap();
+
+    for (script, value, width) in [
+        ("load Mux.hdl,\n  set sel 2;", 2, 1),
+        ("load Mux.hdl,\n  set a -2;", -2, 1),
+        ("load Mux16.hdl,\n  set a 65536;", 65536, 16),
+        ("load Mux16.hdl,\n  set b -32769;", -32769, 16),
+    ] {
+        let error = run(script).unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3), "{script}");
+        let ScriptErrorKind::ValueTooWide {
+            value: found,
+            width: bits,
+            ..
+        } = error.kind
+        else {
+            panic!("{error}");
+        };
+        assert_eq!((found, bits), (value, width));
+    }
+}
+
+#[test]
+fn step_through_script() {
+    let script = parse_script(
+        "\
+load Bit.hdl,
+output-list time%S1.4.1 out%B1.1.1;
+set in 1, set load 1;
+repeat 2 {
+    tick, tock, output;
+}
+repeat 0 { eval; }",
+    )
+    .unwrap();
+    let mut runner = TestRunner::new(test_files());
+    runner.start(&script);
+
+    let mut lines = Vec::new();
+    while let Some(command) = runner.current() {
+        lines.push(command.line);
+        assert!(matches!(runner.step().unwrap(), StepResult::Ran(_)));
+        if lines.len() == 7 {
+            // the first row is visible before the rest of the loop has run
+            assert_eq!(runner.output().lines().count(), 2);
+        }
+    }
+    assert_eq!(lines, vec![1, 2, 3, 3, 5, 5, 5, 5, 5, 5]);
+    assert_eq!(runner.step().unwrap(), StepResult::Finished);
+    assert_eq!(
+        runner.output(),
+        "| time |out|\n| 1    | 1 |\n| 2    | 1 |\n"
+    );
+}
+
+#[test]
+fn cycle_limit() {
+    let script = parse_script(
+        "\
+load Bit.hdl,
+repeat {
+    tick, tock;
+}",
+    )
+    .unwrap();
+    let error = TestRunner::new(test_files())
+        .with_limits(Limits {
+            cycles: Some(10),
+            time: None,
+        })
+        .run(&script)
+        .unwrap_err();
+    assert!(matches!(error.kind, ScriptErrorKind::CycleLimit(10)));
+}
+
+#[test]
+fn halt_condition() {
+    let script = parse_script(
+        "\
+load Bit.hdl,
+set in 1, set load 1,
+repeat {
+    tick, tock;
+}",
+    )
+    .unwrap();
+    let mut runner = TestRunner::new(test_files())
+        .with_halt_condition(|simulator| simulator.get("out") == Ok(&[true][..]));
+    runner.run(&script).unwrap();
+    let simulator = runner.simulator().unwrap();
+    assert_eq!(simulator.clock().time(), 1);
+    assert_eq!(runner.step().unwrap(), StepResult::Finished);
+}
+
+#[test]
+fn pause_and_resume() {
+    let script = parse_script("load Bit.hdl, set in 1, set load 1, tick, tock;").unwrap();
+    let mut runner = TestRunner::new(test_files());
+    let control = runner.control();
+    runner.start(&script);
+    assert!(matches!(runner.step().unwrap(), StepResult::Ran(_)));
+
+    control.pause();
+    assert_eq!(runner.step().unwrap(), StepResult::Paused);
+    assert_eq!(runner.proceed().unwrap(), StepResult::Paused);
+    assert_eq!(runner.current().unwrap().line, 1);
+
+    // a control can be used from another thread
+    std::thread::spawn(move || control.resume()).join().unwrap();
+    assert_eq!(runner.proceed().unwrap(), StepResult::Finished);
+    assert_eq!(runner.simulator().unwrap().outputs(), [true]);
+}
+
+#[test]
+fn batch_grading() {
+    let dir = TempDir::new("test-batch");
+    for (submission, expected) in [("alice", "0"), ("bob", "1")] {
+        let submission = dir.join(submission);
+        fs::create_dir_all(&submission).unwrap();
+        fs::copy(test_files().join("Not.hdl"), submission.join("Not.hdl")).unwrap();
+        fs::write(
+            submission.join("Not.tst"),
+            format!(
+                "load Not.hdl, output-file Not.out, output-list in%B3.1.3 out%B3.1.3;\n\
+                 set in 1, eval, output, expect out {expected};"
+            ),
+        )
+        .unwrap();
+    }
+    // a broken manifest only stops its own submission from being graded
+    let broken = dir.join("carol");
+    fs::create_dir_all(&broken).unwrap();
+    fs::write(broken.join("hdl.toml"), "chips = hdl").unwrap();
+    let report = grade_batch(&dir, Limits::default(), 2).unwrap();
+    let names = report
+        .submissions
+        .iter()
+        .map(|x| (x.name.as_str(), x.passed(), x.failed()))
+        .collect::<Vec<_>>();
+    assert_eq!(names, [("alice", 1, 0), ("bob", 0, 1), ("carol", 0, 1)]);
+    assert_eq!(report.failed(), 2);
+    let error = report.submissions[2].error.as_ref().unwrap();
+    assert!(error.code.starts_with("E06"), "{error:?}");
+    // grading leaves the submissions as they were
+    assert_eq!(fs::read_dir(dir.join("alice")).unwrap().count(), 2);
+    assert!(report
+        .summary()
+        .starts_with("submission |     time | result\n"));
+}
+
+#[test]
+fn project_manifest() {
+    let dir = TempDir::new("test-manifest");
+    fs::create_dir_all(dir.join("hdl")).unwrap();
+    fs::create_dir_all(dir.join("tests")).unwrap();
+    fs::copy(test_files().join("Not.hdl"), dir.join("hdl/Not.hdl")).unwrap();
+    // a DFF which is really an inverter, used by Top only when HDL is preferred
+    fs::write(
+        dir.join("hdl/DFF.hdl"),
+        "CHIP DFF { IN in; OUT out; PARTS: Not(in=in, out=out); }",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("hdl/Top.hdl"),
+        "CHIP Top { IN in; OUT out; PARTS: DFF(in=in, out=out); }",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("tests/Top.tst"),
+        "load Top.hdl, set in 0, eval, expect out 1;",
+    )
+    .unwrap();
+    // outside of the test directories, so never run
+    fs::write(dir.join("Stray.tst"), "load Missing.hdl;").unwrap();
+
+    let mut results = Vec::new();
+    for manifest in [
+        "chips = [\"hdl\"]\ntests = [\"tests\"]\nprefer = \"hdl\"",
+        "chips = [\"hdl\"]\ntests = [\"tests\"]\nprefer = \"hdl\"\nbuiltins = [\"DFF\"]",
+    ] {
+        fs::write(dir.join("hdl.toml"), manifest).unwrap();
+        let report = grade_with(&dir, Limits::default()).unwrap();
+        let names = report
+            .scripts
+            .iter()
+            .map(|x| x.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Top"]);
+        results.push(report.passed());
+    }
+    fs::write(dir.join("hdl.toml"), "chips = hdl").unwrap();
+    let broken = grade_with(&dir, Limits::default());
+    assert_eq!(results, [1, 0]);
+    assert!(broken.is_err());
+}
+
+#[test]
+fn golden_outputs() {
+    let dir = scratch_copy("golden");
+    let scripts = find_golden(&dir, &test_files().join("golden")).unwrap();
+    assert!(scripts.len() > 10);
+    for (script, golden) in scripts {
+        let diffs = run_golden(&script, &golden).unwrap();
+        assert!(diffs.is_empty(), "{script:?}: {diffs:#?}");
+    }
+}
+
+#[test]
+fn differential_against_builtin() {
+    // a flip-flop which passes its input straight through, instead of waiting for the clock
+    let dir = TempDir::new("test-differential");
+    fs::copy(test_files().join("Not.hdl"), dir.join("Not.hdl")).unwrap();
+    fs::write(
+        dir.join("DFF.hdl"),
+        "CHIP DFF { IN in; OUT out; PARTS: Not(in=in, out=x); Not(in=x, out=out); }",
+    )
+    .unwrap();
+    let script = parse_script("load DFF.hdl, set in 0, tick, tock, set in 1, eval;").unwrap();
+
+    // without the mode, the builtin shadows the chip
+    TestRunner::new(&*dir).run(&script).unwrap();
+    let error = TestRunner::new(&*dir)
+        .with_differential()
+        .run(&script)
+        .unwrap_err();
+
+    assert_eq!((error.line, error.column), (1, 47));
+    let ScriptErrorKind::ReferenceMismatch { chip, dump } = error.kind else {
+        panic!("{error}");
+    };
+    assert_eq!(chip, "DFF");
+    assert_eq!(dump, "  in = 1 (builtin: 1)\n* out = 1 (builtin: 0)\n");
+}
+
+#[test]
+fn preprocessor_directives() {
+    let dir = TempDir::new("test-preprocess");
+    fs::write(
+        dir.join("pins.hdl"),
+        "//! define WIDTH 2\nIN a[WIDTH], b[WIDTH];\nOUT out[WIDTH];",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("Nand2.hdl"),
+        "\
+CHIP Nand2 {
+    //! include \"pins.hdl\"
+    PARTS:
+    Nand(a=a[0], b=b[0], out=out[0]);
+    Nand(a=a[1], b=b[1], out=out[1]);
+}",
+    )
+    .unwrap();
+    let script =
+        parse_script("load Nand2.hdl, set a %B11, set b %B01, eval, expect out 2;").unwrap();
+
+    // the course HDL reads directives as comments, so the chip has no pins
+    let strict = TestRunner::new(&*dir).run(&script);
+    let extended = TestRunner::new(&*dir)
+        .with_dialect(Dialect::Extended)
+        .run(&script);
+
+    assert!(matches!(
+        strict.unwrap_err().kind,
+        ScriptErrorKind::LoadError(_)
+    ));
+    extended.unwrap();
+}
+
+#[test]
+fn generic_chips() {
+    let dir = TempDir::new("test-generic");
+    for chip in ["Not", "And", "And16"] {
+        fs::copy(
+            test_files().join(format!("{chip}.hdl")),
+            dir.join(format!("{chip}.hdl")),
+        )
+        .unwrap();
+    }
+    let files = [
+        (
+            "AndN.hdl",
+            "CHIP AndN<N> { IN a[N], b[N]; OUT out[N]; \
+             PARTS: And16(a[0..N-1]=a, b[0..N-1]=b, out[0..N-1]=out); }",
+        ),
+        (
+            "Pair.hdl",
+            "CHIP Pair<N> { IN a[N*2], b[N*2]; OUT out[N*2]; PARTS: \
+             AndN<N>(a=a[0..N-1], b=b[0..N-1], out=out[0..N-1]); \
+             AndN<N>(a=a[N..N*2-1], b=b[N..N*2-1], out=out[N..N*2-1]); }",
+        ),
+        (
+            "Top.hdl",
+            "CHIP Top { IN a[4], b[4]; OUT out[4]; PARTS: Pair<2>(a=a, b=b, out=out); }",
+        ),
+    ];
+    for (name, source) in files {
+        fs::write(dir.join(name), source).unwrap();
+    }
+    let run = |script: &str| {
+        TestRunner::new(&*dir)
+            .with_dialect(Dialect::Extended)
+            .run(&parse_script(script).unwrap())
+    };
+    let top = run("load Top.hdl, set a %B1100, set b %B1010, eval, expect out %B1000;");
+    let template = run("load AndN.hdl;");
+    let strict = TestRunner::new(&*dir).run(&parse_script("load Top.hdl;").unwrap());
+
+    top.unwrap();
+    assert_eq!(
+        template.unwrap_err().kind.to_string(),
+        "Could not load the chip: Chip `AndN` has width parameters, and can only be used as a \
+         part such as `AndN<16>`"
+    );
+    assert!(strict.is_err());
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{test_files, TempDir};
+use hardware_simulator::test_script::{parse_script, TestRunner};
+use hardware_simulator::trace::diff_traces;
+use std::fs;
+
+#[test]
+fn first_divergence() {
+    // a register which ignores `load`, and so differs once it should keep its value
+    let dir = TempDir::new("test-trace");
+    fs::write(
+        dir.join("Bit.hdl"),
+        "CHIP Bit { IN in, load; OUT out; PARTS: DFF(in=in, out=out); }",
+    )
+    .unwrap();
+
+    let script = parse_script(
+        "\
+load Bit.hdl,
+set in 1, set load 1, tick, tock,
+set in 0, set load 0, tick, tock,
+set in 1, tick, tock;",
+    )
+    .unwrap();
+    let mut traces = [test_files(), dir.to_path_buf()].map(|dir| {
+        let mut runner = TestRunner::new(dir).with_trace();
+        runner.run(&script).unwrap();
+        runner.trace().unwrap().clone()
+    });
+
+    assert_eq!(traces[0].signals, ["in", "load", "out"]);
+    assert_eq!(traces[0].steps.len(), 6);
+    assert_eq!(diff_traces(&traces[0], &traces[0], 2), None);
+
+    let divergence = diff_traces(&traces[0], &traces[1], 2).unwrap();
+    assert_eq!((divergence.step, divergence.time.as_str()), (3, "2"));
+    assert_eq!(divergence.signals.len(), 1);
+    assert_eq!(divergence.signals[0].signal, "out");
+    assert_eq!(divergence.context.first().unwrap().step, 1);
+    assert_eq!(divergence.context.last().unwrap().step, 5);
+
+    // a trace which stops early diverges where it ends
+    traces[1].steps.truncate(2);
+    assert_eq!(diff_traces(&traces[0], &traces[1], 0).unwrap().step, 2);
+}
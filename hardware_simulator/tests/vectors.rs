@@ -0,0 +1,75 @@
+mod common;
+
+use common::{builder_with, TempDir};
+use hardware_simulator::model::chip::build_ctx::ChipBuilder;
+use hardware_simulator::simulator::Simulator;
+use hardware_simulator::vectors::{check_against, run_patterns, Drive, PropertyError};
+use std::fs;
+
+/// Loads the course `And` and `Not`, and a chip with the interface of `Nand` from the given parts
+fn nand_from(name: &str, parts: &str) -> ChipBuilder {
+    let mut builder = builder_with(&["Not", "And"]);
+    let dir = TempDir::new(&format!("test-{name}"));
+    let path = dir.join(format!("{name}.hdl"));
+    fs::write(
+        &path,
+        format!("CHIP {name} {{ IN a, b; OUT out; PARTS: {parts} }}"),
+    )
+    .unwrap();
+    builder.add_hdl(&path).unwrap();
+    builder
+}
+
+#[test]
+fn matches_builtin() {
+    let mut builder = nand_from("MyNand", "And(a=a, b=b, out=x); Not(in=x, out=out);");
+    let chip = builder.resolve_chip("MyNand").unwrap();
+    let reference = builder.resolve_chip("Nand").unwrap();
+    check_against(chip, reference, 64, 1).unwrap();
+}
+
+#[test]
+fn shrinks_mismatch() {
+    // only differs from `Nand` when `a` is set and `b` is not
+    let mut builder = nand_from("BadNand", "Not(in=a, out=out);");
+    let chip = builder.resolve_chip("BadNand").unwrap();
+    let reference = builder.resolve_chip("Nand").unwrap();
+    let error = check_against(chip, reference, 64, 1).unwrap_err();
+    assert!(matches!(error, PropertyError::Mismatch { .. }));
+    assert_eq!(error.to_string(), "With a=1 b=0, `out` is 0 instead of 1");
+}
+
+#[test]
+fn random_patterns() {
+    let mut builder = builder_with(&["Mux16"]);
+    let mut simulator = Simulator::new(builder.resolve_chip("Mux16").unwrap());
+
+    let samples = run_patterns(&mut simulator, 32, 5, Drive::Eval);
+    assert_eq!(samples, run_patterns(&mut simulator, 32, 5, Drive::Eval));
+    for sample in samples {
+        let pin = |name: &str| {
+            let (_, bits) = sample.input.pins.iter().find(|(x, _)| x == name).unwrap();
+            bits.clone()
+        };
+        let expected = if pin("sel")[0] { pin("b") } else { pin("a") };
+        assert_eq!(sample.outputs, [("out".to_string(), expected)], "{sample}");
+    }
+}
+
+#[test]
+fn random_patterns_with_clock() {
+    let mut builder = builder_with(&["Bit"]);
+    let mut simulator = Simulator::new(builder.resolve_chip("Bit").unwrap());
+
+    // the register follows its input whenever it is loaded
+    let mut stored = false;
+    for sample in run_patterns(&mut simulator, 32, 9, Drive::Cycle) {
+        let [(_, input), (_, load)] = sample.input.pins.as_slice() else {
+            panic!("{sample}");
+        };
+        if load[0] {
+            stored = input[0];
+        }
+        assert_eq!(sample.outputs[0].1, [stored], "{sample}");
+    }
+}